@@ -1,3 +0,0 @@
-CREATE TABLE meetings (...);
-CREATE TABLE transcript_chunks (...);
--- See schema section above
\ No newline at end of file
@@ -0,0 +1,52 @@
+-- Meetings, what was said in them and what the copilot made of it.
+-- Timestamps are RFC 3339 in UTC; times within a meeting are seconds since
+-- listening started.
+
+CREATE TABLE meetings (
+    id TEXT PRIMARY KEY NOT NULL,
+    title TEXT NOT NULL DEFAULT '',
+    started_at TEXT NOT NULL,
+    ended_at TEXT
+);
+
+-- Final transcripts only; partial hypotheses are never stored
+CREATE TABLE transcript_chunks (
+    id INTEGER PRIMARY KEY,
+    meeting_id TEXT NOT NULL REFERENCES meetings(id) ON DELETE CASCADE,
+    utterance_id INTEGER NOT NULL,
+    start_time REAL NOT NULL,
+    end_time REAL NOT NULL,
+    text TEXT NOT NULL,
+    speaker TEXT,
+    source TEXT NOT NULL DEFAULT '',
+    source_role TEXT NOT NULL DEFAULT 'mixed',
+    language TEXT NOT NULL DEFAULT '',
+    confidence REAL NOT NULL,
+    -- JSON array of word timings
+    words TEXT NOT NULL DEFAULT '[]',
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX transcript_chunks_by_meeting ON transcript_chunks (meeting_id, start_time);
+
+CREATE TABLE suggestions (
+    id TEXT PRIMARY KEY NOT NULL,
+    meeting_id TEXT NOT NULL REFERENCES meetings(id) ON DELETE CASCADE,
+    suggestion_type TEXT NOT NULL,
+    content TEXT NOT NULL,
+    confidence REAL NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX suggestions_by_meeting ON suggestions (meeting_id, created_at);
+
+CREATE TABLE action_items (
+    id INTEGER PRIMARY KEY,
+    meeting_id TEXT NOT NULL REFERENCES meetings(id) ON DELETE CASCADE,
+    text TEXT NOT NULL,
+    owner TEXT,
+    done INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX action_items_by_meeting ON action_items (meeting_id);
@@ -0,0 +1,33 @@
+-- What a meeting was and who was in it, for filtering search results
+ALTER TABLE meetings ADD COLUMN meeting_type TEXT NOT NULL DEFAULT '';
+
+CREATE TABLE meeting_participants (
+    meeting_id TEXT NOT NULL REFERENCES meetings(id) ON DELETE CASCADE,
+    name TEXT NOT NULL COLLATE NOCASE,
+    PRIMARY KEY (meeting_id, name)
+);
+
+-- Full-text index over transcript text, kept in step with transcript_chunks
+-- by the triggers below
+CREATE VIRTUAL TABLE transcript_search USING fts5(
+    text,
+    content = 'transcript_chunks',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER transcript_chunks_search_insert AFTER INSERT ON transcript_chunks BEGIN
+    INSERT INTO transcript_search (rowid, text) VALUES (new.id, new.text);
+END;
+
+CREATE TRIGGER transcript_chunks_search_delete AFTER DELETE ON transcript_chunks BEGIN
+    INSERT INTO transcript_search (transcript_search, rowid, text) VALUES ('delete', old.id, old.text);
+END;
+
+CREATE TRIGGER transcript_chunks_search_update AFTER UPDATE OF text ON transcript_chunks BEGIN
+    INSERT INTO transcript_search (transcript_search, rowid, text) VALUES ('delete', old.id, old.text);
+    INSERT INTO transcript_search (rowid, text) VALUES (new.id, new.text);
+END;
+
+-- Index transcripts stored before search existed
+INSERT INTO transcript_search (transcript_search) VALUES ('rebuild');
@@ -0,0 +1,2 @@
+-- Post-call summary, kept when retention purges the transcript it came from
+ALTER TABLE meetings ADD COLUMN summary TEXT;
@@ -0,0 +1,21 @@
+-- Chunks of documents the copilot can retrieve, embedded for similarity
+-- search. Embeddings are little-endian f32 arrays; only those made by the
+-- same embedder are comparable.
+CREATE TABLE rag_chunks (
+    id INTEGER PRIMARY KEY,
+    document_id TEXT NOT NULL,
+    chunk_index INTEGER NOT NULL,
+    -- Where the document came from, e.g. a file path
+    source TEXT NOT NULL,
+    -- Byte offset of the chunk within the document
+    start_offset INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    embedder TEXT NOT NULL,
+    embedding BLOB NOT NULL,
+    -- Set for chunks derived from a meeting, which go with its transcript
+    meeting_id TEXT REFERENCES meetings(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL,
+    UNIQUE (document_id, chunk_index)
+);
+
+CREATE INDEX rag_chunks_by_meeting ON rag_chunks (meeting_id);
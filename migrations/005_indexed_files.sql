@@ -0,0 +1,10 @@
+-- Files the document connector has seen, so unchanged ones aren't embedded
+-- again and deleted ones can be dropped from the vector store
+CREATE TABLE indexed_files (
+    path TEXT PRIMARY KEY NOT NULL,
+    modified_ms INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    -- Chunks stored for the file; NULL if it couldn't be read
+    chunks INTEGER,
+    indexed_at TEXT NOT NULL
+);
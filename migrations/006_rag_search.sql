@@ -0,0 +1,23 @@
+-- Keyword index over vector store chunks, so exact terms such as SKUs and
+-- ticket numbers are found even when embeddings miss them
+CREATE VIRTUAL TABLE rag_search USING fts5(
+    content,
+    content = 'rag_chunks',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER rag_chunks_search_insert AFTER INSERT ON rag_chunks BEGIN
+    INSERT INTO rag_search (rowid, content) VALUES (new.id, new.content);
+END;
+
+CREATE TRIGGER rag_chunks_search_delete AFTER DELETE ON rag_chunks BEGIN
+    INSERT INTO rag_search (rag_search, rowid, content) VALUES ('delete', old.id, old.content);
+END;
+
+CREATE TRIGGER rag_chunks_search_update AFTER UPDATE OF content ON rag_chunks BEGIN
+    INSERT INTO rag_search (rag_search, rowid, content) VALUES ('delete', old.id, old.content);
+    INSERT INTO rag_search (rowid, content) VALUES (new.id, new.content);
+END;
+
+INSERT INTO rag_search (rag_search) VALUES ('rebuild');
@@ -2,30 +2,182 @@ import React, { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
 
+interface Word {
+  word: string;
+  start_time: number;
+  end_time: number;
+  probability: number;
+}
+
+interface Citation {
+  number: number;
+  document_id: string;
+  source: string;
+  chunk_index: number;
+  start_offset: number;
+  score: number;
+}
+
 interface Suggestion {
   id: string;
   content: string;
   suggestion_type: string;
   confidence: number;
+  speaker?: string | null;
+  own_speech?: boolean;
+  words?: Word[];
+  citations?: Citation[];
+  streaming?: boolean;
+}
+
+interface SuggestionDelta {
+  id: string;
+  delta: string;
+}
+
+interface InputDevice {
+  name: string;
+  is_default: boolean;
+}
+
+interface InputLevel {
+  rms: number;
+  peak: number;
+}
+
+// Words below this probability are highlighted for review
+const LOW_CONFIDENCE = 0.6;
+
+interface Transcript {
+  text: string;
+  confidence: number;
+  start_time: number;
+  end_time: number;
+  words: Word[];
+  utterance_id: number;
+  speaker: string | null;
+  source: string;
+  source_role: 'me' | 'them' | 'mixed';
+  is_final: boolean;
+  stable_text: string;
+  unstable_text: string;
 }
 
 export function HUD() {
   const [suggestions, setSuggestions] = useState<Suggestion[]>([]);
   const [isListening, setIsListening] = useState(false);
+  const [isPaused, setIsPaused] = useState(false);
   const [isPinned, setIsPinned] = useState(false);
+  const [liveCaption, setLiveCaption] = useState<Transcript | null>(null);
+  const [devices, setDevices] = useState<InputDevice[]>([]);
+  const [selectedDevice, setSelectedDevice] = useState<string | null>(null);
+  const [inputLevel, setInputLevel] = useState<InputLevel | null>(null);
 
   useEffect(() => {
     // Listen for transcript events from Rust backend
     const unlistenTranscript = listen('transcript', (event: any) => {
-      const transcript = event.payload as Suggestion;
-      setSuggestions(prev => [transcript, ...prev.slice(0, 4)]); // Keep last 5
+      const transcript = event.payload as Transcript;
+
+      // Partials only update the live caption until the final result lands
+      if (!transcript.is_final) {
+        setLiveCaption(transcript);
+        return;
+      }
+
+      setLiveCaption(prev => (prev?.utterance_id === transcript.utterance_id ? null : prev));
+      const suggestion: Suggestion = {
+        id: `utterance-${transcript.utterance_id}`,
+        content: transcript.text,
+        suggestion_type: 'transcript',
+        confidence: transcript.confidence,
+        speaker: transcript.speaker,
+        own_speech: transcript.source_role === 'me',
+        words: transcript.words,
+      };
+      setSuggestions(prev => [suggestion, ...prev.slice(0, 4)]); // Keep last 5
+    });
+
+    // Suggestions stream in: a card appears when one starts, grows with
+    // each delta and is replaced by the final text, or removed if a newer
+    // utterance cancelled it
+    const unlistenSuggestionStarted = listen('suggestion-started', (event: any) => {
+      const suggestion = event.payload as Suggestion;
+      setSuggestions(prev => [{ ...suggestion, streaming: true }, ...prev.slice(0, 4)]);
+    });
+
+    const unlistenSuggestionDelta = listen('suggestion-delta', (event: any) => {
+      const { id, delta } = event.payload as SuggestionDelta;
+      setSuggestions(prev => prev.map(suggestion =>
+        suggestion.id === id ? { ...suggestion, content: suggestion.content + delta } : suggestion
+      ));
+    });
+
+    const unlistenSuggestion = listen('suggestion', (event: any) => {
+      const suggestion = event.payload as Suggestion;
+      setSuggestions(prev => prev.some(existing => existing.id === suggestion.id)
+        ? prev.map(existing => (existing.id === suggestion.id ? suggestion : existing))
+        : [suggestion, ...prev.slice(0, 4)]);
+    });
+
+    const unlistenSuggestionCancelled = listen('suggestion-cancelled', (event: any) => {
+      const id = event.payload as string;
+      setSuggestions(prev => prev.filter(suggestion => suggestion.id !== id));
+    });
+
+    // The backend reports every lifecycle change, including its own
+    const unlistenState = listen('listening-state', (event: any) => {
+      const state = event.payload as 'running' | 'paused' | 'stopped';
+      setIsListening(state !== 'stopped');
+      setIsPaused(state === 'paused');
+      if (state === 'stopped') {
+        setLiveCaption(null);
+      }
+    });
+
+    const unlistenLevel = listen('input-level', (event: any) => {
+      setInputLevel(event.payload as InputLevel);
     });
 
+    invoke<InputDevice[]>('list_input_devices')
+      .then(setDevices)
+      .catch(error => console.error('Failed to list input devices:', error));
+    invoke<string | null>('selected_input_device')
+      .then(setSelectedDevice)
+      .catch(error => console.error('Failed to read input device:', error));
+
     return () => {
       unlistenTranscript.then(fn => fn());
+      unlistenSuggestionStarted.then(fn => fn());
+      unlistenSuggestionDelta.then(fn => fn());
+      unlistenSuggestion.then(fn => fn());
+      unlistenSuggestionCancelled.then(fn => fn());
+      unlistenState.then(fn => fn());
+      unlistenLevel.then(fn => fn());
     };
   }, []);
 
+  // Meter the microphone while idle so the right one is picked before a meeting
+  useEffect(() => {
+    if (isListening) {
+      setInputLevel(null);
+      return;
+    }
+    invoke('start_level_meter').catch(error => console.error('Failed to start level meter:', error));
+    return () => {
+      invoke('stop_level_meter').catch(() => {});
+    };
+  }, [isListening, selectedDevice]);
+
+  const handleSelectDevice = async (name: string) => {
+    const device = name === '' ? null : name;
+    try {
+      await invoke('select_input_device', { name: device });
+      setSelectedDevice(device);
+    } catch (error) {
+      console.error('Failed to select input device:', error);
+    }
+  };
+
   const handleStartListening = async () => {
     try {
       await invoke('start_listening');
@@ -44,6 +196,15 @@ export function HUD() {
     }
   };
 
+  const handleTogglePause = async () => {
+    try {
+      await invoke(isPaused ? 'resume_listening' : 'pause_listening');
+      setIsPaused(!isPaused);
+    } catch (error) {
+      console.error('Failed to pause listening:', error);
+    }
+  };
+
   const handleCopySuggestion = async (suggestionId: string) => {
     try {
       await invoke('copy_suggestion', { suggestionId });
@@ -64,6 +225,11 @@ export function HUD() {
           >
             {isListening ? '🔴 Stop' : '🎤 Start'}
           </button>
+          {isListening && (
+            <button onClick={handleTogglePause} className="pause-btn">
+              {isPaused ? '▶️ Resume' : '⏸️ Pause'}
+            </button>
+          )}
           <button 
             onClick={() => setIsPinned(!isPinned)}
             className="pin-btn"
@@ -74,13 +240,45 @@ export function HUD() {
       </div>
       
       <div className="status">
-        {isListening ? (
+        {isPaused ? (
+          <span className="paused">⏸️ Paused, microphone released</span>
+        ) : isListening ? (
           <span className="listening">🎧 Listening for voice...</span>
         ) : (
           <span className="idle">Click Start to begin transcription</span>
         )}
       </div>
       
+      {!isListening && (
+        <div className="device-picker">
+          <select
+            value={selectedDevice ?? ''}
+            onChange={event => handleSelectDevice(event.target.value)}
+          >
+            <option value="">System default</option>
+            {devices.map(device => (
+              <option key={device.name} value={device.name}>
+                {device.name}{device.is_default ? ' (default)' : ''}
+              </option>
+            ))}
+          </select>
+          <div className="level-meter">
+            <div
+              className="level-fill"
+              style={{ width: `${Math.min(100, Math.sqrt(inputLevel?.rms ?? 0) * 200)}%` }}
+            />
+          </div>
+        </div>
+      )}
+
+      {liveCaption && (
+        <div className="live-caption">
+          {liveCaption.speaker && <span className="speaker">{liveCaption.speaker}:</span>}{' '}
+          <span className="stable">{liveCaption.stable_text}</span>{' '}
+          <span className="unstable">{liveCaption.unstable_text}</span>
+        </div>
+      )}
+
       <div className="suggestions-container">
         {suggestions.length === 0 ? (
           <div className="empty-state">
@@ -89,14 +287,39 @@ export function HUD() {
           </div>
         ) : (
           suggestions.map((suggestion) => (
-            <div key={suggestion.id} className="suggestion-card">
+            <div
+              key={suggestion.id}
+              className={`suggestion-card ${suggestion.own_speech ? 'own-speech' : ''} ${suggestion.streaming ? 'streaming' : ''}`}
+            >
               <div className="suggestion-header">
                 <span className="suggestion-type">{suggestion.suggestion_type}</span>
+                {suggestion.speaker && <span className="speaker">{suggestion.speaker}</span>}
                 <span className="confidence">
                   {Math.round(suggestion.confidence * 100)}%
                 </span>
               </div>
-              <div className="suggestion-content">{suggestion.content}</div>
+              <div className="suggestion-content">
+                {suggestion.words && suggestion.words.length > 0
+                  ? suggestion.words.map((word, index) => (
+                      <span
+                        key={index}
+                        className={word.probability < LOW_CONFIDENCE ? 'low-confidence' : undefined}
+                        title={`${word.start_time.toFixed(2)}s · ${Math.round(word.probability * 100)}%`}
+                      >
+                        {word.word}{' '}
+                      </span>
+                    ))
+                  : suggestion.content}
+              </div>
+              {suggestion.citations && suggestion.citations.length > 0 && (
+                <ol className="citations">
+                  {suggestion.citations.map(citation => (
+                    <li key={citation.number} title={citation.source}>
+                      [{citation.number}] {citation.source.split(/[\\/]/).pop()}
+                    </li>
+                  ))}
+                </ol>
+              )}
               <div className="suggestion-actions">
                 <button 
                   onClick={() => handleCopySuggestion(suggestion.id)}
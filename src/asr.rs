@@ -1,85 +1,500 @@
 use anyhow::Result;
-use std::path::Path;
-use tracing::{info, warn, error};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tracing::info;
+use crate::diarize::speaker_label;
+use crate::http_asr::{HttpAsrBackend, HttpAsrConfig};
+use crate::source::SourceRole;
 use crate::vad::AudioChunk;
 
-pub struct WhisperEngine {
-    model_path: String,
-    context: Option<WhisperContext>,
+// Multilingual; `.en` models are slightly more accurate but English-only
+pub const DEFAULT_MODEL_PATH: &str = "models/ggml-base.bin";
+
+/// ISO 639-1 codes and the English names Whisper uses for them.
+const LANGUAGES: &[(&str, &str)] = &[
+    ("en", "english"),
+    ("es", "spanish"),
+    ("de", "german"),
+    ("fr", "french"),
+    ("it", "italian"),
+    ("pt", "portuguese"),
+    ("nl", "dutch"),
+    ("pl", "polish"),
+    ("sv", "swedish"),
+    ("ru", "russian"),
+    ("uk", "ukrainian"),
+    ("tr", "turkish"),
+    ("ar", "arabic"),
+    ("hi", "hindi"),
+    ("zh", "chinese"),
+    ("ja", "japanese"),
+    ("ko", "korean"),
+];
+
+/// English name for a language code, e.g. `"de"` -> `"german"`.
+pub fn language_name(code: &str) -> Option<&'static str> {
+    LANGUAGES.iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(code))
+        .map(|&(_, name)| name)
 }
 
-// Placeholder for whisper.cpp context - will be replaced with actual FFI
-struct WhisperContext {
-    _placeholder: (),
+/// Normalise a code or English name as reported by a server to the code.
+pub fn language_code(language: &str) -> String {
+    let language = language.trim().to_lowercase();
+    LANGUAGES.iter()
+        .find(|(_, name)| *name == language)
+        .map(|&(code, _)| code.to_string())
+        .unwrap_or(language)
 }
 
-#[derive(Clone, Debug, serde::Serialize)]
+/// How the spoken language of each utterance is chosen.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct LanguageConfig {
+    /// Pin every utterance to this language code; detected per utterance
+    /// when unset.
+    pub language: Option<String>,
+    /// Restrict detection to these codes, e.g. the languages the team
+    /// speaks. Empty allows anything the model knows.
+    pub candidates: Vec<String>,
+    /// Return English text whatever language was spoken.
+    pub translate: bool,
+}
+
+impl LanguageConfig {
+    /// Read `ASR_LANGUAGE` (a code, or `auto`), `ASR_LANGUAGES` (comma
+    /// separated candidates) and `ASR_TRANSLATE`.
+    pub fn from_env() -> Self {
+        let language = std::env::var("ASR_LANGUAGE").ok()
+            .map(|language| language.trim().to_lowercase())
+            .filter(|language| !language.is_empty() && language != "auto");
+        let candidates = std::env::var("ASR_LANGUAGES").ok()
+            .map(|languages| languages.split(',')
+                .map(|language| language.trim().to_lowercase())
+                .filter(|language| !language.is_empty())
+                .collect())
+            .unwrap_or_default();
+        let translate = std::env::var("ASR_TRANSLATE")
+            .map(|value| matches!(value.as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+        
+        Self { language, candidates, translate }
+    }
+    
+    /// Language to report when nothing better is known.
+    pub fn fallback(&self) -> String {
+        self.language.clone()
+            .or_else(|| self.candidates.first().cloned())
+            .unwrap_or_else(|| "en".to_string())
+    }
+}
+
+/// Speech-to-text engine behind the ASR stage of the pipeline.
+///
+/// Backends receive 16 kHz mono utterances and time everything from the start
+/// of the chunk; `transcribe_chunk` does the bookkeeping shared by all of them.
+#[async_trait]
+pub trait AsrBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+    
+    /// Prepare the backend (load weights, open a client) before first use.
+    async fn load(&mut self) -> Result<()>;
+    
+    async fn transcribe(&self, audio_chunk: &AudioChunk) -> Result<TranscriptResult>;
+    
+    fn model_info(&self) -> String;
+    
+    /// Transcribe an utterance and place the result on the session timeline.
+    /// Returns `None` for chunks too short to transcribe or with no speech.
+    async fn transcribe_chunk(&self, audio_chunk: &AudioChunk) -> Result<Option<TranscriptResult>> {
+        if audio_chunk.data.len() < 1600 { // Less than 100ms at 16kHz
+            return Ok(None);
+        }
+        
+        let mut result = self.transcribe(audio_chunk).await?;
+        if result.text.is_empty() {
+            return Ok(None);
+        }
+        
+        result.utterance_id = audio_chunk.utterance_id;
+        result.is_final = audio_chunk.is_final;
+        result.speaker_id = audio_chunk.speaker_id;
+        result.speaker = match audio_chunk.source_role {
+            SourceRole::Me => Some("Me".to_string()),
+            _ => audio_chunk.speaker_id.map(speaker_label),
+        };
+        result.source = audio_chunk.source.clone();
+        result.source_role = audio_chunk.source_role;
+        
+        // Backends time everything from the start of the chunk; shift onto
+        // the session timeline
+        let offset = audio_chunk.start_time;
+        result.start_time += offset;
+        result.end_time += offset;
+        for segment in &mut result.segments {
+            segment.start_time += offset;
+            segment.end_time += offset;
+        }
+        for word in &mut result.words {
+            word.start_time += offset;
+            word.end_time += offset;
+        }
+        
+        if result.is_final {
+            info!("Transcribed: '{}'", result.text);
+        }
+        Ok(Some(result))
+    }
+}
+
+/// Which ASR backend the pipeline should run, chosen at runtime.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum AsrBackendKind {
+    Mock,
+    Local { model_path: String },
+    Http(HttpAsrConfig),
+}
+
+impl Default for AsrBackendKind {
+    fn default() -> Self {
+        if cfg!(feature = "whisper") {
+            AsrBackendKind::Local { model_path: DEFAULT_MODEL_PATH.to_string() }
+        } else {
+            AsrBackendKind::Mock
+        }
+    }
+}
+
+impl AsrBackendKind {
+    /// Read the backend from `ASR_BACKEND` (`mock`, `local` or `http`) and
+    /// its settings from the other `ASR_*` variables, falling back to the
+    /// default for this build.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("ASR_BACKEND").ok().as_deref() {
+            None | Some("") => Ok(Self::default()),
+            Some("mock") => Ok(AsrBackendKind::Mock),
+            Some("local") => Ok(AsrBackendKind::Local {
+                model_path: std::env::var("ASR_MODEL_PATH")
+                    .unwrap_or_else(|_| DEFAULT_MODEL_PATH.to_string()),
+            }),
+            Some("http") => Ok(AsrBackendKind::Http(HttpAsrConfig::from_env()?)),
+            Some(other) => Err(anyhow::anyhow!("Unknown ASR backend: {}", other)),
+        }
+    }
+    
+    pub fn build(&self, language: &LanguageConfig) -> Result<Box<dyn AsrBackend>> {
+        let language = language.clone();
+        match self {
+            AsrBackendKind::Mock => Ok(Box::new(MockAsrBackend::new(language))),
+            #[cfg(feature = "whisper")]
+            AsrBackendKind::Local { model_path } => Ok(Box::new(WhisperEngine::new(model_path, language)?)),
+            #[cfg(not(feature = "whisper"))]
+            AsrBackendKind::Local { .. } => {
+                Err(anyhow::anyhow!("Local Whisper transcription requires building with `--features whisper`"))
+            },
+            AsrBackendKind::Http(config) => Ok(Box::new(HttpAsrBackend::new(config.clone(), language)?)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
 pub struct TranscriptResult {
     pub text: String,
     pub confidence: f32,
     pub start_time: f32,
     pub end_time: f32,
+    /// Language spoken, as an ISO 639-1 code.
     pub language: String,
+    /// How sure detection was of `language`: 1.0 when it was pinned, 0.0
+    /// when the backend doesn't say.
+    pub language_probability: f32,
+    /// `text` is an English translation rather than what was said.
+    pub translated: bool,
+    pub segments: Vec<TranscriptSegment>,
+    pub words: Vec<WordTiming>,
+    pub utterance_id: u64,
+    /// Diarized speaker, when the pipeline runs diarization.
+    pub speaker_id: Option<u32>,
+    /// Display label for `speaker_id`, e.g. "Speaker 2", or "Me" for the
+    /// user's own microphone.
+    pub speaker: Option<String>,
+    /// Name of the input the utterance was heard on.
+    pub source: String,
+    pub source_role: SourceRole,
+    /// Partial hypotheses are superseded by later results for the same
+    /// utterance; the final one is not.
+    pub is_final: bool,
+    /// Prefix of `text` that later partials will not revise.
+    pub stable_text: String,
+    /// Remainder of `text` that may still change.
+    pub unstable_text: String,
+}
+
+/// A span of text as segmented by Whisper.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct TranscriptSegment {
+    pub text: String,
+    pub start_time: f32,
+    pub end_time: f32,
+}
+
+/// A single recognised word with its timing and confidence.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct WordTiming {
+    pub word: String,
+    pub start_time: f32,
+    pub end_time: f32,
+    pub probability: f32,
+}
+
+/// whisper.cpp run in-process through its FFI bindings.
+#[cfg(feature = "whisper")]
+pub struct WhisperEngine {
+    model_path: String,
+    language: LanguageConfig,
+    context: Option<WhisperContext>,
+}
+
+// Loaded whisper.cpp model, shared with blocking inference tasks
+#[cfg(feature = "whisper")]
+#[derive(Clone)]
+struct WhisperContext {
+    inner: std::sync::Arc<whisper_rs::WhisperContext>,
 }
 
+#[cfg(feature = "whisper")]
 impl WhisperEngine {
-    pub fn new(model_path: &str) -> Result<Self> {
-        if !Path::new(model_path).exists() {
-            warn!("Whisper model not found at: {}. Using mock transcription.", model_path);
+    pub fn new(model_path: &str, language: LanguageConfig) -> Result<Self> {
+        if !std::path::Path::new(model_path).exists() {
+            tracing::warn!("Whisper model not found at: {}", model_path);
         }
         
         Ok(Self {
             model_path: model_path.to_string(),
+            language,
             context: None,
         })
     }
     
-    pub async fn load_model(&mut self) -> Result<()> {
-        info!("Loading Whisper model from: {}", self.model_path);
+    // Runs whisper.cpp on the calling thread; callers keep it off the async runtime
+    fn run_inference(context: &WhisperContext, audio: &[f32], config: &LanguageConfig) -> Result<TranscriptResult> {
+        use whisper_rs::{FullParams, SamplingStrategy};
         
-        // TODO: Replace with actual whisper.cpp FFI calls
-        // let ctx = unsafe {
-        //     whisper_init_from_file(self.model_path.as_ptr() as *const i8)
-        // };
-        // if ctx.is_null() {
-        //     return Err(anyhow::anyhow!("Failed to load Whisper model"));
-        // }
+        let mut state = context.inner.create_state()
+            .map_err(|e| anyhow::anyhow!("Failed to create Whisper state: {}", e))?;
         
-        self.context = Some(WhisperContext { _placeholder: () });
-        info!("Whisper model loaded successfully");
-        Ok(())
+        let threads = std::thread::available_parallelism()
+            .map(|n| n.get().min(4))
+            .unwrap_or(2);
+        
+        let (language, language_probability) = Self::choose_language(context, &mut state, audio, config, threads)?;
+        
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_n_threads(threads as i32);
+        params.set_language(Some(&language));
+        params.set_translate(config.translate && language != "en");
+        params.set_no_context(true);
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+        params.set_token_timestamps(true);
+        
+        state.full(params, audio)
+            .map_err(|e| anyhow::anyhow!("Whisper inference failed: {}", e))?;
+        
+        let eot = context.inner.token_eot();
+        let mut segments = Vec::new();
+        let mut words: Vec<WordTiming> = Vec::new();
+        let mut token_probs = Vec::new();
+        
+        let n_segments = state.full_n_segments()?;
+        for i in 0..n_segments {
+            let text = state.full_get_segment_text_lossy(i)?;
+            // whisper.cpp reports segment times in 10ms units
+            let start_time = state.full_get_segment_t0(i)? as f32 / 100.0;
+            let end_time = state.full_get_segment_t1(i)? as f32 / 100.0;
+            
+            for j in 0..state.full_n_tokens(i)? {
+                let token = state.full_get_token_data(i, j)?;
+                // Special and timestamp tokens sit at or above end-of-text
+                if token.id >= eot {
+                    continue;
+                }
+                token_probs.push(token.p);
+                
+                let piece = state.full_get_token_text_lossy(i, j)?;
+                let token_start = token.t0 as f32 / 100.0;
+                let token_end = token.t1 as f32 / 100.0;
+                
+                // Sub-word tokens continue the current word unless they start
+                // with a space; a word is only as confident as its weakest piece
+                match words.last_mut() {
+                    Some(word) if !piece.starts_with(' ') && !piece.trim().is_empty() => {
+                        word.word.push_str(&piece);
+                        word.end_time = token_end;
+                        word.probability = word.probability.min(token.p);
+                    },
+                    _ if piece.trim().is_empty() => {},
+                    _ => words.push(WordTiming {
+                        word: piece.trim().to_string(),
+                        start_time: token_start,
+                        end_time: token_end,
+                        probability: token.p,
+                    }),
+                }
+            }
+            
+            segments.push(TranscriptSegment {
+                text: text.trim().to_string(),
+                start_time,
+                end_time,
+            });
+        }
+        
+        let confidence = if token_probs.is_empty() {
+            0.0
+        } else {
+            token_probs.iter().sum::<f32>() / token_probs.len() as f32
+        };
+        
+        let text = segments.iter()
+            .map(|segment| segment.text.as_str())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        
+        Ok(TranscriptResult {
+            text,
+            confidence,
+            start_time: 0.0,
+            end_time: audio.len() as f32 / 16000.0,
+            translated: config.translate && language != "en",
+            language,
+            language_probability,
+            segments,
+            words,
+            ..Default::default()
+        })
     }
     
-    pub async fn transcribe_chunk(&self, audio_chunk: &AudioChunk) -> Result<Option<TranscriptResult>> {
-        if self.context.is_none() {
-            return Err(anyhow::anyhow!("Whisper model not loaded"));
+    // Pinned language, or the most likely of the allowed candidates. English-only
+    // models can't detect or translate, so they are always English.
+    fn choose_language(
+        context: &WhisperContext,
+        state: &mut whisper_rs::WhisperState,
+        audio: &[f32],
+        config: &LanguageConfig,
+        threads: usize,
+    ) -> Result<(String, f32)> {
+        if !context.inner.is_multilingual() {
+            return Ok(("en".to_string(), 1.0));
+        }
+        if let Some(language) = &config.language {
+            return Ok((language.clone(), 1.0));
         }
         
-        if audio_chunk.data.len() < 1600 { // Less than 100ms at 16kHz
-            return Ok(None);
+        state.pcm_to_mel(audio, threads)
+            .map_err(|e| anyhow::anyhow!("Failed to compute mel spectrogram: {}", e))?;
+        let (detected, probs) = state.lang_detect(0, threads)
+            .map_err(|e| anyhow::anyhow!("Language detection failed: {}", e))?;
+        
+        let allowed: Vec<i32> = config.candidates.iter()
+            .filter_map(|language| whisper_rs::get_lang_id(language))
+            .collect();
+        let lang_id = allowed.iter()
+            .copied()
+            .max_by(|&a, &b| probs[a as usize].total_cmp(&probs[b as usize]))
+            .unwrap_or(detected);
+        
+        let language = whisper_rs::get_lang_str(lang_id).unwrap_or("en").to_string();
+        Ok((language, probs.get(lang_id as usize).copied().unwrap_or(0.0)))
+    }
+}
+
+#[cfg(feature = "whisper")]
+#[async_trait]
+impl AsrBackend for WhisperEngine {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+    
+    async fn load(&mut self) -> Result<()> {
+        info!("Loading Whisper model from: {}", self.model_path);
+        
+        if !std::path::Path::new(&self.model_path).exists() {
+            return Err(anyhow::anyhow!("Whisper model not found at: {}", self.model_path));
         }
         
-        // TODO: Replace with actual whisper.cpp transcription
-        // let result = unsafe {
-        //     whisper_full_default(
-        //         ctx,
-        //         audio_chunk.data.as_ptr(),
-        //         audio_chunk.data.len() as i32,
-        //     )
-        // };
+        let model_path = self.model_path.clone();
+        let ctx = tokio::task::spawn_blocking(move || {
+            whisper_rs::WhisperContext::new_with_params(
+                &model_path,
+                whisper_rs::WhisperContextParameters::default(),
+            )
+        }).await?
+            .map_err(|e| anyhow::anyhow!("Failed to load Whisper model: {}", e))?;
         
-        // Mock transcription for development
-        let mock_result = self.mock_transcribe(&audio_chunk.data).await?;
+        if !ctx.is_multilingual() && (self.language.translate
+            || self.language.language.as_deref().is_some_and(|language| language != "en")) {
+            tracing::warn!("{} is an English-only model; language settings are ignored", self.model_path);
+        }
         
-        info!("Transcribed: '{}'", mock_result.text);
-        Ok(Some(mock_result))
+        self.context = Some(WhisperContext { inner: std::sync::Arc::new(ctx) });
+        info!("Whisper model loaded successfully");
+        Ok(())
+    }
+    
+    async fn transcribe(&self, audio_chunk: &AudioChunk) -> Result<TranscriptResult> {
+        let Some(context) = self.context.clone() else {
+            return Err(anyhow::anyhow!("Whisper model not loaded"));
+        };
+        
+        let audio = audio_chunk.data.clone();
+        let language = self.language.clone();
+        tokio::task::spawn_blocking(move || Self::run_inference(&context, &audio, &language)).await?
+    }
+    
+    fn model_info(&self) -> String {
+        format!("Whisper model: {} (loaded: {})", 
+                self.model_path, 
+                self.context.is_some())
+    }
+}
+
+/// Canned transcripts for development and tests; needs no model or server.
+pub struct MockAsrBackend {
+    language: LanguageConfig,
+    loaded: bool,
+}
+
+impl MockAsrBackend {
+    pub fn new(language: LanguageConfig) -> Self {
+        Self { language, loaded: false }
+    }
+}
+
+#[async_trait]
+impl AsrBackend for MockAsrBackend {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+    
+    async fn load(&mut self) -> Result<()> {
+        self.loaded = true;
+        info!("Using mock transcription backend");
+        Ok(())
     }
     
-    // Mock transcription for development - replace with actual Whisper calls
-    async fn mock_transcribe(&self, audio_data: &[f32]) -> Result<TranscriptResult> {
+    // Mock transcription for development and tests. Partials reveal the
+    // phrase a word at a time so the streaming path behaves like a real model.
+    async fn transcribe(&self, audio_chunk: &AudioChunk) -> Result<TranscriptResult> {
         // Simulate processing time
         tokio::time::sleep(tokio::time::Duration::from_millis(150)).await;
         
+        let audio_data = &audio_chunk.data;
+        
         // Calculate some basic audio properties for mock text
         let rms = (audio_data.iter()
             .map(|&sample| sample * sample)
@@ -97,39 +512,230 @@ impl WhisperEngine {
             "Could you elaborate on that",
         ];
         
-        let phrase_index = (rms * 1000.0) as usize % mock_phrases.len();
-        let text = mock_phrases[phrase_index].to_string();
+        let phrase_index = audio_chunk.utterance_id as usize % mock_phrases.len();
+        let end_time = audio_data.len() as f32 / 16000.0; // Duration in seconds
+        
+        let words: Vec<&str> = mock_phrases[phrase_index].split_whitespace().collect();
+        let spoken = if audio_chunk.is_final {
+            words.len()
+        } else {
+            ((end_time / 0.4) as usize).clamp(1, words.len())
+        };
+        let text = words[..spoken].join(" ");
+        let confidence = 0.85 + (rms * 0.15); // Mock confidence based on audio energy
+        
+        // Spread the words evenly over the audio
+        let word_duration = end_time / spoken as f32;
+        let word_timings = words[..spoken].iter()
+            .enumerate()
+            .map(|(index, word)| WordTiming {
+                word: word.to_string(),
+                start_time: index as f32 * word_duration,
+                end_time: (index + 1) as f32 * word_duration,
+                probability: confidence,
+            })
+            .collect();
         
         Ok(TranscriptResult {
+            segments: vec![TranscriptSegment {
+                text: text.clone(),
+                start_time: 0.0,
+                end_time,
+            }],
+            words: word_timings,
             text,
-            confidence: 0.85 + (rms * 0.15), // Mock confidence based on audio energy
+            confidence,
             start_time: 0.0,
-            end_time: audio_data.len() as f32 / 16000.0, // Duration in seconds
-            language: "en".to_string(),
+            end_time,
+            language: self.language.fallback(),
+            language_probability: 1.0,
+            ..Default::default()
         })
     }
     
-    pub fn get_model_info(&self) -> String {
-        format!("Whisper model: {} (loaded: {})", 
-                self.model_path, 
-                self.context.is_some())
+    fn model_info(&self) -> String {
+        format!("Mock transcription (loaded: {})", self.loaded)
+    }
+}
+
+/// Splits streaming hypotheses into a committed prefix and a revisable tail.
+///
+/// Uses local agreement: words on which two consecutive partials agree are
+/// committed and never retracted, so captions only grow at the stable edge.
+/// A final result commits everything and forgets the utterance. State is
+/// kept per utterance, since separate inputs interleave their partials.
+#[derive(Default)]
+pub struct PartialStabilizer {
+    utterances: HashMap<u64, UtteranceHypotheses>,
+}
+
+#[derive(Default)]
+struct UtteranceHypotheses {
+    previous: Vec<String>,
+    committed: Vec<String>,
+}
+
+// Utterances whose final never arrives, e.g. merged or dropped by the queue,
+// are forgotten oldest first beyond this many
+const MAX_OPEN_UTTERANCES: usize = 8;
+
+impl PartialStabilizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn apply(&mut self, result: &mut TranscriptResult) {
+        let words: Vec<String> = result.text.split_whitespace().map(str::to_string).collect();
+        
+        if result.is_final {
+            result.stable_text = words.join(" ");
+            result.unstable_text.clear();
+            self.utterances.remove(&result.utterance_id);
+            return;
+        }
+        
+        if !self.utterances.contains_key(&result.utterance_id) && self.utterances.len() >= MAX_OPEN_UTTERANCES {
+            // IDs come from an increasing sequence, so the lowest is the oldest
+            if let Some(oldest) = self.utterances.keys().min().copied() {
+                self.utterances.remove(&oldest);
+            }
+        }
+        let state = self.utterances.entry(result.utterance_id).or_default();
+        
+        let agreed = words.iter()
+            .zip(&state.previous)
+            .take_while(|(current, previous)| current == previous)
+            .count();
+        if agreed > state.committed.len() {
+            state.committed.extend_from_slice(&words[state.committed.len()..agreed]);
+        }
+        
+        // Committed words are never retracted, so whatever this hypothesis
+        // has in their place is dropped rather than shown twice
+        let tail_start = state.committed.len().min(words.len());
+        
+        result.stable_text = state.committed.join(" ");
+        result.unstable_text = words[tail_start..].join(" ");
+        result.text = [result.stable_text.as_str(), result.unstable_text.as_str()]
+            .iter()
+            .filter(|part| !part.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ");
+        
+        state.previous = words;
     }
 }
 
-// Future: Actual whisper.cpp FFI bindings
-/*
-extern "C" {
-    fn whisper_init_from_file(path_model: *const i8) -> *mut WhisperContext;
-    fn whisper_full_default(
-        ctx: *mut WhisperContext,
-        samples: *const f32,
-        n_samples: i32,
-    ) -> i32;
-    fn whisper_full_get_segment_text(
-        ctx: *mut WhisperContext,
-        i_segment: i32,
-    ) -> *const i8;
-    fn whisper_full_n_segments(ctx: *mut WhisperContext) -> i32;
-    fn whisper_free(ctx: *mut WhisperContext);
-}
-*/
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partial(utterance_id: u64, text: &str) -> TranscriptResult {
+        TranscriptResult {
+            text: text.to_string(),
+            utterance_id,
+            ..TranscriptResult::default()
+        }
+    }
+
+    fn stabilize(stabilizer: &mut PartialStabilizer, utterance_id: u64, text: &str) -> TranscriptResult {
+        let mut result = partial(utterance_id, text);
+        stabilizer.apply(&mut result);
+        result
+    }
+
+    #[test]
+    fn stabilizer_commits_words_two_hypotheses_agree_on() {
+        let mut stabilizer = PartialStabilizer::new();
+        let first = stabilize(&mut stabilizer, 1, "we should ship");
+        assert_eq!(first.stable_text, "");
+        assert_eq!(first.unstable_text, "we should ship");
+
+        let second = stabilize(&mut stabilizer, 1, "we should ship it");
+        assert_eq!(second.stable_text, "we should ship");
+        assert_eq!(second.unstable_text, "it");
+        assert_eq!(second.text, "we should ship it");
+    }
+
+    #[test]
+    fn stabilizer_keeps_committed_words_when_revised() {
+        let mut stabilizer = PartialStabilizer::new();
+        stabilize(&mut stabilizer, 1, "we should ship");
+        stabilize(&mut stabilizer, 1, "we should ship");
+
+        let revised = stabilize(&mut stabilizer, 1, "we could ship it friday");
+        assert_eq!(revised.stable_text, "we should ship");
+        assert_eq!(revised.unstable_text, "it friday");
+        assert_eq!(revised.text, "we should ship it friday");
+
+        // Agreement on the revised words doesn't overwrite the committed ones
+        let again = stabilize(&mut stabilizer, 1, "we could ship it friday");
+        assert_eq!(again.stable_text, "we should ship it friday");
+        assert_eq!(again.unstable_text, "");
+    }
+
+    #[test]
+    fn stabilizer_keeps_committed_words_when_dropped() {
+        let mut stabilizer = PartialStabilizer::new();
+        stabilize(&mut stabilizer, 1, "we should ship it");
+        stabilize(&mut stabilizer, 1, "we should ship it");
+
+        let shorter = stabilize(&mut stabilizer, 1, "we should");
+        assert_eq!(shorter.stable_text, "we should ship it");
+        assert_eq!(shorter.unstable_text, "");
+        assert_eq!(shorter.text, "we should ship it");
+    }
+
+    #[test]
+    fn stabilizer_final_replaces_partials() {
+        let mut stabilizer = PartialStabilizer::new();
+        stabilize(&mut stabilizer, 1, "we should ship");
+        stabilize(&mut stabilizer, 1, "we should ship");
+
+        let mut last = partial(1, "we could ship it");
+        last.is_final = true;
+        stabilizer.apply(&mut last);
+        assert_eq!(last.stable_text, "we could ship it");
+        assert_eq!(last.unstable_text, "");
+
+        // The next utterance starts from nothing committed
+        let next = stabilize(&mut stabilizer, 2, "thanks");
+        assert_eq!(next.stable_text, "");
+        assert_eq!(next.unstable_text, "thanks");
+    }
+
+    #[test]
+    fn stabilizer_tracks_interleaved_utterances_separately() {
+        let mut stabilizer = PartialStabilizer::new();
+        // Microphone and loopback partials arrive alternately
+        stabilize(&mut stabilizer, 1, "can you hear");
+        stabilize(&mut stabilizer, 2, "the budget is");
+        let mic = stabilize(&mut stabilizer, 1, "can you hear me");
+        let loopback = stabilize(&mut stabilizer, 2, "the budget is final");
+        assert_eq!(mic.stable_text, "can you hear");
+        assert_eq!(mic.unstable_text, "me");
+        assert_eq!(loopback.stable_text, "the budget is");
+        assert_eq!(loopback.unstable_text, "final");
+
+        let mut mic_final = partial(1, "can you hear me");
+        mic_final.is_final = true;
+        stabilizer.apply(&mut mic_final);
+        assert!(!stabilizer.utterances.contains_key(&1));
+
+        // The other utterance keeps its committed words
+        let loopback = stabilize(&mut stabilizer, 2, "the budget is");
+        assert_eq!(loopback.stable_text, "the budget is");
+        assert_eq!(loopback.text, "the budget is");
+    }
+
+    #[test]
+    fn stabilizer_forgets_oldest_unfinished_utterance() {
+        let mut stabilizer = PartialStabilizer::new();
+        for utterance_id in 1..=MAX_OPEN_UTTERANCES as u64 + 1 {
+            stabilize(&mut stabilizer, utterance_id, "hello");
+        }
+        assert_eq!(stabilizer.utterances.len(), MAX_OPEN_UTTERANCES);
+        assert!(!stabilizer.utterances.contains_key(&1));
+    }
+}
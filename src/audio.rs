@@ -1,68 +1,327 @@
 use anyhow::Result;
-use tokio::sync::broadcast;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
 use tracing::{info, error};
-use crate::asr::TranscriptResult;
+use crate::asr::{AsrBackend, AsrBackendKind, LanguageConfig, PartialStabilizer, TranscriptResult};
+use crate::detector::DetectorKind;
+use crate::diarize::{Diarizer, DiarizerConfig};
+use crate::noise::VadLevels;
+use crate::queue::{self, QueueConfig, QueueMetrics};
+use crate::source::{AudioInput, AudioSource, MicrophoneSource, SourceRole};
+use crate::vad::{CaptureState, VadEngine};
 
+/// Threads available to blocking ASR work such as whisper.cpp inference.
+const ASR_BLOCKING_THREADS: usize = 2;
+
+/// How several inputs are combined before voice detection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputMode {
+    /// Each input gets its own VAD and its utterances keep the input's role.
+    #[default]
+    Separate,
+    /// Inputs are summed into one stream, e.g. several mics in one room.
+    Mixed,
+}
+
+/// Capture, VAD and ASR for a session, which can be started, paused and
+/// stopped any number of times.
 pub struct AudioPipeline {
-    is_running: bool,
+    inputs: Vec<AudioInput>,
+    // Built from `inputs` on the first start and handed back by their tasks
+    // on every stop
+    vad_engines: Vec<(String, VadEngine)>,
+    vad_tasks: Vec<JoinHandle<(String, VadEngine)>>,
+    // Loaded once and returned by the worker thread when it exits
+    loaded_asr: Option<Box<dyn AsrBackend>>,
+    asr_worker: Option<std::thread::JoinHandle<Box<dyn AsrBackend>>>,
+    control: watch::Sender<CaptureState>,
+    input_mode: InputMode,
+    detector: DetectorKind,
+    asr_backend: AsrBackendKind,
+    language: LanguageConfig,
+    diarizer: Option<DiarizerConfig>,
+    model_info: String,
+    vad_levels: Vec<(String, Arc<Mutex<VadLevels>>)>,
+    queue_config: QueueConfig,
+    queue_metrics: Arc<Mutex<QueueMetrics>>,
 }
 
 impl AudioPipeline {
+    /// Listen on the default microphone, or on `MIC_DEVICE` plus a
+    /// `LOOPBACK_DEVICE` carrying the other side of the call when set.
     pub fn new() -> Result<Self> {
-        Ok(Self {
-            is_running: false,
-        })
+        Self::with_microphone(std::env::var("MIC_DEVICE").ok().as_deref())
+    }
+    
+    /// Like `new`, but listen on the named microphone, or the default one
+    /// when `None`.
+    pub fn with_microphone(device: Option<&str>) -> Result<Self> {
+        let pipeline = Self::with_inputs(microphone_inputs(device));
+        let input_mode = match std::env::var("INPUT_MODE").ok().as_deref() {
+            None | Some("") | Some("separate") => InputMode::Separate,
+            Some("mixed") => InputMode::Mixed,
+            Some(other) => return Err(anyhow::anyhow!("Unknown input mode: {}", other)),
+        };
+        
+        Ok(pipeline
+            .with_input_mode(input_mode)
+            .with_detector(DetectorKind::from_env()?)
+            .with_asr_backend(AsrBackendKind::from_env()?)
+            .with_language(LanguageConfig::from_env())
+            .with_diarizer(DiarizerConfig::from_env()?)
+            .with_queue_config(QueueConfig::from_env()?))
+    }
+    
+    /// Build a pipeline fed by the given source instead of the default microphone.
+    pub fn with_source(source: Box<dyn AudioSource>) -> Self {
+        Self::with_inputs(vec![AudioInput::new("input", SourceRole::Mixed, source)])
+    }
+    
+    /// Build a pipeline fed by several labelled inputs, e.g. the user's
+    /// microphone and a loopback of the call.
+    pub fn with_inputs(inputs: Vec<AudioInput>) -> Self {
+        Self {
+            inputs,
+            vad_engines: Vec::new(),
+            vad_tasks: Vec::new(),
+            loaded_asr: None,
+            asr_worker: None,
+            control: watch::channel(CaptureState::Stopped).0,
+            input_mode: InputMode::default(),
+            detector: DetectorKind::default(),
+            asr_backend: AsrBackendKind::default(),
+            language: LanguageConfig::default(),
+            diarizer: Some(DiarizerConfig::default()),
+            model_info: "not loaded".to_string(),
+            vad_levels: Vec::new(),
+            queue_config: QueueConfig::default(),
+            queue_metrics: Arc::new(Mutex::new(QueueMetrics::default())),
+        }
+    }
+    
+    /// Listen on another microphone from the next start, keeping the loaded
+    /// ASR model. A running session is stopped first.
+    pub async fn switch_microphone(&mut self, device: Option<&str>) {
+        self.stop().await;
+        self.inputs = microphone_inputs(device);
+        self.vad_engines.clear();
+        self.vad_levels.clear();
+    }
+    
+    pub fn with_input_mode(mut self, input_mode: InputMode) -> Self {
+        self.input_mode = input_mode;
+        self
+    }
+    
+    /// Choose the speech detector every input's VAD runs; takes effect when
+    /// the VAD engines are next built.
+    pub fn with_detector(mut self, detector: DetectorKind) -> Self {
+        self.detector = detector;
+        self
+    }
+    
+    /// Bound the VAD to ASR queue and choose what is lost when ASR falls behind.
+    pub fn with_queue_config(mut self, queue_config: QueueConfig) -> Self {
+        self.queue_config = queue_config;
+        self
+    }
+    
+    /// Shared view of the VAD to ASR queue counters.
+    pub fn queue_metrics(&self) -> Arc<Mutex<QueueMetrics>> {
+        self.queue_metrics.clone()
+    }
+    
+    pub fn with_asr_backend(mut self, asr_backend: AsrBackendKind) -> Self {
+        self.asr_backend = asr_backend;
+        self
+    }
+    
+    /// Pin or auto-detect the spoken language, optionally translating to English.
+    pub fn with_language(mut self, language: LanguageConfig) -> Self {
+        self.language = language;
+        self
+    }
+    
+    /// Label utterances by speaker, or pass `None` to skip diarization.
+    pub fn with_diarizer(mut self, diarizer: Option<DiarizerConfig>) -> Self {
+        self.diarizer = diarizer;
+        self
     }
     
     pub async fn start_streaming(&mut self) -> Result<broadcast::Receiver<TranscriptResult>> {
-        if self.is_running {
+        if self.is_running() {
             return Err(anyhow::anyhow!("Audio pipeline already running"));
         }
+        // Reclaim the engines and ASR backend of a session that ended by itself
+        self.stop().await;
+        
+        if self.vad_engines.is_empty() && self.inputs.is_empty() {
+            return Err(anyhow::anyhow!("No audio input configured"));
+        }
+        if self.vad_engines.is_empty() {
+            self.vad_engines = self.build_vad_engines()?;
+        }
         
+        // Load the ASR backend, unless it survived a previous run
+        let asr_backend = match self.loaded_asr.take() {
+            Some(asr_backend) => asr_backend,
+            None => {
+                let mut asr_backend = self.asr_backend.build(&self.language)?;
+                asr_backend.load().await?;
+                asr_backend
+            },
+        };
+        self.model_info = asr_backend.model_info();
+        
+        let diarizer = match self.diarizer.clone().map(Diarizer::new).transpose() {
+            Ok(diarizer) => diarizer,
+            Err(e) => {
+                self.loaded_asr = Some(asr_backend);
+                return Err(e);
+            }
+        };
+        
+        let vad_engines = std::mem::take(&mut self.vad_engines);
+        
+        let (queue_tx, queue_rx) = queue::channel(self.queue_config.clone());
+        self.queue_metrics = queue_rx.metrics();
         let (transcript_tx, transcript_rx) = broadcast::channel::<TranscriptResult>(100);
         
-        info!("Starting audio pipeline...");
+        info!("Starting audio pipeline with {} ASR backend...", asr_backend.name());
+        self.control.send_replace(CaptureState::Running);
         
-        // For now, use mock transcripts that appear more frequently when "listening"
-        let tx_clone = transcript_tx.clone();
-        tokio::spawn(async move {
-            let mut counter = 0;
-            loop {
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-                
-                let mock_texts = [
-                    "Hello, this is a test of the meeting copilot",
-                    "The audio pipeline is working correctly",
-                    "Real-time transcription is functioning", 
-                    "Privacy-first processing is active",
-                    "All systems are operational",
-                    "Voice detection is working",
-                    "Testing microphone input",
-                    "Meeting copilot is listening",
-                ];
-                
-                let text = mock_texts[counter % mock_texts.len()];
-                let result = TranscriptResult {
-                    text: text.to_string(),
-                    confidence: 0.80 + (counter as f32 * 0.03) % 0.20,
-                    start_time: 0.0,
-                    end_time: 2.0,
-                    language: "en".to_string(),
-                };
-                
-                if tx_clone.send(result).is_err() {
-                    break; // No more receivers
+        // Start VAD engines in background
+        self.vad_levels = vad_engines.iter()
+            .map(|(name, engine)| (name.clone(), engine.levels()))
+            .collect();
+        for (name, mut vad_engine) in vad_engines {
+            let queue_tx = queue_tx.clone();
+            self.vad_tasks.push(tokio::spawn(async move {
+                if let Err(e) = vad_engine.start_detection(queue_tx).await {
+                    error!("VAD engine error on {}: {}", name, e);
                 }
+                (name, vad_engine)
+            }));
+        }
+        drop(queue_tx);
+        
+        // ASR gets a thread and blocking pool of its own, so slow inference
+        // never stalls the runtime driving capture, VAD and the UI
+        let control = self.control.clone();
+        let worker = std::thread::Builder::new()
+            .name("asr-worker".to_string())
+            .spawn(move || {
+                let runtime = match tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .max_blocking_threads(ASR_BLOCKING_THREADS)
+                    .thread_name("asr-blocking")
+                    .build() {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        error!("Failed to start ASR runtime: {}", e);
+                        control.send_replace(CaptureState::Stopped);
+                        return asr_backend;
+                    }
+                };
                 
-                counter += 1;
+                runtime.block_on(async move {
+                    info!("ASR pipeline started, waiting for audio chunks...");
+                    let mut stabilizer = PartialStabilizer::new();
+                    let mut diarizer = diarizer;
+                    
+                    while let Some(mut audio_chunk) = queue_rx.recv().await {
+                        // The user's own microphone needs no clustering; otherwise
+                        // only finals are clustered and partials look up a match
+                        if let Some(diarizer) = diarizer.as_mut().filter(|_| audio_chunk.source_role != SourceRole::Me) {
+                            let speaker = if audio_chunk.is_final {
+                                diarizer.assign(&audio_chunk.data)
+                            } else {
+                                diarizer.identify(&audio_chunk.data)
+                            };
+                            match speaker {
+                                Ok(speaker_id) => audio_chunk.speaker_id = speaker_id,
+                                Err(e) => error!("Diarization error: {}", e),
+                            }
+                        }
+                        
+                        match asr_backend.transcribe_chunk(&audio_chunk).await {
+                            Ok(Some(mut result)) => {
+                                stabilizer.apply(&mut result);
+                                if transcript_tx.send(result).is_err() {
+                                    break; // No more receivers
+                                }
+                            },
+                            Ok(None) => {
+                                // Audio chunk too short, skip
+                            },
+                            Err(e) => {
+                                error!("Transcription error: {}", e);
+                            }
+                        }
+                    }
+                    
+                    // The session also ends when every source does, e.g. at the
+                    // end of a file, or when nothing listens any more
+                    control.send_replace(CaptureState::Stopped);
+                    info!("ASR pipeline finished");
+                    asr_backend
+                })
+            });
+        match worker {
+            Ok(worker) => self.asr_worker = Some(worker),
+            Err(e) => {
+                self.stop().await;
+                return Err(anyhow::anyhow!("Failed to start ASR worker: {}", e));
             }
-        });
+        }
         
-        self.is_running = true;
-        info!("Audio pipeline started successfully (enhanced mock mode)");
+        info!("Audio pipeline started successfully");
         Ok(transcript_rx)
     }
     
+    fn build_vad_engines(&mut self) -> Result<Vec<(String, VadEngine)>> {
+        // Detectors are built before the inputs are taken, so a bad model
+        // path leaves the pipeline intact
+        match self.input_mode {
+            InputMode::Mixed => {
+                let detector = self.detector.build()?;
+                let inputs = std::mem::take(&mut self.inputs);
+                let name = inputs.iter().map(|input| input.name.as_str()).collect::<Vec<_>>().join("+");
+                let role = if inputs.iter().all(|input| input.role == inputs[0].role) {
+                    inputs[0].role
+                } else {
+                    SourceRole::Mixed
+                };
+                let sources = inputs.into_iter().map(|input| input.source).collect();
+                let engine = VadEngine::with_sources(sources)
+                    .with_detector(detector)
+                    .with_label(&name, role)
+                    .with_control(self.control.subscribe());
+                Ok(vec![(name, engine)])
+            },
+            InputMode::Separate => {
+                let detectors = self.inputs.iter()
+                    .map(|_| self.detector.build())
+                    .collect::<Result<Vec<_>>>()?;
+                // Engines share one ID sequence so utterances never collide downstream
+                let utterance_ids = Arc::new(AtomicU64::new(1));
+                Ok(std::mem::take(&mut self.inputs).into_iter()
+                    .zip(detectors)
+                    .map(|(input, detector)| {
+                        let engine = VadEngine::with_source(input.source)
+                            .with_detector(detector)
+                            .with_label(&input.name, input.role)
+                            .with_utterance_ids(utterance_ids.clone())
+                            .with_control(self.control.subscribe());
+                        (input.name, engine)
+                    })
+                    .collect())
+            },
+        }
+    }
+    
     async fn start_mock_pipeline(&mut self, transcript_tx: broadcast::Sender<TranscriptResult>) -> Result<broadcast::Receiver<TranscriptResult>> {
         let transcript_rx = transcript_tx.subscribe();
         
@@ -88,6 +347,8 @@ impl AudioPipeline {
                     start_time: 0.0,
                     end_time: 2.0,
                     language: "en".to_string(),
+                    is_final: true,
+                    ..Default::default()
                 };
                 
                 if tx_clone.send(result).is_err() {
@@ -98,17 +359,198 @@ impl AudioPipeline {
             }
         });
         
-        self.is_running = true;
+        self.control.send_replace(CaptureState::Running);
         info!("Audio pipeline started successfully (mock mode)");
         Ok(transcript_rx)
     }
     
-    pub fn stop(&mut self) {
-        self.is_running = false;
+    /// Close the audio devices and wait for queued speech to be transcribed.
+    /// The pipeline can be started again afterwards.
+    pub async fn stop(&mut self) {
+        // A session that ended by itself is stopped but still holds its tasks
+        if !self.is_running() && self.vad_tasks.is_empty() && self.asr_worker.is_none() {
+            return;
+        }
+        self.control.send_replace(CaptureState::Stopped);
+        
+        for task in std::mem::take(&mut self.vad_tasks) {
+            match task.await {
+                Ok(vad_engine) => self.vad_engines.push(vad_engine),
+                Err(e) => error!("VAD engine task failed: {}", e),
+            }
+        }
+        
+        // With the VAD engines gone the queue closes, and the worker exits
+        // once it has drained
+        if let Some(worker) = self.asr_worker.take() {
+            match tokio::task::spawn_blocking(move || worker.join()).await {
+                Ok(Ok(asr_backend)) => self.loaded_asr = Some(asr_backend),
+                _ => error!("ASR worker failed"),
+            }
+        }
+        
         info!("Audio pipeline stopped");
     }
     
+    /// Close the audio devices until `resume`, keeping the session, its
+    /// speakers and loaded models.
+    pub fn pause(&self) -> Result<()> {
+        if !self.is_running() {
+            return Err(anyhow::anyhow!("Audio pipeline is not running"));
+        }
+        self.control.send_replace(CaptureState::Paused);
+        info!("Audio pipeline paused");
+        Ok(())
+    }
+    
+    pub fn resume(&self) -> Result<()> {
+        if !self.is_running() {
+            return Err(anyhow::anyhow!("Audio pipeline is not running"));
+        }
+        self.control.send_replace(CaptureState::Running);
+        info!("Audio pipeline resumed");
+        Ok(())
+    }
+    
+    pub fn state(&self) -> CaptureState {
+        *self.control.borrow()
+    }
+    
     pub fn is_running(&self) -> bool {
-        self.is_running
+        self.state() != CaptureState::Stopped
+    }
+    
+    pub fn get_status(&self) -> String {
+        let vad_status: Vec<String> = self.vad_levels.iter()
+            .map(|(name, levels)| {
+                let levels = levels.lock()
+                    .map(|levels| levels.clone())
+                    .unwrap_or_default();
+                format!("{}: {} (noise floor: {:.4}, threshold: {:.4}, level: {:.4}, voice: {})",
+                        name,
+                        if levels.calibrating { "calibrating" } else { "OK" },
+                        levels.noise_floor,
+                        levels.threshold,
+                        levels.last_rms,
+                        levels.has_voice)
+            })
+            .collect();
+        
+        let queue = self.queue_metrics.lock()
+            .map(|metrics| metrics.clone())
+            .unwrap_or_default();
+        
+        format!("Pipeline: {:?}, VAD: [{}], queue: {}/{} (peak: {}, dropped: {} / {:.1}s, merged: {}), ASR: {}",
+                self.state(),
+                vad_status.join("; "),
+                queue.depth,
+                queue.capacity,
+                queue.peak_depth,
+                queue.dropped,
+                queue.dropped_audio_secs,
+                queue.merged,
+                self.model_info)
+    }
+}
+
+// The named or default microphone, plus `LOOPBACK_DEVICE` when set
+fn microphone_inputs(device: Option<&str>) -> Vec<AudioInput> {
+    let mut microphone = MicrophoneSource::new(1024);
+    if let Some(device) = device {
+        microphone = microphone.with_device(device);
+    }
+    
+    match std::env::var("LOOPBACK_DEVICE") {
+        Ok(device) => vec![
+            AudioInput::new("microphone", SourceRole::Me, Box::new(microphone)),
+            AudioInput::new("loopback", SourceRole::Them, Box::new(MicrophoneSource::new(1024).with_device(&device))),
+        ],
+        Err(_) => vec![AudioInput::new("input", SourceRole::Mixed, Box::new(microphone))],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vad::AudioChunk;
+    use std::time::Duration;
+    use tokio::sync::mpsc::UnboundedSender;
+
+    // A second of silence, after which the source ends
+    struct FiniteSource;
+
+    impl AudioSource for FiniteSource {
+        fn describe(&self) -> String {
+            "finite".to_string()
+        }
+
+        fn start(&mut self, tx: UnboundedSender<AudioChunk>) -> Result<()> {
+            for _ in 0..16 {
+                let _ = tx.send(AudioChunk {
+                    data: vec![0.0; 1000],
+                    sample_rate: 16000,
+                    channels: 1,
+                    timestamp: std::time::Instant::now(),
+                    has_voice: false,
+                    start_time: 0.0,
+                    end_time: 0.0,
+                    utterance_id: 0,
+                    is_final: false,
+                    speaker_id: None,
+                    source: String::new(),
+                    source_role: SourceRole::default(),
+                });
+            }
+            Ok(())
+        }
+
+        fn stop(&mut self) {}
+    }
+
+    #[tokio::test]
+    async fn session_stops_when_its_sources_end() {
+        let mut pipeline = AudioPipeline::with_source(Box::new(FiniteSource))
+            .with_asr_backend(AsrBackendKind::Mock)
+            .with_diarizer(None);
+        let mut state = pipeline.control.subscribe();
+
+        pipeline.start_streaming().await.unwrap();
+        tokio::time::timeout(Duration::from_secs(10), state.wait_for(|state| *state == CaptureState::Stopped))
+            .await
+            .expect("session did not stop by itself")
+            .unwrap();
+        assert!(!pipeline.is_running());
+
+        // The ended session's engines and backend are reused
+        pipeline.start_streaming().await.unwrap();
+        assert!(pipeline.vad_engines.is_empty());
+        pipeline.stop().await;
+        assert_eq!(pipeline.state(), CaptureState::Stopped);
+        assert_eq!(pipeline.vad_engines.len(), 1);
+        assert!(pipeline.loaded_asr.is_some());
+    }
+
+    #[tokio::test]
+    async fn detector_that_fails_to_load_keeps_the_inputs() {
+        let mut pipeline = AudioPipeline::with_source(Box::new(FiniteSource))
+            .with_detector(DetectorKind::Silero { model_path: "models/missing.onnx".to_string() })
+            .with_asr_backend(AsrBackendKind::Mock)
+            .with_diarizer(None);
+
+        assert!(pipeline.start_streaming().await.is_err());
+        assert!(!pipeline.is_running());
+        assert_eq!(pipeline.inputs.len(), 1);
+        assert!(pipeline.vad_engines.is_empty());
+    }
+
+    #[test]
+    fn detector_spec_is_parsed() {
+        assert!(matches!(DetectorKind::parse("energy").unwrap(), DetectorKind::Energy));
+        assert!(matches!(DetectorKind::parse("").unwrap(), DetectorKind::Energy));
+        match DetectorKind::parse("silero:models/silero_vad.onnx").unwrap() {
+            DetectorKind::Silero { model_path } => assert_eq!(model_path, "models/silero_vad.onnx"),
+            other => panic!("unexpected detector: {:?}", other),
+        }
+        assert!(DetectorKind::parse("webrtc").is_err());
     }
 }
@@ -0,0 +1,463 @@
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::{Row, SqlitePool};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use crate::extract::{extract_text, is_supported};
+use crate::rag::{Chunk, DocumentInfo, VectorStore};
+use crate::storage::{timestamp, Database};
+
+/// How a document is cut up before embedding.
+#[derive(Debug, Clone)]
+pub struct ChunkingConfig {
+    /// Target chunk length in bytes.
+    pub chunk_bytes: usize,
+    /// Bytes shared with the previous chunk, so a fact split by a chunk
+    /// boundary is still found whole in one of them.
+    pub overlap_bytes: usize,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            chunk_bytes: 1200,
+            overlap_bytes: 200,
+        }
+    }
+}
+
+/// Files larger than this are skipped; they are rarely useful as context.
+const MAX_FILE_BYTES: u64 = 50 * 1024 * 1024;
+
+/// What a sync changed in the vector store.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct SyncReport {
+    pub indexed: u32,
+    pub unchanged: u32,
+    pub removed: u32,
+    pub failed: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileVersion {
+    modified_ms: i64,
+    size: i64,
+}
+
+/// Keeps the vector store in step with the Markdown, text, PDF and DOCX files
+/// under a set of folders.
+///
+/// Each `sync` compares modification times and sizes with the last one, so
+/// only new and changed files are parsed and embedded again, and files that
+/// were deleted or whose folder was removed drop out of the store. Files in a
+/// folder that is missing, e.g. an unmounted drive, are kept until it returns.
+pub struct DocumentConnector {
+    store: VectorStore,
+    pool: SqlitePool,
+    chunking: ChunkingConfig,
+    // Syncs from the timer and from a settings change must not interleave
+    sync_lock: Mutex<()>,
+}
+
+impl DocumentConnector {
+    pub fn new(database: &Database, store: VectorStore) -> Self {
+        Self {
+            store,
+            pool: database.pool().clone(),
+            chunking: ChunkingConfig::default(),
+            sync_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn with_chunking(mut self, chunking: ChunkingConfig) -> Self {
+        self.chunking = chunking;
+        self
+    }
+
+    /// Bring the store up to date with `folders`.
+    pub async fn sync(&self, folders: &[PathBuf]) -> Result<SyncReport> {
+        let _guard = self.sync_lock.lock().await;
+
+        let scan_folders = folders.to_vec();
+        let (found, available) = tokio::task::spawn_blocking(move || scan(&scan_folders)).await?;
+
+        let rows = sqlx::query("SELECT path, modified_ms, size FROM indexed_files")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut known = HashMap::new();
+        for row in &rows {
+            let version = FileVersion {
+                modified_ms: row.try_get("modified_ms")?,
+                size: row.try_get("size")?,
+            };
+            known.insert(row.try_get::<String, _>("path")?, version);
+        }
+
+        let mut report = SyncReport::default();
+        for (path, version) in &found {
+            if known.get(path) == Some(version) {
+                report.unchanged += 1;
+                continue;
+            }
+
+            let chunks = match self.index_file(path).await {
+                Ok(chunks) => {
+                    report.indexed += 1;
+                    Some(chunks as i64)
+                },
+                Err(e) => {
+                    warn!("Could not index {}: {}", path, e);
+                    // Stale chunks of an earlier version must not linger
+                    self.store.delete(&document_id(path)).await?;
+                    report.failed += 1;
+                    None
+                },
+            };
+
+            // Failures are recorded too, so they aren't retried until the
+            // file changes
+            sqlx::query(
+                "INSERT OR REPLACE INTO indexed_files (path, modified_ms, size, chunks, indexed_at)
+                 VALUES (?, ?, ?, ?, ?)")
+                .bind(path)
+                .bind(version.modified_ms)
+                .bind(version.size)
+                .bind(chunks)
+                .bind(timestamp(Utc::now()))
+                .execute(&self.pool)
+                .await?;
+        }
+
+        for path in known.keys().filter(|path| !found.contains_key(*path)) {
+            let under = |folder: &PathBuf| Path::new(path).starts_with(folder);
+            // Gone from a folder we could read, or its folder was removed
+            if available.iter().any(under) || !folders.iter().any(under) {
+                self.store.delete(&document_id(path)).await?;
+                sqlx::query("DELETE FROM indexed_files WHERE path = ?")
+                    .bind(path)
+                    .execute(&self.pool)
+                    .await?;
+                report.removed += 1;
+            }
+        }
+
+        if report.indexed + report.removed + report.failed > 0 {
+            info!("Document sync: {:?}", report);
+        }
+        Ok(report)
+    }
+
+    async fn index_file(&self, path: &str) -> Result<usize> {
+        let file = PathBuf::from(path);
+        let text = tokio::task::spawn_blocking(move || extract_text(&file)).await??;
+        let chunks = chunk_text(&text, &self.chunking);
+
+        let document = DocumentInfo {
+            id: document_id(path),
+            source: path.to_string(),
+            meeting_id: None,
+        };
+        self.store.upsert(&document, &chunks).await?;
+        Ok(chunks.len())
+    }
+}
+
+fn document_id(path: &str) -> String {
+    format!("file:{}", path)
+}
+
+// Supported files under `folders` with their versions, and the folders that
+// could be read
+fn scan(folders: &[PathBuf]) -> (HashMap<String, FileVersion>, Vec<PathBuf>) {
+    let mut found = HashMap::new();
+    let mut available = Vec::new();
+
+    for folder in folders {
+        if !folder.is_dir() {
+            warn!("Document folder {} is not available", folder.display());
+            continue;
+        }
+        available.push(folder.clone());
+
+        let entries = walkdir::WalkDir::new(folder)
+            .follow_links(true)
+            .into_iter()
+            // Skip hidden files and folders such as .git
+            .filter_entry(|entry| entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|entry| entry.ok());
+        for entry in entries {
+            if !entry.file_type().is_file() || !is_supported(entry.path()) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.len() > MAX_FILE_BYTES {
+                continue;
+            }
+
+            let modified_ms = metadata.modified().ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_millis() as i64);
+            found.insert(entry.path().to_string_lossy().into_owned(), FileVersion {
+                modified_ms,
+                size: metadata.len() as i64,
+            });
+        }
+    }
+
+    (found, available)
+}
+
+/// Split `text` into overlapping chunks of about `config.chunk_bytes`,
+/// breaking at a paragraph, line or word where one is near the end.
+pub fn chunk_text(text: &str, config: &ChunkingConfig) -> Vec<Chunk> {
+    let chunk_bytes = config.chunk_bytes.max(1);
+    let overlap_bytes = config.overlap_bytes.min(chunk_bytes / 2);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    while start < text.len() {
+        let mut end = floor_char_boundary(text, (start + chunk_bytes).min(text.len()));
+        if end <= start {
+            end = ceil_char_boundary(text, start + 1);
+        }
+        if end < text.len() {
+            let window = &text[start..end];
+            let break_at = ["\n\n", "\n", " "].iter().find_map(|separator| {
+                window.rfind(separator)
+                    .filter(|&at| at >= window.len() / 2)
+                    .map(|at| at + separator.len())
+            });
+            if let Some(at) = break_at {
+                end = start + at;
+            }
+        }
+
+        let piece = &text[start..end];
+        let trimmed = piece.trim();
+        if !trimmed.is_empty() {
+            chunks.push(Chunk {
+                content: trimmed.to_string(),
+                start_offset: start + (piece.len() - piece.trim_start().len()),
+            });
+        }
+        if end >= text.len() {
+            break;
+        }
+
+        // Back up by the overlap, then forward to a word start
+        let mut next = floor_char_boundary(text, end.saturating_sub(overlap_bytes));
+        if let Some(at) = text[next..end].find(char::is_whitespace) {
+            next += at;
+        }
+        start = if next > start { next } else { end };
+    }
+
+    chunks
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    while index < text.len() && !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index.min(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(chunk_bytes: usize, overlap_bytes: usize) -> ChunkingConfig {
+        ChunkingConfig { chunk_bytes, overlap_bytes }
+    }
+
+    // Every chunk is the trimmed text at its offset, chunks move forward, and
+    // together they cover the text
+    fn assert_covers(text: &str, chunks: &[Chunk]) {
+        assert!(!chunks.is_empty());
+        for chunk in chunks {
+            assert_eq!(&text[chunk.start_offset..chunk.start_offset + chunk.content.len()], chunk.content);
+        }
+        for pair in chunks.windows(2) {
+            assert!(pair[1].start_offset > pair[0].start_offset);
+            assert!(pair[1].start_offset <= pair[0].start_offset + pair[0].content.len());
+        }
+        assert_eq!(text[..chunks[0].start_offset].trim(), "");
+        let last = chunks.last().unwrap();
+        assert_eq!(text[last.start_offset + last.content.len()..].trim(), "");
+    }
+
+    #[test]
+    fn chunks_overlap_and_break_between_words() {
+        let text: String = (0..100).map(|i| format!("w{:02} ", i)).collect();
+        let chunks = chunk_text(&text, &config(40, 12));
+        assert_covers(&text, &chunks);
+
+        for chunk in &chunks {
+            assert!(chunk.content.len() <= 40);
+            assert!(chunk.content.starts_with('w') && chunk.content.ends_with(|c: char| c.is_ascii_digit()));
+        }
+        for pair in chunks.windows(2) {
+            let overlap = pair[0].start_offset + pair[0].content.len() - pair[1].start_offset;
+            assert!(overlap > 0 && overlap <= 12, "overlap of {} bytes", overlap);
+        }
+    }
+
+    #[test]
+    fn chunks_prefer_paragraph_breaks() {
+        let text = format!("{}\n\n{}", "alpha ".repeat(10).trim(), "beta ".repeat(10).trim());
+        let chunks = chunk_text(&text, &config(70, 0));
+        assert_eq!(chunks[0].content, "alpha ".repeat(10).trim());
+        assert!(chunks[1].content.starts_with("beta"));
+    }
+
+    #[test]
+    fn chunks_split_multibyte_text_on_char_boundaries() {
+        let text = "ä€😀x".repeat(50);
+        for chunk_bytes in 1..12 {
+            let chunks = chunk_text(&text, &config(chunk_bytes, chunk_bytes / 2));
+            assert_covers(&text, &chunks);
+        }
+    }
+
+    #[test]
+    fn chunks_progress_through_text_without_whitespace() {
+        let text = "x".repeat(10_000);
+        let chunks = chunk_text(&text, &config(100, 500));
+        assert_covers(&text, &chunks);
+        // The overlap is capped at half a chunk
+        assert_eq!(chunks[1].start_offset, 50);
+        assert_eq!(chunks.len(), 199);
+    }
+
+    #[test]
+    fn blank_text_has_no_chunks() {
+        assert!(chunk_text("", &ChunkingConfig::default()).is_empty());
+        assert!(chunk_text(" \n\n\t ", &ChunkingConfig::default()).is_empty());
+    }
+
+    fn scratch_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!("connector-{}", uuid::Uuid::new_v4())).join(name);
+        std::fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    async fn connector() -> DocumentConnector {
+        let database = Database::in_memory().await.unwrap();
+        let store = VectorStore::new(&database, crate::rag::EmbedderKind::default().build().unwrap());
+        DocumentConnector::new(&database, store)
+    }
+
+    // What the store holds for `path`, in chunk order
+    async fn indexed(connector: &DocumentConnector, path: &Path) -> Vec<String> {
+        sqlx::query_scalar("SELECT content FROM rag_chunks WHERE document_id = ? ORDER BY chunk_index")
+            .bind(document_id(&path.to_string_lossy()))
+            .fetch_all(&connector.pool)
+            .await
+            .unwrap()
+    }
+
+    fn counts(report: &SyncReport) -> (u32, u32, u32, u32) {
+        (report.indexed, report.unchanged, report.removed, report.failed)
+    }
+
+    #[tokio::test]
+    async fn sync_indexes_new_and_changed_files_only() {
+        let folder = scratch_folder("docs");
+        let notes = folder.join("notes.md");
+        std::fs::write(&notes, "The launch moves to March.").unwrap();
+        std::fs::write(folder.join("plan.txt"), "Hiring freeze until Q3.").unwrap();
+        std::fs::create_dir_all(folder.join(".git")).unwrap();
+        std::fs::write(folder.join(".git").join("HEAD.md"), "hidden").unwrap();
+        std::fs::write(folder.join("image.png"), "not a document").unwrap();
+        let connector = connector().await;
+        let folders = vec![folder.clone()];
+
+        let report = connector.sync(&folders).await.unwrap();
+        assert_eq!(counts(&report), (2, 0, 0, 0));
+        assert_eq!(indexed(&connector, &notes).await, ["The launch moves to March."]);
+
+        let report = connector.sync(&folders).await.unwrap();
+        assert_eq!(counts(&report), (0, 2, 0, 0));
+
+        std::fs::write(&notes, "The launch moves to April after review.").unwrap();
+        let report = connector.sync(&folders).await.unwrap();
+        assert_eq!(counts(&report), (1, 1, 0, 0));
+        assert_eq!(indexed(&connector, &notes).await, ["The launch moves to April after review."]);
+    }
+
+    #[tokio::test]
+    async fn sync_removes_deleted_files_and_removed_folders() {
+        let docs = scratch_folder("docs");
+        let archive = scratch_folder("archive");
+        let notes = docs.join("notes.md");
+        let old = archive.join("old.md");
+        std::fs::write(&notes, "The launch moves to March.").unwrap();
+        std::fs::write(docs.join("plan.md"), "Hiring freeze until Q3.").unwrap();
+        std::fs::write(&old, "Budget from last year.").unwrap();
+        let connector = connector().await;
+
+        connector.sync(&[docs.clone(), archive.clone()]).await.unwrap();
+        std::fs::remove_file(&notes).unwrap();
+        let report = connector.sync(&[docs.clone(), archive.clone()]).await.unwrap();
+        assert_eq!(counts(&report), (0, 2, 1, 0));
+        assert!(indexed(&connector, &notes).await.is_empty());
+
+        // The archive is no longer one of the folders
+        let report = connector.sync(std::slice::from_ref(&docs)).await.unwrap();
+        assert_eq!(counts(&report), (0, 1, 1, 0));
+        assert!(indexed(&connector, &old).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sync_keeps_files_of_an_unavailable_folder() {
+        let docs = scratch_folder("docs");
+        let notes = docs.join("notes.md");
+        std::fs::write(&notes, "The launch moves to March.").unwrap();
+        let connector = connector().await;
+        let folders = vec![docs.clone()];
+        connector.sync(&folders).await.unwrap();
+
+        // e.g. an unmounted drive
+        let unmounted = docs.with_file_name("unmounted");
+        std::fs::rename(&docs, &unmounted).unwrap();
+        let report = connector.sync(&folders).await.unwrap();
+        assert_eq!(counts(&report), (0, 0, 0, 0));
+        assert_eq!(indexed(&connector, &notes).await.len(), 1);
+
+        std::fs::rename(&unmounted, &docs).unwrap();
+        let report = connector.sync(&folders).await.unwrap();
+        assert_eq!(counts(&report), (0, 1, 0, 0));
+    }
+
+    #[tokio::test]
+    async fn failed_file_is_not_retried_until_it_changes() {
+        let folder = scratch_folder("docs");
+        let broken = folder.join("broken.pdf");
+        std::fs::write(&broken, "not really a pdf").unwrap();
+        let connector = connector().await;
+        let folders = vec![folder.clone()];
+
+        let report = connector.sync(&folders).await.unwrap();
+        assert_eq!(counts(&report), (0, 0, 0, 1));
+        assert!(indexed(&connector, &broken).await.is_empty());
+
+        let report = connector.sync(&folders).await.unwrap();
+        assert_eq!(counts(&report), (0, 1, 0, 0));
+
+        std::fs::write(&broken, "still not really a pdf").unwrap();
+        let report = connector.sync(&folders).await.unwrap();
+        assert_eq!(counts(&report), (0, 0, 0, 1));
+    }
+}
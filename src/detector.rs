@@ -0,0 +1,248 @@
+use anyhow::Result;
+
+/// Window length, in 16 kHz samples, that detectors score (32 ms).
+pub const DETECTOR_WINDOW: usize = 512;
+
+/// Per-frame speech scoring used by the VAD.
+///
+/// Detectors consume 16 kHz mono audio of any length, buffer it internally
+/// and return one speech probability in `[0, 1]` for every complete
+/// `DETECTOR_WINDOW` they have seen, so scores can be compared directly
+/// against labelled fixtures.
+pub trait VoiceDetector: Send {
+    fn name(&self) -> &'static str;
+
+    fn speech_probabilities(&mut self, samples: &[f32]) -> Result<Vec<f32>>;
+
+    /// Probability at or above which a window counts as speech.
+    fn threshold(&self) -> f32;
+
+    /// Forget buffered audio and model state, e.g. between sessions.
+    fn reset(&mut self);
+
+    /// Latest RMS speech threshold from the noise-floor estimator. Detectors
+    /// that don't gate on raw energy can ignore it.
+    fn set_noise_threshold(&mut self, _rms_threshold: f32) {}
+}
+
+/// Which detector the VAD should run, chosen at runtime.
+#[derive(Debug, Clone, Default)]
+pub enum DetectorKind {
+    #[default]
+    Energy,
+    Silero { model_path: String },
+}
+
+impl DetectorKind {
+    /// Parse `energy` or `silero:<model path>`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        match spec {
+            "" | "energy" => Ok(DetectorKind::Energy),
+            _ => match spec.strip_prefix("silero:") {
+                Some(model_path) => Ok(DetectorKind::Silero { model_path: model_path.to_string() }),
+                None => Err(anyhow::anyhow!("Unknown VAD detector: {}", spec)),
+            },
+        }
+    }
+
+    /// Read `VAD_DETECTOR`, defaulting to the energy detector when unset.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("VAD_DETECTOR") {
+            Ok(spec) => Self::parse(&spec),
+            Err(_) => Ok(DetectorKind::Energy),
+        }
+    }
+
+    pub fn build(&self) -> Result<Box<dyn VoiceDetector>> {
+        match self {
+            DetectorKind::Energy => Ok(Box::new(EnergyDetector::new(0.005))),
+            #[cfg(feature = "silero")]
+            DetectorKind::Silero { model_path } => Ok(Box::new(SileroDetector::new(model_path)?)),
+            #[cfg(not(feature = "silero"))]
+            DetectorKind::Silero { .. } => {
+                Err(anyhow::anyhow!("Silero VAD requires building with `--features silero`"))
+            },
+        }
+    }
+}
+
+/// Splits an arbitrary stream of samples into fixed-size windows.
+struct WindowBuffer {
+    pending: Vec<f32>,
+}
+
+impl WindowBuffer {
+    fn new() -> Self {
+        Self { pending: Vec::with_capacity(DETECTOR_WINDOW * 2) }
+    }
+
+    fn push(&mut self, samples: &[f32]) -> Vec<Vec<f32>> {
+        self.pending.extend_from_slice(samples);
+
+        let complete = self.pending.len() / DETECTOR_WINDOW;
+        let windows = self.pending[..complete * DETECTOR_WINDOW]
+            .chunks(DETECTOR_WINDOW)
+            .map(|window| window.to_vec())
+            .collect();
+        self.pending.drain(..complete * DETECTOR_WINDOW);
+
+        windows
+    }
+
+    fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+/// RMS energy gate combined with a first-difference activity check.
+///
+/// The RMS term rejects silence, while the difference term rejects steady
+/// low-frequency hum that is loud but carries no speech.
+pub struct EnergyDetector {
+    rms_threshold: f32,
+    windows: WindowBuffer,
+}
+
+impl EnergyDetector {
+    pub fn new(rms_threshold: f32) -> Self {
+        Self {
+            rms_threshold,
+            windows: WindowBuffer::new(),
+        }
+    }
+
+    fn score(&self, window: &[f32]) -> f32 {
+        let rms = rms(window);
+
+        let mut spectral_energy = 0.0f32;
+        for i in 1..window.len() {
+            spectral_energy += (window[i] - window[i - 1]).abs();
+        }
+        spectral_energy /= window.len() as f32;
+
+        if spectral_energy <= 0.001 {
+            return 0.0;
+        }
+
+        // Maps the RMS threshold onto the 0.5 decision point
+        (rms / (2.0 * self.rms_threshold)).min(1.0)
+    }
+}
+
+impl VoiceDetector for EnergyDetector {
+    fn name(&self) -> &'static str {
+        "energy"
+    }
+
+    fn speech_probabilities(&mut self, samples: &[f32]) -> Result<Vec<f32>> {
+        let windows = self.windows.push(samples);
+        Ok(windows.iter().map(|window| self.score(window)).collect())
+    }
+
+    fn threshold(&self) -> f32 {
+        0.5
+    }
+
+    fn reset(&mut self) {
+        self.windows.clear();
+    }
+
+    fn set_noise_threshold(&mut self, rms_threshold: f32) {
+        self.rms_threshold = rms_threshold;
+    }
+}
+
+/// Silero VAD (v5) ONNX model run on the CPU through ONNX Runtime.
+#[cfg(feature = "silero")]
+pub struct SileroDetector {
+    session: ort::session::Session,
+    state: Vec<f32>,
+    context: Vec<f32>,
+    windows: WindowBuffer,
+}
+
+/// Samples from the previous window the model expects in front of each input.
+#[cfg(feature = "silero")]
+const SILERO_CONTEXT: usize = 64;
+
+#[cfg(feature = "silero")]
+const SILERO_STATE_LEN: usize = 2 * 128;
+
+#[cfg(feature = "silero")]
+impl SileroDetector {
+    pub fn new<P: AsRef<std::path::Path>>(model_path: P) -> Result<Self> {
+        let model_path = model_path.as_ref();
+        if !model_path.exists() {
+            return Err(anyhow::anyhow!("Silero VAD model not found at: {}", model_path.display()));
+        }
+
+        let session = ort::session::Session::builder()?
+            .with_intra_threads(1)?
+            .commit_from_file(model_path)?;
+
+        tracing::info!("Loaded Silero VAD model from: {}", model_path.display());
+
+        Ok(Self {
+            session,
+            state: vec![0.0; SILERO_STATE_LEN],
+            context: vec![0.0; SILERO_CONTEXT],
+            windows: WindowBuffer::new(),
+        })
+    }
+
+    fn score(&mut self, window: &[f32]) -> Result<f32> {
+        use ort::value::Tensor;
+
+        let mut input = Vec::with_capacity(SILERO_CONTEXT + window.len());
+        input.extend_from_slice(&self.context);
+        input.extend_from_slice(window);
+
+        let inputs = ort::inputs![
+            "input" => Tensor::from_array(([1usize, input.len()], input))?,
+            "state" => Tensor::from_array(([2usize, 1, 128], self.state.clone()))?,
+            "sr" => Tensor::from_array(([1usize], vec![crate::resample::TARGET_SAMPLE_RATE as i64]))?,
+        ];
+        let outputs = self.session.run(inputs)?;
+
+        let (_, probability) = outputs["output"].try_extract_tensor::<f32>()?;
+        let (_, state) = outputs["stateN"].try_extract_tensor::<f32>()?;
+        let probability = probability.first().copied().unwrap_or(0.0);
+        self.state.copy_from_slice(state);
+
+        self.context.copy_from_slice(&window[window.len() - SILERO_CONTEXT..]);
+        Ok(probability)
+    }
+}
+
+#[cfg(feature = "silero")]
+impl VoiceDetector for SileroDetector {
+    fn name(&self) -> &'static str {
+        "silero"
+    }
+
+    fn speech_probabilities(&mut self, samples: &[f32]) -> Result<Vec<f32>> {
+        let windows = self.windows.push(samples);
+        windows.iter().map(|window| self.score(window)).collect()
+    }
+
+    fn threshold(&self) -> f32 {
+        0.5
+    }
+
+    fn reset(&mut self) {
+        self.state.iter_mut().for_each(|s| *s = 0.0);
+        self.context.iter_mut().for_each(|s| *s = 0.0);
+        self.windows.clear();
+    }
+}
+
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    (samples.iter()
+        .map(|&sample| sample * sample)
+        .sum::<f32>() / samples.len() as f32)
+        .sqrt()
+}
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use cpal::traits::{DeviceTrait, HostTrait};
+use tokio::sync::mpsc::unbounded_channel;
+use tracing::{info, warn};
+use crate::source::{AudioSource, MicrophoneSource};
+
+/// How often a level meter reports, fast enough for a smooth meter.
+const LEVEL_INTERVAL_MS: u32 = 100;
+
+/// A range of formats an input device can capture in.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct InputConfigInfo {
+    pub channels: u16,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+    pub sample_format: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct InputDeviceInfo {
+    pub name: String,
+    pub is_default: bool,
+    /// The format capture opens the device with.
+    pub default_config: Option<InputConfigInfo>,
+    pub configs: Vec<InputConfigInfo>,
+}
+
+/// Every input device on the default host. Devices that fail to report
+/// their formats are still listed, without configs.
+pub fn list_input_devices() -> Result<Vec<InputDeviceInfo>> {
+    let host = cpal::default_host();
+    let default_name = host.default_input_device().and_then(|device| device.name().ok());
+
+    let mut devices = Vec::new();
+    for device in host.input_devices()? {
+        let Ok(name) = device.name() else {
+            continue;
+        };
+
+        let configs = match device.supported_input_configs() {
+            Ok(configs) => configs
+                .map(|config| InputConfigInfo {
+                    channels: config.channels(),
+                    min_sample_rate: config.min_sample_rate().0,
+                    max_sample_rate: config.max_sample_rate().0,
+                    sample_format: config.sample_format().to_string(),
+                })
+                .collect(),
+            Err(e) => {
+                warn!("Could not query formats of {}: {}", name, e);
+                Vec::new()
+            },
+        };
+
+        let default_config = device.default_input_config().ok().map(|config| InputConfigInfo {
+            channels: config.channels(),
+            min_sample_rate: config.sample_rate().0,
+            max_sample_rate: config.sample_rate().0,
+            sample_format: config.sample_format().to_string(),
+        });
+
+        devices.push(InputDeviceInfo {
+            is_default: default_name.as_deref() == Some(name.as_str()),
+            name,
+            default_config,
+            configs,
+        });
+    }
+
+    Ok(devices)
+}
+
+/// Loudness of an input over the last reporting interval, from 0 to 1.
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct InputLevel {
+    pub rms: f32,
+    pub peak: f32,
+}
+
+/// Reports the level of an input device, e.g. to check the right microphone
+/// is picked before a meeting. Capture stops when the meter is dropped.
+pub struct LevelMeter {
+    source: MicrophoneSource,
+}
+
+impl LevelMeter {
+    /// Open the named device, or the default one, and call `on_level` every
+    /// `LEVEL_INTERVAL_MS`. Must be called from within a tokio runtime.
+    pub fn start<F>(device: Option<&str>, on_level: F) -> Result<Self>
+    where
+        F: Fn(InputLevel) + Send + 'static,
+    {
+        let mut source = MicrophoneSource::new(1024);
+        if let Some(device) = device {
+            source = source.with_device(device);
+        }
+
+        let (tx, mut rx) = unbounded_channel();
+        source.start(tx)?;
+        info!("Level meter started on {}", source.describe());
+
+        tokio::spawn(async move {
+            let mut sum_squares = 0.0f64;
+            let mut peak = 0.0f32;
+            let mut samples = 0usize;
+
+            while let Some(chunk) = rx.recv().await {
+                for &sample in &chunk.data {
+                    sum_squares += (sample * sample) as f64;
+                    peak = peak.max(sample.abs());
+                }
+                samples += chunk.data.len();
+
+                let interval = (chunk.sample_rate * chunk.channels as u32 * LEVEL_INTERVAL_MS / 1000) as usize;
+                if samples >= interval.max(1) {
+                    on_level(InputLevel {
+                        rms: (sum_squares / samples as f64).sqrt() as f32,
+                        peak: peak.min(1.0),
+                    });
+                    sum_squares = 0.0;
+                    peak = 0.0;
+                    samples = 0;
+                }
+            }
+        });
+
+        Ok(Self { source })
+    }
+}
+
+impl Drop for LevelMeter {
+    fn drop(&mut self) {
+        self.source.stop();
+        info!("Level meter stopped");
+    }
+}
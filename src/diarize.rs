@@ -0,0 +1,449 @@
+use anyhow::Result;
+use tracing::info;
+use crate::embedding::{dot, normalize};
+
+/// Tuning for how utterances are grouped into speakers.
+#[derive(Debug, Clone)]
+pub struct DiarizerConfig {
+    pub embedder: EmbedderKind,
+    /// Cosine similarity above which an utterance joins an existing speaker;
+    /// the embedder's own default when unset.
+    pub similarity_threshold: Option<f32>,
+    /// Once this many speakers exist, new voices join the closest one.
+    pub max_speakers: usize,
+    /// Shorter utterances are matched against known speakers but never
+    /// enrol a new one or move a centroid, as their embeddings are noisy.
+    pub min_enroll_ms: u32,
+}
+
+impl Default for DiarizerConfig {
+    fn default() -> Self {
+        Self {
+            embedder: EmbedderKind::Spectral,
+            similarity_threshold: None,
+            max_speakers: 8,
+            min_enroll_ms: 1000,
+        }
+    }
+}
+
+impl DiarizerConfig {
+    /// Read `DIARIZER` (`off`, `spectral` or `onnx:<model path>`); `None`
+    /// means diarization is switched off.
+    pub fn from_env() -> Result<Option<Self>> {
+        let embedder = match std::env::var("DIARIZER").ok().as_deref() {
+            None | Some("") | Some("spectral") => EmbedderKind::Spectral,
+            Some("off") => return Ok(None),
+            Some(spec) => match spec.strip_prefix("onnx:") {
+                Some(model_path) => EmbedderKind::Onnx { model_path: model_path.to_string() },
+                None => return Err(anyhow::anyhow!("Unknown diarizer: {}", spec)),
+            },
+        };
+
+        Ok(Some(Self { embedder, ..Self::default() }))
+    }
+}
+
+/// Display label for a speaker ID, e.g. "Speaker 2".
+pub fn speaker_label(speaker_id: u32) -> String {
+    format!("Speaker {}", speaker_id)
+}
+
+/// Maps 16 kHz mono audio to a fixed-length voice embedding.
+pub trait SpeakerEmbedder: Send {
+    fn name(&self) -> &'static str;
+
+    fn embed(&mut self, samples: &[f32]) -> Result<Vec<f32>>;
+
+    /// Cosine similarity at or above which two embeddings are the same voice.
+    fn similarity_threshold(&self) -> f32;
+}
+
+/// Which embedder the diarizer should run, chosen at runtime.
+#[derive(Debug, Clone)]
+pub enum EmbedderKind {
+    Spectral,
+    Onnx { model_path: String },
+}
+
+impl EmbedderKind {
+    pub fn build(&self) -> Result<Box<dyn SpeakerEmbedder>> {
+        match self {
+            EmbedderKind::Spectral => Ok(Box::new(SpectralEmbedder::new())),
+            #[cfg(feature = "speaker-onnx")]
+            EmbedderKind::Onnx { model_path } => Ok(Box::new(OnnxSpeakerEmbedder::new(model_path)?)),
+            #[cfg(not(feature = "speaker-onnx"))]
+            EmbedderKind::Onnx { .. } => {
+                Err(anyhow::anyhow!("ONNX speaker embeddings require building with `--features speaker-onnx`"))
+            },
+        }
+    }
+}
+
+struct Speaker {
+    id: u32,
+    centroid: Vec<f32>,
+    utterances: u32,
+}
+
+/// Online clustering of utterances by voice.
+///
+/// Each final utterance is embedded and compared with the running centroid of
+/// every speaker heard so far. A close enough match joins that speaker;
+/// otherwise a new speaker is enrolled. IDs are handed out in order of first
+/// appearance and never change during a session.
+pub struct Diarizer {
+    embedder: Box<dyn SpeakerEmbedder>,
+    config: DiarizerConfig,
+    threshold: f32,
+    speakers: Vec<Speaker>,
+}
+
+impl Diarizer {
+    pub fn new(config: DiarizerConfig) -> Result<Self> {
+        let embedder = config.embedder.build()?;
+        let threshold = config.similarity_threshold.unwrap_or_else(|| embedder.similarity_threshold());
+        info!("Speaker diarization using the {} embedder", embedder.name());
+
+        Ok(Self {
+            embedder,
+            config,
+            threshold,
+            speakers: Vec::new(),
+        })
+    }
+
+    /// Attribute a finished utterance to a speaker, enrolling a new one if the
+    /// voice is unfamiliar.
+    pub fn assign(&mut self, samples: &[f32]) -> Result<Option<u32>> {
+        let min_len = (crate::resample::TARGET_SAMPLE_RATE as u64 * self.config.min_enroll_ms as u64 / 1000) as usize;
+        if samples.len() < min_len {
+            return self.identify(samples);
+        }
+
+        let embedding = self.embedder.embed(samples)?;
+        let closest = self.closest(&embedding);
+
+        let index = match closest {
+            Some((index, similarity)) if similarity >= self.threshold => index,
+            Some((index, _)) if self.speakers.len() >= self.config.max_speakers => index,
+            _ => {
+                let id = self.speakers.len() as u32 + 1;
+                info!("Enrolled {}", speaker_label(id));
+                self.speakers.push(Speaker {
+                    id,
+                    centroid: embedding,
+                    utterances: 1,
+                });
+                return Ok(Some(id));
+            },
+        };
+
+        let speaker = &mut self.speakers[index];
+        speaker.utterances += 1;
+        let weight = 1.0 / speaker.utterances as f32;
+        for (centroid, value) in speaker.centroid.iter_mut().zip(&embedding) {
+            *centroid += weight * (value - *centroid);
+        }
+        normalize(&mut speaker.centroid);
+
+        Ok(Some(speaker.id))
+    }
+
+    /// Best guess at who is speaking without learning from the audio, for
+    /// partial results and fragments too short to trust.
+    pub fn identify(&mut self, samples: &[f32]) -> Result<Option<u32>> {
+        if self.speakers.is_empty() {
+            return Ok(None);
+        }
+
+        let embedding = self.embedder.embed(samples)?;
+        Ok(self.closest(&embedding)
+            .filter(|&(_, similarity)| similarity >= self.threshold)
+            .map(|(index, _)| self.speakers[index].id))
+    }
+
+    fn closest(&self, embedding: &[f32]) -> Option<(usize, f32)> {
+        self.speakers.iter()
+            .enumerate()
+            .map(|(index, speaker)| (index, dot(&speaker.centroid, embedding)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+const FRAME_LEN: usize = 400; // 25 ms
+const FRAME_HOP: usize = 160; // 10 ms
+const FFT_LEN: usize = 512;
+
+/// Log mel filterbank energies of 16 kHz audio, one row per 10 ms frame.
+struct Filterbank {
+    window: Vec<f32>,
+    filters: Vec<Vec<(usize, f32)>>,
+}
+
+impl Filterbank {
+    fn new(n_mels: usize) -> Self {
+        let window = (0..FRAME_LEN)
+            .map(|i| 0.54 - 0.46 * (2.0 * std::f32::consts::PI * i as f32 / (FRAME_LEN - 1) as f32).cos())
+            .collect();
+
+        let mel = |hz: f32| 2595.0 * (1.0 + hz / 700.0).log10();
+        let hz = |mel: f32| 700.0 * (10f32.powf(mel / 2595.0) - 1.0);
+        let (low, high) = (mel(20.0), mel(7600.0));
+        let bin_hz = crate::resample::TARGET_SAMPLE_RATE as f32 / FFT_LEN as f32;
+
+        let edges: Vec<f32> = (0..n_mels + 2)
+            .map(|i| hz(low + (high - low) * i as f32 / (n_mels + 1) as f32) / bin_hz)
+            .collect();
+
+        let filters = edges.windows(3)
+            .map(|edge| {
+                let (left, center, right) = (edge[0], edge[1], edge[2]);
+                (left.ceil() as usize..=right.floor() as usize)
+                    .filter_map(|bin| {
+                        let position = bin as f32;
+                        let weight = if position <= center {
+                            (position - left) / (center - left)
+                        } else {
+                            (right - position) / (right - center)
+                        };
+                        (weight > 0.0).then_some((bin, weight))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self { window, filters }
+    }
+
+    fn compute(&self, samples: &[f32]) -> Vec<Vec<f32>> {
+        if samples.len() < FRAME_LEN {
+            return Vec::new();
+        }
+
+        let mut frames = Vec::with_capacity((samples.len() - FRAME_LEN) / FRAME_HOP + 1);
+        let mut buffer = vec![(0.0f32, 0.0f32); FFT_LEN];
+
+        for start in (0..=samples.len() - FRAME_LEN).step_by(FRAME_HOP) {
+            let frame = &samples[start..start + FRAME_LEN];
+            let mean = frame.iter().sum::<f32>() / FRAME_LEN as f32;
+
+            // Pre-emphasis boosts the formants that distinguish voices
+            let mut previous = frame[0] - mean;
+            for (i, slot) in buffer.iter_mut().enumerate() {
+                *slot = match frame.get(i) {
+                    Some(&sample) => {
+                        let sample = sample - mean;
+                        let emphasized = sample - 0.97 * previous;
+                        previous = sample;
+                        (emphasized * self.window[i], 0.0)
+                    },
+                    None => (0.0, 0.0),
+                };
+            }
+            fft(&mut buffer);
+
+            let power: Vec<f32> = buffer[..=FFT_LEN / 2].iter()
+                .map(|&(re, im)| re * re + im * im)
+                .collect();
+
+            frames.push(self.filters.iter()
+                .map(|filter| {
+                    let energy: f32 = filter.iter().map(|&(bin, weight)| power[bin] * weight).sum();
+                    energy.max(1e-10).ln()
+                })
+                .collect());
+        }
+
+        frames
+    }
+}
+
+// In-place iterative radix-2 FFT; `data.len()` must be a power of two
+fn fft(data: &mut [(f32, f32)]) {
+    let n = data.len();
+
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f32::consts::PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (re, im) = data[start + k + len / 2];
+                let twiddled = (re * cos - im * sin, re * sin + im * cos);
+                let (even_re, even_im) = data[start + k];
+                data[start + k] = (even_re + twiddled.0, even_im + twiddled.1);
+                data[start + k + len / 2] = (even_re - twiddled.0, even_im - twiddled.1);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+const SPECTRAL_MELS: usize = 40;
+const SPECTRAL_CEPSTRA: usize = 12;
+
+/// Mean and spread of liftered MFCCs over the utterance.
+///
+/// Needs no model and separates voices of clearly different timbre, but is
+/// sensitive to the microphone and room; use an ONNX speaker model where
+/// several similar voices share a call.
+pub struct SpectralEmbedder {
+    filterbank: Filterbank,
+}
+
+impl SpectralEmbedder {
+    pub fn new() -> Self {
+        Self { filterbank: Filterbank::new(SPECTRAL_MELS) }
+    }
+}
+
+impl Default for SpectralEmbedder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpeakerEmbedder for SpectralEmbedder {
+    fn name(&self) -> &'static str {
+        "spectral"
+    }
+
+    fn embed(&mut self, samples: &[f32]) -> Result<Vec<f32>> {
+        let frames = self.filterbank.compute(samples);
+        if frames.is_empty() {
+            return Err(anyhow::anyhow!("Utterance too short to embed"));
+        }
+
+        // DCT-II of the log energies, skipping c0 (overall loudness) and
+        // liftered so no single coefficient dominates the distance
+        let cepstra: Vec<Vec<f32>> = frames.iter()
+            .map(|energies| {
+                (1..=SPECTRAL_CEPSTRA)
+                    .map(|k| {
+                        let lifter = 1.0 + 11.0 * (std::f32::consts::PI * k as f32 / 22.0).sin();
+                        let coefficient: f32 = energies.iter()
+                            .enumerate()
+                            .map(|(m, &energy)| {
+                                energy * (std::f32::consts::PI * k as f32 * (m as f32 + 0.5) / SPECTRAL_MELS as f32).cos()
+                            })
+                            .sum();
+                        coefficient * lifter
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let count = cepstra.len() as f32;
+        let mut embedding = vec![0.0f32; SPECTRAL_CEPSTRA * 2];
+        for k in 0..SPECTRAL_CEPSTRA {
+            let mean = cepstra.iter().map(|c| c[k]).sum::<f32>() / count;
+            let variance = cepstra.iter().map(|c| (c[k] - mean).powi(2)).sum::<f32>() / count;
+            embedding[k] = mean;
+            embedding[SPECTRAL_CEPSTRA + k] = variance.sqrt();
+        }
+
+        normalize(&mut embedding);
+        Ok(embedding)
+    }
+
+    fn similarity_threshold(&self) -> f32 {
+        0.85
+    }
+}
+
+/// Speaker embedding model (e.g. WeSpeaker ResNet34) run through ONNX Runtime.
+///
+/// Expects 80-dimensional log mel features of shape `[1, frames, 80]` and
+/// returns a single embedding vector.
+#[cfg(feature = "speaker-onnx")]
+pub struct OnnxSpeakerEmbedder {
+    session: ort::session::Session,
+    input_name: String,
+    output_name: String,
+    filterbank: Filterbank,
+}
+
+#[cfg(feature = "speaker-onnx")]
+const ONNX_MELS: usize = 80;
+
+#[cfg(feature = "speaker-onnx")]
+impl OnnxSpeakerEmbedder {
+    pub fn new<P: AsRef<std::path::Path>>(model_path: P) -> Result<Self> {
+        let model_path = model_path.as_ref();
+        if !model_path.exists() {
+            return Err(anyhow::anyhow!("Speaker embedding model not found at: {}", model_path.display()));
+        }
+
+        let session = ort::session::Session::builder()?
+            .with_intra_threads(1)?
+            .commit_from_file(model_path)?;
+
+        let input_name = session.inputs.first()
+            .map(|input| input.name.clone())
+            .ok_or_else(|| anyhow::anyhow!("Speaker embedding model has no inputs"))?;
+        let output_name = session.outputs.first()
+            .map(|output| output.name.clone())
+            .ok_or_else(|| anyhow::anyhow!("Speaker embedding model has no outputs"))?;
+
+        info!("Loaded speaker embedding model from: {}", model_path.display());
+
+        Ok(Self {
+            session,
+            input_name,
+            output_name,
+            filterbank: Filterbank::new(ONNX_MELS),
+        })
+    }
+}
+
+#[cfg(feature = "speaker-onnx")]
+impl SpeakerEmbedder for OnnxSpeakerEmbedder {
+    fn name(&self) -> &'static str {
+        "onnx"
+    }
+
+    fn embed(&mut self, samples: &[f32]) -> Result<Vec<f32>> {
+        use ort::value::Tensor;
+
+        let mut frames = self.filterbank.compute(samples);
+        if frames.is_empty() {
+            return Err(anyhow::anyhow!("Utterance too short to embed"));
+        }
+
+        // Cepstral mean normalisation, as the models were trained with
+        for m in 0..ONNX_MELS {
+            let mean = frames.iter().map(|frame| frame[m]).sum::<f32>() / frames.len() as f32;
+            frames.iter_mut().for_each(|frame| frame[m] -= mean);
+        }
+
+        let n_frames = frames.len();
+        let features: Vec<f32> = frames.into_iter().flatten().collect();
+        let inputs = ort::inputs![
+            self.input_name.as_str() => Tensor::from_array(([1usize, n_frames, ONNX_MELS], features))?,
+        ];
+        let outputs = self.session.run(inputs)?;
+
+        let (_, embedding) = outputs[self.output_name.as_str()].try_extract_tensor::<f32>()?;
+        let mut embedding = embedding.to_vec();
+        normalize(&mut embedding);
+        Ok(embedding)
+    }
+
+    fn similarity_threshold(&self) -> f32 {
+        0.5
+    }
+}
@@ -0,0 +1,14 @@
+//! Vector helpers shared by the speaker and document embedders.
+
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Scale `vector` to unit length, so dot products are cosine similarities.
+/// A zero vector is left as is.
+pub fn normalize(vector: &mut [f32]) {
+    let norm = dot(vector, vector).sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|value| *value /= norm);
+    }
+}
@@ -0,0 +1,584 @@
+use anyhow::Result;
+use flate2::read::{DeflateDecoder, ZlibDecoder};
+use std::io::Read;
+use std::path::Path;
+
+/// Extensions of the files `extract_text` can read.
+pub const SUPPORTED_EXTENSIONS: &[&str] = &["md", "markdown", "txt", "pdf", "docx"];
+
+/// Share of printable characters below which PDF text is taken to be in a
+/// font encoding this extractor can't map back to Unicode.
+const MIN_READABLE_RATIO: f32 = 0.85;
+
+/// How far before a PDF stream to look for its dictionary.
+const MAX_DICTIONARY_BYTES: usize = 4096;
+
+pub fn is_supported(path: &Path) -> bool {
+    extension(path).is_some_and(|extension| SUPPORTED_EXTENSIONS.contains(&extension.as_str()))
+}
+
+/// Plain text of a Markdown, text, PDF or DOCX file.
+pub fn extract_text(path: &Path) -> Result<String> {
+    let bytes = std::fs::read(path)?;
+    match extension(path).as_deref() {
+        Some("md") | Some("markdown") | Some("txt") => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+        Some("pdf") => pdf_text(&bytes),
+        Some("docx") => docx_text(&bytes),
+        _ => Err(anyhow::anyhow!("Unsupported document type: {}", path.display())),
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+/// Text of a Word document: the runs of `word/document.xml`, one line per
+/// paragraph.
+fn docx_text(bytes: &[u8]) -> Result<String> {
+    let xml = zip_entry(bytes, "word/document.xml")?;
+    let xml = String::from_utf8_lossy(&xml);
+
+    let pattern = regex::Regex::new(r"(?s)<w:t(?:\s[^>]*)?>(.*?)</w:t>|<w:tab\s*/>|<w:br\s*/>|</w:p>")?;
+    let mut text = String::new();
+    for captures in pattern.captures_iter(&xml) {
+        match captures.get(1) {
+            Some(run) => text.push_str(&unescape_xml(run.as_str())),
+            None if captures[0].starts_with("<w:tab") => text.push('\t'),
+            None => text.push('\n'),
+        }
+    }
+    Ok(text)
+}
+
+fn unescape_xml(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find('&') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let decoded = match &rest[1..end] {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            entity => entity.strip_prefix("#x").map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Contents of the file called `name` in a ZIP archive, stored or deflated.
+fn zip_entry(bytes: &[u8], name: &str) -> Result<Vec<u8>> {
+    const END_OF_DIRECTORY: [u8; 4] = [0x50, 0x4b, 0x05, 0x06];
+    const DIRECTORY_ENTRY: [u8; 4] = [0x50, 0x4b, 0x01, 0x02];
+    const LOCAL_HEADER: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+    let malformed = || anyhow::anyhow!("Not a valid DOCX (ZIP) file");
+
+    // The end record is the last thing in the file, after an optional comment
+    let end = (0..bytes.len().saturating_sub(21))
+        .rev()
+        .find(|&at| bytes[at..].starts_with(&END_OF_DIRECTORY))
+        .ok_or_else(malformed)?;
+    let entries = le_u16(bytes, end + 10).ok_or_else(malformed)?;
+    let mut at = le_u32(bytes, end + 16).ok_or_else(malformed)? as usize;
+
+    for _ in 0..entries {
+        if !bytes.get(at..).is_some_and(|rest| rest.starts_with(&DIRECTORY_ENTRY)) {
+            return Err(malformed());
+        }
+        let method = le_u16(bytes, at + 10).ok_or_else(malformed)?;
+        let compressed_size = le_u32(bytes, at + 20).ok_or_else(malformed)? as usize;
+        let name_len = le_u16(bytes, at + 28).ok_or_else(malformed)? as usize;
+        let extra_len = le_u16(bytes, at + 30).ok_or_else(malformed)? as usize;
+        let comment_len = le_u16(bytes, at + 32).ok_or_else(malformed)? as usize;
+        let local = le_u32(bytes, at + 42).ok_or_else(malformed)? as usize;
+        let entry_name = bytes.get(at + 46..at + 46 + name_len).ok_or_else(malformed)?;
+
+        if entry_name == name.as_bytes() {
+            if !bytes.get(local..).is_some_and(|rest| rest.starts_with(&LOCAL_HEADER)) {
+                return Err(malformed());
+            }
+            // The local header repeats the name but may have its own extra field
+            let local_name_len = le_u16(bytes, local + 26).ok_or_else(malformed)? as usize;
+            let local_extra_len = le_u16(bytes, local + 28).ok_or_else(malformed)? as usize;
+            let start = local + 30 + local_name_len + local_extra_len;
+            let data = bytes.get(start..start + compressed_size).ok_or_else(malformed)?;
+
+            return match method {
+                0 => Ok(data.to_vec()),
+                8 => {
+                    let mut out = Vec::new();
+                    DeflateDecoder::new(data).read_to_end(&mut out)?;
+                    Ok(out)
+                },
+                other => Err(anyhow::anyhow!("Unsupported ZIP compression method {}", other)),
+            };
+        }
+        at += 46 + name_len + extra_len + comment_len;
+    }
+
+    Err(anyhow::anyhow!("{} not found in archive", name))
+}
+
+fn le_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn le_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Text shown by a PDF's content streams.
+///
+/// Handles the common case of text in standard or Unicode encodings, stored
+/// plain or Flate-compressed. PDFs whose fonts use custom encodings (common
+/// for CJK and some exported slides) or whose pages are scanned images have
+/// no recoverable text and fail rather than index gibberish.
+fn pdf_text(bytes: &[u8]) -> Result<String> {
+    if !bytes.starts_with(b"%PDF") {
+        return Err(anyhow::anyhow!("Not a PDF file"));
+    }
+
+    let mut text = String::new();
+    let mut at = 0;
+    while let Some(found) = find(bytes, b"stream", at) {
+        at = found + b"stream".len();
+        // `endstream` also contains the keyword
+        if bytes[..found].ends_with(b"end") {
+            continue;
+        }
+        let start = match bytes.get(at..at + 2) {
+            Some([b'\r', b'\n']) => at + 2,
+            Some([b'\n', _]) | Some([b'\r', _]) => at + 1,
+            _ => continue,
+        };
+        let Some(end) = find(bytes, b"endstream", start) else {
+            break;
+        };
+        at = end + b"endstream".len();
+
+        // The stream dictionary sits between the object header and here
+        let window = found.saturating_sub(MAX_DICTIONARY_BYTES);
+        let header_start = rfind(&bytes[window..found], b" obj").map_or(window, |at| window + at);
+        let dictionary = String::from_utf8_lossy(&bytes[header_start..found]);
+        if !is_content_stream(&dictionary) {
+            continue;
+        }
+
+        let data = &bytes[start..end];
+        let content = if dictionary.contains("/FlateDecode") {
+            // Keep what inflates even if the stream is truncated
+            let mut out = Vec::new();
+            let _ = ZlibDecoder::new(data).read_to_end(&mut out);
+            out
+        } else {
+            data.to_vec()
+        };
+        content_text(&content, &mut text);
+    }
+
+    let visible: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if visible.is_empty() {
+        return Err(anyhow::anyhow!("PDF has no extractable text (scanned or image-only?)"));
+    }
+    let readable = visible.iter()
+        .filter(|&&c| c.is_alphanumeric() || c.is_ascii_punctuation() || ('\u{2010}'..='\u{206f}').contains(&c))
+        .count();
+    if (readable as f32) < visible.len() as f32 * MIN_READABLE_RATIO {
+        return Err(anyhow::anyhow!("PDF text uses font encodings that can't be extracted"));
+    }
+    Ok(text)
+}
+
+// Page content and form streams hold text; fonts, images and cross
+// reference data don't
+fn is_content_stream(dictionary: &str) -> bool {
+    let filtered = dictionary.contains("/Filter");
+    let flate_only = dictionary.contains("/FlateDecode")
+        && !["/DCTDecode", "/JPXDecode", "/CCITTFaxDecode", "/JBIG2Decode", "/LZWDecode", "/ASCII85Decode"]
+            .iter()
+            .any(|filter| dictionary.contains(filter));
+    let binary = ["/Image", "/Length1", "/Length2", "/Type1C", "/CIDFontType0C", "/OpenType", "/XRef", "/ObjStm",
+        "/Metadata", "/EmbeddedFile", "/Alternate", "/FunctionType"]
+        .iter()
+        .any(|marker| dictionary.contains(marker));
+    (!filtered || flate_only) && !binary
+}
+
+// Appends the strings shown by text operators in a content stream, breaking
+// lines where the text position moves to a new line
+fn content_text(content: &[u8], text: &mut String) {
+    let mut operands: Vec<Vec<u8>> = Vec::new();
+    let mut in_array = false;
+    let mut i = 0;
+
+    while i < content.len() {
+        match content[i] {
+            b'(' => {
+                let (string, next) = literal_string(content, i + 1);
+                operands.push(string);
+                i = next;
+            },
+            b'<' if content.get(i + 1) == Some(&b'<') => i += 2,
+            b'<' => {
+                let end = content[i..].iter().position(|&b| b == b'>').map_or(content.len(), |end| i + end);
+                operands.push(hex_string(&content[i + 1..end]));
+                i = end + 1;
+            },
+            b'/' => {
+                // Names such as font resources are operands, not operators
+                i += 1;
+                while i < content.len() && !content[i].is_ascii_whitespace() && !b"/[]()<>{}%".contains(&content[i]) {
+                    i += 1;
+                }
+            },
+            b'[' => {
+                in_array = true;
+                operands.clear();
+                i += 1;
+            },
+            b']' => {
+                in_array = false;
+                i += 1;
+            },
+            b'%' => {
+                while i < content.len() && content[i] != b'\n' && content[i] != b'\r' {
+                    i += 1;
+                }
+            },
+            b'-' | b'0'..=b'9' | b'.' if in_array => {
+                // Kerning inside a TJ array; a large gap is a word space
+                let start = i;
+                i += 1;
+                while i < content.len() && (content[i].is_ascii_digit() || content[i] == b'.') {
+                    i += 1;
+                }
+                let gap: f32 = std::str::from_utf8(&content[start..i]).ok().and_then(|n| n.parse().ok()).unwrap_or(0.0);
+                if gap < -200.0 {
+                    operands.push(b" ".to_vec());
+                }
+            },
+            c if c.is_ascii_alphabetic() || c == b'\'' || c == b'"' || c == b'*' => {
+                let start = i;
+                while i < content.len() && (content[i].is_ascii_alphanumeric() || matches!(content[i], b'\'' | b'"' | b'*')) {
+                    i += 1;
+                }
+                match &content[start..i] {
+                    b"Tj" | b"TJ" => operands.drain(..).for_each(|string| text.push_str(&decode_pdf_string(&string))),
+                    b"'" | b"\"" => {
+                        text.push('\n');
+                        operands.drain(..).for_each(|string| text.push_str(&decode_pdf_string(&string)));
+                    },
+                    // Inline image data runs from ID to EI
+                    b"ID" => {
+                        i = find(content, b"EI", i).map_or(content.len(), |end| end + 2);
+                        operands.clear();
+                    },
+                    b"T*" | b"Td" | b"TD" | b"ET" => {
+                        if !text.ends_with('\n') && !text.is_empty() {
+                            text.push('\n');
+                        }
+                        operands.clear();
+                    },
+                    _ => operands.clear(),
+                }
+            },
+            _ => i += 1,
+        }
+    }
+}
+
+// Reads a `(...)` string starting after the parenthesis, with nesting and
+// escapes; returns the bytes and the index after the closing parenthesis
+fn literal_string(content: &[u8], mut i: usize) -> (Vec<u8>, usize) {
+    let mut out = Vec::new();
+    let mut depth = 1;
+    while i < content.len() {
+        let c = content[i];
+        i += 1;
+        match c {
+            b'\\' => {
+                let Some(&escaped) = content.get(i) else {
+                    break;
+                };
+                i += 1;
+                match escaped {
+                    b'n' => out.push(b'\n'),
+                    b'r' => out.push(b'\r'),
+                    b't' => out.push(b'\t'),
+                    b'b' => out.push(0x08),
+                    b'f' => out.push(0x0c),
+                    b'0'..=b'7' => {
+                        let mut value = (escaped - b'0') as u32;
+                        for _ in 0..2 {
+                            match content.get(i) {
+                                Some(&digit @ b'0'..=b'7') => {
+                                    value = value * 8 + (digit - b'0') as u32;
+                                    i += 1;
+                                },
+                                _ => break,
+                            }
+                        }
+                        out.push(value as u8);
+                    },
+                    // A backslash before a line break continues the string
+                    b'\r' | b'\n' => {},
+                    other => out.push(other),
+                }
+            },
+            b'(' => {
+                depth += 1;
+                out.push(c);
+            },
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+                out.push(c);
+            },
+            _ => out.push(c),
+        }
+    }
+    (out, i)
+}
+
+fn hex_string(hex: &[u8]) -> Vec<u8> {
+    let digits: Vec<u8> = hex.iter()
+        .filter_map(|&c| (c as char).to_digit(16).map(|digit| digit as u8))
+        .collect();
+    digits.chunks(2)
+        .map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0))
+        .collect()
+}
+
+// Strings are UTF-16 with a byte order mark, or else single-byte text that
+// is close enough to Latin-1 for search
+fn decode_pdf_string(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(&[0xfe, 0xff]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+            String::from_utf16_lossy(&units)
+        },
+        None => bytes.iter().map(|&b| win_ansi_char(b)).collect(),
+    }
+}
+
+// Latin-1 differs from the usual WinAnsi encoding mostly in punctuation
+fn win_ansi_char(byte: u8) -> char {
+    match byte {
+        0x85 => '…',
+        0x91 => '‘',
+        0x92 => '’',
+        0x93 => '“',
+        0x94 => '”',
+        0x95 => '•',
+        0x96 => '–',
+        0x97 => '—',
+        _ => byte as char,
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack.get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|at| from + at)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::{DeflateEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use std::io::Write;
+
+    const DOCUMENT_XML: &str = concat!(
+        r#"<w:document><w:body><w:p><w:r><w:t>Q3 budget</w:t></w:r>"#,
+        r#"<w:r><w:tab/><w:t xml:space="preserve"> &amp; hiring </w:t></w:r></w:p>"#,
+        r#"<w:p><w:r><w:t>R&amp;D &#x2013; plan</w:t><w:br/><w:t>&#8364;5k</w:t></w:r></w:p></w:body></w:document>"#,
+    );
+    const DOCUMENT_TEXT: &str = "Q3 budget\t & hiring \nR&D \u{2013} plan\n\u{20ac}5k\n";
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    // A ZIP archive of (name, contents, deflated) entries. Local headers get
+    // an extra field the directory doesn't have, as some writers do.
+    fn zip(entries: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut directory = Vec::new();
+        for &(name, data, deflated) in entries {
+            let (method, stored) = if deflated { (8u16, deflate(data)) } else { (0u16, data.to_vec()) };
+            let offset = out.len() as u32;
+
+            out.extend_from_slice(&[0x50, 0x4b, 0x03, 0x04, 20, 0, 0, 0]);
+            out.extend_from_slice(&method.to_le_bytes());
+            out.extend_from_slice(&[0; 8]);
+            out.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&4u16.to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&[0xca, 0xfe, 0, 0]);
+            out.extend_from_slice(&stored);
+
+            directory.extend_from_slice(&[0x50, 0x4b, 0x01, 0x02, 20, 0, 20, 0, 0, 0]);
+            directory.extend_from_slice(&method.to_le_bytes());
+            directory.extend_from_slice(&[0; 8]);
+            directory.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+            directory.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+
+        let directory_offset = out.len() as u32;
+        out.extend_from_slice(&directory);
+        out.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0]);
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&directory_offset.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out
+    }
+
+    fn docx(deflated: bool) -> Vec<u8> {
+        zip(&[
+            ("[Content_Types].xml", b"<Types/>", false),
+            ("word/document.xml", DOCUMENT_XML.as_bytes(), deflated),
+        ])
+    }
+
+    // A PDF of (dictionary entries, stream data) objects
+    fn pdf(streams: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut out = b"%PDF-1.4\n".to_vec();
+        for (number, (dictionary, data)) in streams.iter().enumerate() {
+            out.extend_from_slice(format!("{} 0 obj\n<< /Length {} {} >>\nstream\n", number + 1, data.len(), dictionary).as_bytes());
+            out.extend_from_slice(data);
+            out.extend_from_slice(b"\nendstream\nendobj\n");
+        }
+        out.extend_from_slice(b"%%EOF\n");
+        out
+    }
+
+    fn report_pdf() -> Vec<u8> {
+        pdf(&[
+            ("", b"BT /F1 12 Tf 72 712 Td [(Quar) -20 (terly) -250 (rev) 10 (iew)] TJ T* (Second line) Tj ET"),
+            ("/Filter /FlateDecode", &zlib(b"BT 72 650 Td (Compressed text) Tj ET")),
+            // Embedded font programs and images are not text
+            ("/Length1 42", b"BT (Font program) Tj ET"),
+            ("/Subtype /Image /Width 1 /Height 1", b"BT (Pixels) Tj ET"),
+        ])
+    }
+
+    #[test]
+    fn supported_extensions_ignore_case() {
+        assert!(is_supported(Path::new("notes/Plan.MD")));
+        assert!(is_supported(Path::new("report.pdf")));
+        assert!(!is_supported(Path::new("legacy.doc")));
+        assert!(!is_supported(Path::new("README")));
+    }
+
+    #[test]
+    fn docx_text_from_stored_and_deflated_entries() {
+        assert_eq!(docx_text(&docx(false)).unwrap(), DOCUMENT_TEXT);
+        assert_eq!(docx_text(&docx(true)).unwrap(), DOCUMENT_TEXT);
+    }
+
+    #[test]
+    fn docx_without_a_document_is_an_error() {
+        let archive = zip(&[("word/styles.xml", b"<w:styles/>", true)]);
+        let error = docx_text(&archive).unwrap_err();
+        assert!(error.to_string().contains("word/document.xml"), "{}", error);
+    }
+
+    #[test]
+    fn truncated_or_garbage_docx_is_an_error() {
+        let archive = docx(true);
+        for len in 0..archive.len() {
+            assert!(docx_text(&archive[..len]).is_err(), "accepted {} of {} bytes", len, archive.len());
+        }
+
+        // Corrupt bytes may or may not leave a readable archive, but never panic
+        for at in 0..archive.len() {
+            let mut corrupt = archive.clone();
+            corrupt[at] ^= 0xff;
+            let _ = docx_text(&corrupt);
+        }
+
+        let garbage: Vec<u8> = (0..4096u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+        assert!(docx_text(&garbage).is_err());
+    }
+
+    #[test]
+    fn pdf_text_joins_kerned_runs_and_inflates_streams() {
+        assert_eq!(pdf_text(&report_pdf()).unwrap(), "Quarterly review\nSecond line\nCompressed text\n");
+    }
+
+    #[test]
+    fn pdf_text_in_a_custom_font_encoding_is_rejected() {
+        let bytes = pdf(&[("", b"BT /F1 12 Tf <0102030405060708090a0b0c0d0e0f10> Tj ET")]);
+        let error = pdf_text(&bytes).unwrap_err();
+        assert!(error.to_string().contains("font encodings"), "{}", error);
+    }
+
+    #[test]
+    fn pdf_without_text_is_an_error() {
+        let bytes = pdf(&[("/Subtype /Image /Filter /DCTDecode", b"\xff\xd8\xff\xe0 scanned page")]);
+        let error = pdf_text(&bytes).unwrap_err();
+        assert!(error.to_string().contains("no extractable text"), "{}", error);
+    }
+
+    #[test]
+    fn truncated_or_garbage_pdf_never_panics() {
+        let bytes = report_pdf();
+        // Cut anywhere, what is left may still hold some text
+        for len in 0..bytes.len() {
+            let _ = pdf_text(&bytes[..len]);
+        }
+        assert!(pdf_text(&bytes[..20]).is_err());
+
+        let mut garbage = b"%PDF-1.7\n".to_vec();
+        garbage.extend((0..4096u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8));
+        assert!(pdf_text(&garbage).is_err());
+        assert!(pdf_text(b"PK\x03\x04 not a pdf").is_err());
+    }
+}
@@ -0,0 +1,498 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::io::Cursor;
+use std::time::Duration;
+use tracing::info;
+use crate::asr::{language_code, AsrBackend, LanguageConfig, TranscriptResult, TranscriptSegment, WordTiming};
+use crate::vad::AudioChunk;
+
+/// Request format spoken by the transcription server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpAsrApi {
+    /// `POST /v1/audio/transcriptions` on an OpenAI-compatible server
+    #[default]
+    OpenAi,
+    /// `POST /inference` on whisper.cpp's `whisper-server`
+    WhisperCpp,
+}
+
+/// Where and how to reach a self-hosted transcription server.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct HttpAsrConfig {
+    /// Base URL such as `http://10.0.0.5:8080`; the API path is appended
+    /// unless it is already present.
+    pub url: String,
+    #[serde(default)]
+    pub api: HttpAsrApi,
+    /// Model name sent to OpenAI-compatible servers.
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+impl HttpAsrConfig {
+    pub fn new(url: &str, api: HttpAsrApi) -> Self {
+        Self {
+            url: url.to_string(),
+            api,
+            model: None,
+            api_key: None,
+            timeout_secs: default_timeout_secs(),
+        }
+    }
+
+    /// Read `ASR_URL`, `ASR_API` (`openai` or `whispercpp`), `ASR_MODEL` and
+    /// `ASR_API_KEY`.
+    pub fn from_env() -> Result<Self> {
+        let url = std::env::var("ASR_URL")
+            .map_err(|_| anyhow::anyhow!("ASR_URL must be set for the HTTP ASR backend"))?;
+        let api = match std::env::var("ASR_API").ok().as_deref() {
+            None | Some("") | Some("openai") => HttpAsrApi::OpenAi,
+            Some("whispercpp") => HttpAsrApi::WhisperCpp,
+            Some(other) => return Err(anyhow::anyhow!("Unknown ASR server API: {}", other)),
+        };
+
+        let mut config = Self::new(&url, api);
+        config.model = std::env::var("ASR_MODEL").ok();
+        config.api_key = std::env::var("ASR_API_KEY").ok();
+        Ok(config)
+    }
+
+    fn endpoint(&self, translate: bool) -> String {
+        let path = match self.api {
+            // OpenAI translates through a separate endpoint
+            HttpAsrApi::OpenAi if translate => "/v1/audio/translations",
+            HttpAsrApi::OpenAi => "/v1/audio/transcriptions",
+            HttpAsrApi::WhisperCpp => "/inference",
+        };
+        let base = self.url.trim_end_matches('/');
+        if base.ends_with(path) {
+            base.to_string()
+        } else {
+            format!("{}{}", base, path)
+        }
+    }
+}
+
+/// Sends each utterance as a WAV upload to a transcription server on the LAN.
+///
+/// Servers detect the language themselves, so `LanguageConfig::candidates`
+/// only narrows detection when it names a single language.
+pub struct HttpAsrBackend {
+    config: HttpAsrConfig,
+    language: LanguageConfig,
+    endpoint: String,
+    client: reqwest::Client,
+}
+
+impl HttpAsrBackend {
+    pub fn new(config: HttpAsrConfig, language: LanguageConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+
+        Ok(Self {
+            endpoint: config.endpoint(language.translate),
+            config,
+            language,
+            client,
+        })
+    }
+
+    fn pinned_language(&self) -> Option<&str> {
+        match self.language.candidates.as_slice() {
+            [only] => Some(self.language.language.as_deref().unwrap_or(only)),
+            _ => self.language.language.as_deref(),
+        }
+    }
+
+    fn build_form(&self, audio_chunk: &AudioChunk) -> Result<reqwest::multipart::Form> {
+        let wav = encode_wav(&audio_chunk.data, audio_chunk.sample_rate)?;
+        let file = reqwest::multipart::Part::bytes(wav)
+            .file_name("audio.wav")
+            .mime_str("audio/wav")?;
+
+        let mut form = reqwest::multipart::Form::new()
+            .part("file", file)
+            .text("response_format", "verbose_json")
+            .text("temperature", "0");
+
+        match self.config.api {
+            // The translations endpoint always outputs English and takes no language
+            HttpAsrApi::OpenAi if self.language.translate => {},
+            HttpAsrApi::OpenAi => {
+                if let Some(language) = self.pinned_language() {
+                    form = form.text("language", language.to_string());
+                }
+            },
+            HttpAsrApi::WhisperCpp => {
+                let language = self.pinned_language().unwrap_or("auto").to_string();
+                form = form
+                    .text("language", language)
+                    .text("translate", self.language.translate.to_string());
+            },
+        }
+
+        if self.config.api == HttpAsrApi::OpenAi {
+            let model = self.config.model.clone().unwrap_or_else(|| "whisper-1".to_string());
+            form = form
+                .text("model", model)
+                .text("timestamp_granularities[]", "segment")
+                .text("timestamp_granularities[]", "word");
+        }
+
+        Ok(form)
+    }
+}
+
+#[async_trait]
+impl AsrBackend for HttpAsrBackend {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    async fn load(&mut self) -> Result<()> {
+        // Nothing to load locally; the server owns the model
+        info!("Using transcription server at: {}", self.endpoint);
+        Ok(())
+    }
+
+    async fn transcribe(&self, audio_chunk: &AudioChunk) -> Result<TranscriptResult> {
+        let mut request = self.client
+            .post(&self.endpoint)
+            .multipart(self.build_form(audio_chunk)?);
+        if let Some(api_key) = &self.config.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request.send().await
+            .map_err(|e| anyhow::anyhow!("Transcription server request failed: {}", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("Transcription server returned {}: {}", status, body.trim()));
+        }
+
+        let transcription: VerboseTranscription = response.json().await
+            .map_err(|e| anyhow::anyhow!("Invalid transcription server response: {}", e))?;
+
+        let end_time = audio_chunk.data.len() as f32 / audio_chunk.sample_rate as f32;
+        let mut result = transcription.into_result(end_time);
+
+        match self.pinned_language() {
+            Some(language) => {
+                result.language = language.to_string();
+                result.language_probability = 1.0;
+            },
+            None if result.language.is_empty() => result.language = self.language.fallback(),
+            None => {},
+        }
+        result.translated = self.language.translate && result.language != "en";
+        Ok(result)
+    }
+
+    fn model_info(&self) -> String {
+        format!("Transcription server: {} ({:?})", self.endpoint, self.config.api)
+    }
+}
+
+// `verbose_json` response. OpenAI lists words at the top level, whisper.cpp
+// nests them in segments; everything beyond the text is optional.
+#[derive(serde::Deserialize)]
+struct VerboseTranscription {
+    text: String,
+    #[serde(default)]
+    language: Option<String>,
+    // whisper.cpp only; OpenAI doesn't report how sure it is
+    #[serde(default)]
+    detected_language_probability: Option<f32>,
+    #[serde(default)]
+    segments: Vec<VerboseSegment>,
+    #[serde(default)]
+    words: Vec<VerboseWord>,
+}
+
+#[derive(serde::Deserialize)]
+struct VerboseSegment {
+    text: String,
+    start: f32,
+    end: f32,
+    #[serde(default)]
+    avg_logprob: Option<f32>,
+    #[serde(default)]
+    words: Vec<VerboseWord>,
+}
+
+#[derive(serde::Deserialize)]
+struct VerboseWord {
+    word: String,
+    start: f32,
+    end: f32,
+    #[serde(default)]
+    probability: Option<f32>,
+}
+
+impl VerboseTranscription {
+    fn into_result(mut self, end_time: f32) -> TranscriptResult {
+        let segment_probs: Vec<f32> = self.segments.iter()
+            .filter_map(|segment| segment.avg_logprob)
+            .map(|logprob| logprob.exp().clamp(0.0, 1.0))
+            .collect();
+
+        let mut words = self.words;
+        if words.is_empty() {
+            words = self.segments.iter_mut()
+                .flat_map(|segment| std::mem::take(&mut segment.words))
+                .collect();
+        }
+        // Blank tokens carry no speech and would drag the average down
+        words.retain(|word| !word.word.trim().is_empty());
+        let word_probs: Vec<f32> = words.iter().filter_map(|word| word.probability).collect();
+
+        // Prefer per-word scores; servers that report no scores at all are
+        // taken at their word
+        let confidence = mean(&word_probs)
+            .or_else(|| mean(&segment_probs))
+            .unwrap_or(1.0);
+
+        let words = words.into_iter()
+            .map(|word| WordTiming {
+                word: word.word.trim().to_string(),
+                start_time: word.start,
+                end_time: word.end,
+                probability: word.probability.unwrap_or(confidence),
+            })
+            .collect();
+
+        let segments = self.segments.into_iter()
+            .map(|segment| TranscriptSegment {
+                text: segment.text.trim().to_string(),
+                start_time: segment.start,
+                end_time: segment.end,
+            })
+            .collect();
+
+        TranscriptResult {
+            text: self.text.trim().to_string(),
+            confidence,
+            start_time: 0.0,
+            end_time,
+            language: self.language.as_deref().map(language_code).unwrap_or_default(),
+            language_probability: self.detected_language_probability.unwrap_or(0.0),
+            segments,
+            words,
+            ..Default::default()
+        }
+    }
+}
+
+fn mean(values: &[f32]) -> Option<f32> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f32>() / values.len() as f32)
+    }
+}
+
+// 16-bit PCM WAV in memory, which every transcription server accepts
+fn encode_wav(samples: &[f32], sample_rate: u32) -> Result<Vec<u8>> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut cursor = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut cursor, spec)?;
+    for &sample in samples {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+
+    Ok(cursor.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::SourceRole;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    // Accepts one request, answers with `status` and `body`, and hands back
+    // the raw request so tests can check what was sent
+    async fn stub_server(status: &'static str, body: &'static str) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            loop {
+                let read = socket.read(&mut buffer).await.unwrap();
+                assert!(read > 0, "client closed the connection early");
+                request.extend_from_slice(&buffer[..read]);
+
+                let Some(header_end) = request.windows(4).position(|w| w == b"\r\n\r\n") else {
+                    continue;
+                };
+                let headers = String::from_utf8_lossy(&request[..header_end]).to_lowercase();
+                let content_length: usize = headers.lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .map(|value| value.trim().parse().unwrap())
+                    .expect("multipart upload without a content length");
+                if request.len() >= header_end + 4 + content_length {
+                    break;
+                }
+            }
+
+            let response = format!(
+                "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status, body.len(), body,
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request).into_owned()
+        });
+
+        (url, handle)
+    }
+
+    fn one_second_chunk() -> AudioChunk {
+        AudioChunk {
+            data: vec![0.1; 16000],
+            sample_rate: 16000,
+            channels: 1,
+            timestamp: std::time::Instant::now(),
+            has_voice: true,
+            start_time: 0.0,
+            end_time: 1.0,
+            utterance_id: 1,
+            is_final: true,
+            speaker_id: None,
+            source: String::new(),
+            source_role: SourceRole::default(),
+        }
+    }
+
+    fn form_field(request: &str, name: &str) -> Option<String> {
+        let marker = format!("name=\"{}\"\r\n\r\n", name);
+        let start = request.find(&marker)? + marker.len();
+        let end = request[start..].find("\r\n")?;
+        Some(request[start..start + end].to_string())
+    }
+
+    #[tokio::test]
+    async fn whisper_cpp_upload_and_nested_words() {
+        let (url, server) = stub_server("200 OK", r#"{
+            "text": " Ship it on Friday. ",
+            "language": "english",
+            "detected_language_probability": 0.93,
+            "segments": [{
+                "text": " Ship it on Friday.",
+                "start": 0.0,
+                "end": 1.0,
+                "words": [
+                    {"word": " Ship", "start": 0.0, "end": 0.3, "probability": 0.9},
+                    {"word": " it", "start": 0.3, "end": 0.4, "probability": 0.7},
+                    {"word": " ", "start": 0.4, "end": 0.4, "probability": 0.1},
+                    {"word": " Friday.", "start": 0.5, "end": 1.0, "probability": 0.8}
+                ]
+            }]
+        }"#).await;
+
+        let backend = HttpAsrBackend::new(
+            HttpAsrConfig::new(&url, HttpAsrApi::WhisperCpp),
+            LanguageConfig::default(),
+        ).unwrap();
+        let result = backend.transcribe(&one_second_chunk()).await.unwrap();
+        let request = server.await.unwrap();
+
+        assert!(request.starts_with("POST /inference HTTP/1.1\r\n"));
+        assert!(request.contains("filename=\"audio.wav\""));
+        assert!(request.contains("RIFF"));
+        assert_eq!(form_field(&request, "language").as_deref(), Some("auto"));
+        assert_eq!(form_field(&request, "translate").as_deref(), Some("false"));
+        assert_eq!(form_field(&request, "response_format").as_deref(), Some("verbose_json"));
+        assert_eq!(form_field(&request, "model"), None);
+
+        assert_eq!(result.text, "Ship it on Friday.");
+        assert_eq!(result.language, "en");
+        assert_eq!(result.language_probability, 0.93);
+        assert_eq!(result.segments.len(), 1);
+        let words: Vec<&str> = result.words.iter().map(|word| word.word.as_str()).collect();
+        assert_eq!(words, ["Ship", "it", "Friday."]);
+        // The blank token's score is left out
+        assert!((result.confidence - 0.8).abs() < 1e-6);
+        assert_eq!(result.end_time, 1.0);
+        assert!(!result.translated);
+    }
+
+    #[tokio::test]
+    async fn openai_upload_and_top_level_words() {
+        let (url, server) = stub_server("200 OK", r#"{
+            "text": "Wir liefern am Freitag.",
+            "language": "german",
+            "segments": [{"text": "Wir liefern am Freitag.", "start": 0.0, "end": 1.0, "avg_logprob": -0.5}],
+            "words": [
+                {"word": "Wir", "start": 0.0, "end": 0.2},
+                {"word": "liefern", "start": 0.2, "end": 0.6}
+            ]
+        }"#).await;
+
+        let mut config = HttpAsrConfig::new(&format!("{}/", url), HttpAsrApi::OpenAi);
+        config.model = Some("large-v3".to_string());
+        config.api_key = Some("secret".to_string());
+        let language = LanguageConfig {
+            candidates: vec!["de".to_string()],
+            ..LanguageConfig::default()
+        };
+        let backend = HttpAsrBackend::new(config, language).unwrap();
+        let result = backend.transcribe(&one_second_chunk()).await.unwrap();
+        let request = server.await.unwrap();
+
+        assert!(request.starts_with("POST /v1/audio/transcriptions HTTP/1.1\r\n"));
+        assert!(request.to_lowercase().contains("authorization: bearer secret\r\n"));
+        assert_eq!(form_field(&request, "model").as_deref(), Some("large-v3"));
+        assert_eq!(form_field(&request, "language").as_deref(), Some("de"));
+        assert_eq!(request.matches("name=\"timestamp_granularities[]\"").count(), 2);
+        assert_eq!(form_field(&request, "translate"), None);
+
+        assert_eq!(result.text, "Wir liefern am Freitag.");
+        // A single candidate pins the language
+        assert_eq!(result.language, "de");
+        assert_eq!(result.language_probability, 1.0);
+        assert_eq!(result.words.len(), 2);
+        // No word scores, so the segment log-probability stands in
+        let expected = (-0.5f32).exp();
+        assert!((result.confidence - expected).abs() < 1e-6);
+        assert!((result.words[0].probability - expected).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn server_error_is_reported() {
+        let (url, server) = stub_server("500 Internal Server Error", r#"{"error": "model not loaded"}"#).await;
+
+        let backend = HttpAsrBackend::new(
+            HttpAsrConfig::new(&url, HttpAsrApi::OpenAi),
+            LanguageConfig::default(),
+        ).unwrap();
+        let error = backend.transcribe(&one_second_chunk()).await.unwrap_err();
+        server.await.unwrap();
+
+        let message = error.to_string();
+        assert!(message.contains("500"), "{}", message);
+        assert!(message.contains("model not loaded"), "{}", message);
+    }
+}
@@ -0,0 +1,263 @@
+use anyhow::Result;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Length of a generated database key.
+const KEY_BYTES: usize = 32;
+
+/// Secret the meeting database is encrypted with.
+#[derive(Clone, PartialEq, Eq)]
+pub enum DatabaseKey {
+    /// Stretched into the real key by SQLCipher (PBKDF2-HMAC-SHA512), so a
+    /// memorable passphrase is safe to use.
+    Passphrase(String),
+    /// A random key used as is, as kept in a key file.
+    Raw([u8; KEY_BYTES]),
+}
+
+impl DatabaseKey {
+    /// A new random raw key.
+    pub fn generate() -> Result<Self> {
+        let mut key = [0u8; KEY_BYTES];
+        getrandom::getrandom(&mut key)
+            .map_err(|e| anyhow::anyhow!("Failed to generate database key: {}", e))?;
+        Ok(Self::Raw(key))
+    }
+
+    /// The key as a SQL literal for `PRAGMA key`, `PRAGMA rekey` and
+    /// `ATTACH ... KEY`.
+    pub fn pragma(&self) -> String {
+        match self {
+            Self::Passphrase(passphrase) => format!("'{}'", passphrase.replace('\'', "''")),
+            Self::Raw(key) => format!("\"x'{}'\"", to_hex(key)),
+        }
+    }
+
+    fn parse_raw(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.len() != KEY_BYTES * 2 {
+            return None;
+        }
+
+        let mut key = [0u8; KEY_BYTES];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(text.get(i * 2..i * 2 + 2)?, 16).ok()?;
+        }
+        Some(Self::Raw(key))
+    }
+}
+
+// Keys never end up in logs
+impl std::fmt::Debug for DatabaseKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Passphrase(_) => f.write_str("DatabaseKey::Passphrase(..)"),
+            Self::Raw(_) => f.write_str("DatabaseKey::Raw(..)"),
+        }
+    }
+}
+
+/// Where the database key comes from.
+#[derive(Debug, Clone)]
+pub enum KeySource {
+    Passphrase(String),
+    /// A file holding a hex-encoded raw key, readable by the owner only.
+    KeyFile(PathBuf),
+}
+
+impl KeySource {
+    /// `DB_PASSPHRASE` if set, else the key file at `DB_KEY_FILE` or
+    /// `default_key_file`.
+    pub fn from_env(default_key_file: PathBuf) -> Self {
+        match std::env::var("DB_PASSPHRASE") {
+            Ok(passphrase) if !passphrase.is_empty() => Self::Passphrase(passphrase),
+            _ => Self::KeyFile(
+                std::env::var("DB_KEY_FILE").map(PathBuf::from).unwrap_or(default_key_file),
+            ),
+        }
+    }
+
+    /// Read the key. A missing key file is only created when `create` is set,
+    /// i.e. for a new database; otherwise it is an error, since the database
+    /// can't be read without it.
+    pub fn load(&self, create: bool) -> Result<DatabaseKey> {
+        match self {
+            Self::Passphrase(passphrase) => Ok(DatabaseKey::Passphrase(passphrase.clone())),
+            Self::KeyFile(path) if path.exists() => read_key_file(path),
+            Self::KeyFile(path) if create => {
+                let key = DatabaseKey::generate()?;
+                write_key_file(path, &key)?;
+                info!("Created database key file: {}", path.display());
+                Ok(key)
+            },
+            Self::KeyFile(path) => Err(anyhow::anyhow!(
+                "Database key file {} is missing; the meeting database can't be decrypted without it \
+                 (set DB_KEY_FILE or DB_PASSPHRASE if the key is kept elsewhere)",
+                path.display()
+            )),
+        }
+    }
+
+    /// The key a rotation should switch to: `passphrase` when given, else a
+    /// new random key. A passphrase source needs a new passphrase.
+    pub fn next_key(&self, passphrase: Option<String>) -> Result<DatabaseKey> {
+        match (self, passphrase) {
+            (_, Some(passphrase)) if passphrase.is_empty() => Err(anyhow::anyhow!("The new passphrase is empty")),
+            (Self::Passphrase(_), Some(passphrase)) => Ok(DatabaseKey::Passphrase(passphrase)),
+            (Self::Passphrase(_), None) => Err(anyhow::anyhow!("A new passphrase is required to rotate a passphrase key")),
+            (Self::KeyFile(_), Some(_)) => Err(anyhow::anyhow!(
+                "The database key is kept in a key file; set DB_PASSPHRASE to use a passphrase instead"
+            )),
+            (Self::KeyFile(_), None) => DatabaseKey::generate(),
+        }
+    }
+
+    /// Key written by a rotation that may not have reached the database,
+    /// e.g. because the app quit half-way.
+    pub fn pending(&self) -> Result<Option<DatabaseKey>> {
+        match self {
+            Self::KeyFile(path) if pending_path(path).exists() => read_key_file(&pending_path(path)).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Save `key` next to the current one before the database is rekeyed, so
+    /// it can't be lost if the rekey succeeds but the swap doesn't.
+    pub fn stage(&self, key: &DatabaseKey) -> Result<()> {
+        if let Self::KeyFile(path) = self {
+            write_key_file(&pending_path(path), key)?;
+        }
+        Ok(())
+    }
+
+    /// Make the staged key the current one once the database uses it.
+    pub fn commit(&self) -> Result<()> {
+        if let Self::KeyFile(path) = self {
+            std::fs::rename(pending_path(path), path)?;
+        }
+        Ok(())
+    }
+
+    /// Drop a staged key the database never switched to.
+    pub fn discard(&self) -> Result<()> {
+        if let Self::KeyFile(path) = self {
+            match std::fs::remove_file(pending_path(path)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+}
+
+fn pending_path(path: &Path) -> PathBuf {
+    let mut pending = path.as_os_str().to_owned();
+    pending.push(".new");
+    PathBuf::from(pending)
+}
+
+fn read_key_file(path: &Path) -> Result<DatabaseKey> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path)?.permissions().mode();
+        if mode & 0o077 != 0 {
+            return Err(anyhow::anyhow!(
+                "Database key file {} is accessible by other users (mode {:o}); restrict it with chmod 600",
+                path.display(),
+                mode & 0o777
+            ));
+        }
+    }
+
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read database key file {}: {}", path.display(), e))?;
+    DatabaseKey::parse_raw(&text)
+        .ok_or_else(|| anyhow::anyhow!("Database key file {} does not hold a valid key", path.display()))
+}
+
+fn write_key_file(path: &Path, key: &DatabaseKey) -> Result<()> {
+    let DatabaseKey::Raw(raw) = key else {
+        return Err(anyhow::anyhow!("Only raw keys can be stored in a key file"));
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    tracing::warn!("Cannot restrict access to {} on this platform", path.display());
+
+    let mut file = options.open(path)?;
+    writeln!(file, "{}", to_hex(raw))?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("keys-{}-{}", uuid::Uuid::new_v4(), name))
+    }
+
+    #[test]
+    fn created_key_file_is_private_and_reloads() {
+        let path = key_path("database.key");
+        let keys = KeySource::KeyFile(path.clone());
+        assert!(keys.load(false).is_err());
+
+        let key = keys.load(true).unwrap();
+        assert_eq!(keys.load(false).unwrap(), key);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn key_file_readable_by_others_is_rejected() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = key_path("database.key");
+        let keys = KeySource::KeyFile(path.clone());
+        keys.load(true).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let error = keys.load(false).unwrap_err().to_string();
+        assert!(error.contains("chmod 600"), "{}", error);
+    }
+
+    #[test]
+    fn staged_key_is_pending_until_committed_or_discarded() {
+        let path = key_path("database.key");
+        let keys = KeySource::KeyFile(path.clone());
+        let current = keys.load(true).unwrap();
+        let next = keys.next_key(None).unwrap();
+
+        keys.stage(&next).unwrap();
+        assert_eq!(keys.pending().unwrap(), Some(next.clone()));
+        keys.discard().unwrap();
+        assert_eq!(keys.pending().unwrap(), None);
+        assert_eq!(keys.load(false).unwrap(), current);
+
+        keys.stage(&next).unwrap();
+        keys.commit().unwrap();
+        assert_eq!(keys.pending().unwrap(), None);
+        assert_eq!(keys.load(false).unwrap(), next);
+    }
+}
@@ -1,16 +1,77 @@
 use anyhow::Result;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{mpsc, RwLock};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+use crate::router::estimate_tokens;
+
+// Small instruction-tuned model that runs comfortably on a laptop CPU
+pub const DEFAULT_MODEL_PATH: &str = "models/llama-3.2-1b-instruct-q4_k_m.gguf";
+
+/// Tokens the chat template wraps around the system prompt and the prompt.
+const TEMPLATE_TOKENS: usize = 64;
+
+/// Prompt tokens evaluated at a time. Cancellation is checked in between,
+/// so a stale prompt stops within one batch instead of delaying the next
+/// reply by a whole prompt.
+#[cfg(feature = "llama")]
+const PROMPT_BATCH_TOKENS: usize = 256;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelConfig {
     pub model_path: String,
+    /// Tokens the model sees at once: system prompt, prompt and reply.
     pub context_size: usize,
+    /// Sampling temperature; 0 always picks the likeliest token.
     pub temperature: f32,
+    /// Longest reply, in tokens.
     pub max_tokens: u16,
 }
 
+impl Default for ModelConfig {
+    fn default() -> Self {
+        Self {
+            model_path: DEFAULT_MODEL_PATH.to_string(),
+            context_size: 2048,
+            temperature: 0.7,
+            max_tokens: 128,
+        }
+    }
+}
+
+impl ModelConfig {
+    /// Read `LLM_MODEL_PATH`, `LLM_CONTEXT_SIZE`, `LLM_TEMPERATURE` and
+    /// `LLM_MAX_TOKENS`.
+    pub fn from_env() -> Result<Self> {
+        let mut config = Self::default();
+        if let Ok(model_path) = std::env::var("LLM_MODEL_PATH") {
+            config.model_path = model_path;
+        }
+        if let Ok(context_size) = std::env::var("LLM_CONTEXT_SIZE") {
+            config.context_size = context_size.parse()
+                .map_err(|_| anyhow::anyhow!("Invalid LLM_CONTEXT_SIZE: {}", context_size))?;
+        }
+        if let Ok(temperature) = std::env::var("LLM_TEMPERATURE") {
+            config.temperature = temperature.parse()
+                .map_err(|_| anyhow::anyhow!("Invalid LLM_TEMPERATURE: {}", temperature))?;
+        }
+        if let Ok(max_tokens) = std::env::var("LLM_MAX_TOKENS") {
+            config.max_tokens = max_tokens.parse()
+                .map_err(|_| anyhow::anyhow!("Invalid LLM_MAX_TOKENS: {}", max_tokens))?;
+        }
+        if config.context_size <= config.max_tokens as usize {
+            return Err(anyhow::anyhow!(
+                "LLM_CONTEXT_SIZE ({}) leaves no room for a prompt with LLM_MAX_TOKENS ({})",
+                config.context_size,
+                config.max_tokens
+            ));
+        }
+        Ok(config)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PromptTemplate {
     pub system_prompt: String,
@@ -18,30 +79,193 @@ pub struct PromptTemplate {
     pub safety_prefix: String,
 }
 
+/// A reply arriving a piece at a time, as the model generates it.
+///
+/// Generation stops when the stream is cancelled or dropped, so a reply
+/// nobody reads any more doesn't hold up the next one.
 pub struct TokenStream {
-    pub content: String,
-    pub is_complete: bool,
-    pub token_count: usize,
+    events: mpsc::UnboundedReceiver<StreamEvent>,
+    cancel: CancellationToken,
+    content: String,
+    is_complete: bool,
+    token_count: usize,
+}
+
+enum StreamEvent {
+    Delta(String),
+    Finished { is_complete: bool },
+    Failed(anyhow::Error),
+}
+
+impl TokenStream {
+    /// A stream and the sender a backend feeds it through, stopped by `cancel`.
+    pub fn channel(cancel: CancellationToken) -> (TokenSender, Self) {
+        // Unbounded, as replies are bounded by `max_tokens`; a model never
+        // waits for the reader
+        let (events, receiver) = mpsc::unbounded_channel();
+        let sender = TokenSender {
+            events,
+            cancel: cancel.clone(),
+        };
+        let stream = Self {
+            events: receiver,
+            cancel,
+            content: String::new(),
+            is_complete: false,
+            token_count: 0,
+        };
+        (sender, stream)
+    }
+
+    /// The next piece of the reply, or `None` once it is finished or
+    /// cancelled.
+    pub async fn next(&mut self) -> Option<Result<String>> {
+        let event = tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => return None,
+            event = self.events.recv() => event?,
+        };
+        match event {
+            StreamEvent::Delta(delta) => {
+                self.content.push_str(&delta);
+                self.token_count += 1;
+                Some(Ok(delta))
+            },
+            StreamEvent::Finished { is_complete } => {
+                self.is_complete = is_complete;
+                None
+            },
+            StreamEvent::Failed(e) => Some(Err(e)),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Everything received so far.
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// False until the model ends its reply itself, e.g. when it was cut
+    /// off at `max_tokens`.
+    pub fn is_complete(&self) -> bool {
+        self.is_complete
+    }
+
+    /// Pieces received so far; one per token for model backends.
+    pub fn token_count(&self) -> usize {
+        self.token_count
+    }
+}
+
+impl Drop for TokenStream {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+/// The generating end of a `TokenStream`.
+pub struct TokenSender {
+    events: mpsc::UnboundedSender<StreamEvent>,
+    cancel: CancellationToken,
+}
+
+impl TokenSender {
+    /// Whether the reader gave up on the reply; generation should stop.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled() || self.events.is_closed()
+    }
+
+    /// Pass on a piece of the reply; false once nobody is reading.
+    pub fn send(&self, delta: String) -> bool {
+        !self.is_cancelled() && self.events.send(StreamEvent::Delta(delta)).is_ok()
+    }
+
+    /// End the reply; `is_complete` is false if it was cut short.
+    pub fn finish(self, is_complete: bool) {
+        let _ = self.events.send(StreamEvent::Finished { is_complete });
+    }
+
+    pub fn fail(self, error: anyhow::Error) {
+        let _ = self.events.send(StreamEvent::Failed(error));
+    }
+}
+
+/// Text generator behind suggestions.
+#[async_trait]
+pub trait LlmBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Prepare the backend (load weights) before first use.
+    async fn load(&mut self) -> Result<()>;
+
+    /// Start replying to `prompt` following the `system` instructions. The
+    /// reply streams in until it ends or `cancel` is triggered.
+    async fn generate(&self, system: &str, prompt: &str, cancel: CancellationToken) -> Result<TokenStream>;
+
+    fn model_info(&self) -> String;
+}
+
+/// Which LLM backend generates suggestions, chosen at runtime. The mock
+/// unless llama is asked for, as a model file is needed besides the feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LlmBackendKind {
+    #[default]
+    Mock,
+    Llama,
+}
+
+impl LlmBackendKind {
+    /// Read the backend from `LLM_BACKEND` (`mock` or `llama`), falling back
+    /// to the mock.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("LLM_BACKEND").ok().as_deref() {
+            None | Some("") => Ok(Self::default()),
+            Some("mock") => Ok(LlmBackendKind::Mock),
+            Some("llama") => Ok(LlmBackendKind::Llama),
+            Some(other) => Err(anyhow::anyhow!("Unknown LLM backend: {}", other)),
+        }
+    }
+
+    #[cfg_attr(not(feature = "llama"), allow(unused_variables))]
+    pub fn build(&self, config: &ModelConfig) -> Result<Box<dyn LlmBackend>> {
+        match self {
+            LlmBackendKind::Mock => Ok(Box::new(MockLlmBackend::new())),
+            #[cfg(feature = "llama")]
+            LlmBackendKind::Llama => Ok(Box::new(LlamaEngine::new(config.clone()))),
+            #[cfg(not(feature = "llama"))]
+            LlmBackendKind::Llama => {
+                Err(anyhow::anyhow!("Local LLM inference requires building with `--features llama`"))
+            },
+        }
+    }
 }
 
 pub struct LLMOrchestrator {
     config: ModelConfig,
-    model_handle: Arc<RwLock<Option<LlamaModel>>>,
+    backend: RwLock<Box<dyn LlmBackend>>,
     prompt_templates: std::collections::HashMap<String, PromptTemplate>,
 }
 
-// Placeholder for actual llama.cpp integration
-struct LlamaModel {
-    context: *mut std::ffi::c_void,
-}
-
 impl LLMOrchestrator {
-    pub fn new(config: ModelConfig) -> Self {
+    pub fn new(config: ModelConfig, backend: Box<dyn LlmBackend>) -> Self {
         let mut templates = std::collections::HashMap::new();
 
         // Safety pre-prompt for all interactions
         let safety_prefix = "CRITICAL: You are a privacy-first meeting assistant. NEVER suggest recording calls, joining meetings, or sharing sensitive data externally. If exam/proctoring context detected, immediately pause and inform user. Respect all privacy guardrails.".to_string();
 
+        templates.insert("general".to_string(), PromptTemplate {
+            system_prompt: "You're a meeting assistant. Help the user keep up with the conversation: answer questions, explain terms, and note next steps. Keep responses under 2 lines for real-time use.".to_string(),
+            user_template: "{context}\n\nUser query: {query}".to_string(),
+            safety_prefix: safety_prefix.clone(),
+        });
+
         templates.insert("sales".to_string(), PromptTemplate {
             system_prompt: "You're an expert sales assistant. Help with objection handling, competitive positioning, and next steps. Focus on: customer pain points, value propositions, closing techniques. Keep responses under 2 lines for real-time use.".to_string(),
             user_template: "{context}\n\nUser query: {query}".to_string(),
@@ -50,46 +274,41 @@ impl LLMOrchestrator {
 
         Self {
             config,
-            model_handle: Arc::new(RwLock::new(None)),
+            backend: RwLock::new(backend),
             prompt_templates: templates,
         }
     }
 
     pub async fn load_model(&self) -> Result<()> {
-        // Pseudocode for llama.cpp model loading
-        // let ctx = llama_init_from_file(&self.config.model_path)?;
-        // let model = LlamaModel { context: ctx };
-        // *self.model_handle.write().await = Some(model);
-
-        println!("Model loaded: {}", self.config.model_path);
+        let mut backend = self.backend.write().await;
+        backend.load().await?;
+        info!("{}", backend.model_info());
         Ok(())
     }
 
-    pub async fn generate_stream(&self, prompt: &str, role: &str) -> Result<TokenStream> {
+    /// Tokens a prompt may use, leaving room for the longest system prompt
+    /// and the reply.
+    pub fn prompt_tokens(&self) -> usize {
+        let system_tokens = self.prompt_templates.values()
+            .map(|template| estimate_tokens(&template.safety_prefix) + estimate_tokens(&template.system_prompt))
+            .max()
+            .unwrap_or(0);
+        self.config.context_size
+            .saturating_sub(self.config.max_tokens as usize + system_tokens + TEMPLATE_TOKENS)
+    }
+
+    /// Stream a reply to `prompt` in the voice of the template for `role`,
+    /// until it ends or `cancel` is triggered.
+    pub async fn generate_stream(&self, prompt: &str, role: &str, cancel: CancellationToken) -> Result<TokenStream> {
+        // Meeting types without a template of their own get the general one
         let template = self
             .prompt_templates
-            .get(role)
-            .unwrap_or(self.prompt_templates.get("general").unwrap());
-
-        let full_prompt = format!(
-            "{}\n\n{}\n\n{}",
-            template.safety_prefix, template.system_prompt, prompt
-        );
-
-        // Pseudocode for streaming generation
-        // let model = self.model_handle.read().await;
-        // if let Some(model) = model.as_ref() {
-        //     let tokens = llama_tokenize(&model.context, &full_prompt)?;
-        //     let output = llama_generate_stream(&model.context, &tokens, self.config.max_tokens)?;
-        //     return Ok(TokenStream { content: output, is_complete: true, token_count: tokens.len() });
-        // }
-
-        // Mock response for now
-        Ok(TokenStream {
-            content: "Generated suggestion based on context".to_string(),
-            is_complete: true,
-            token_count: 10,
-        })
+            .get(&role.to_lowercase())
+            .unwrap_or(&self.prompt_templates["general"]);
+
+        let system = format!("{}\n\n{}", template.safety_prefix, template.system_prompt);
+        // Waits for a model that is still loading
+        self.backend.read().await.generate(&system, prompt, cancel).await
     }
 
     pub fn set_context(&mut self, context: &str) {
@@ -98,5 +317,398 @@ impl LLMOrchestrator {
     }
 }
 
-unsafe impl Send for LlamaModel {}
-unsafe impl Sync for LlamaModel {}
+/// A canned reply for development and tests; needs no model. It streams a
+/// word at a time so the HUD behaves as with a real model.
+#[derive(Default)]
+pub struct MockLlmBackend {
+    loaded: bool,
+}
+
+impl MockLlmBackend {
+    pub fn new() -> Self {
+        Self { loaded: false }
+    }
+}
+
+#[async_trait]
+impl LlmBackend for MockLlmBackend {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn load(&mut self) -> Result<()> {
+        self.loaded = true;
+        info!("Using mock LLM backend");
+        Ok(())
+    }
+
+    async fn generate(&self, _system: &str, _prompt: &str, cancel: CancellationToken) -> Result<TokenStream> {
+        let (sender, stream) = TokenStream::channel(cancel);
+        tokio::spawn(async move {
+            for (i, word) in "Generated suggestion based on context".split(' ').enumerate() {
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                let delta = if i == 0 { word.to_string() } else { format!(" {}", word) };
+                if !sender.send(delta) {
+                    return;
+                }
+            }
+            sender.finish(true);
+        });
+        Ok(stream)
+    }
+
+    fn model_info(&self) -> String {
+        format!("Mock LLM (loaded: {})", self.loaded)
+    }
+}
+
+/// llama.cpp run in-process on the CPU through its FFI bindings.
+#[cfg(feature = "llama")]
+pub struct LlamaEngine {
+    config: ModelConfig,
+    model: Option<std::sync::Arc<llama_cpp_2::model::LlamaModel>>,
+    // One reply at a time gets every core, which keeps time to first token
+    // low; a cancelled reply gives way within a batch
+    inference: std::sync::Arc<std::sync::Mutex<()>>,
+}
+
+// llama.cpp may only be initialized once per process
+#[cfg(feature = "llama")]
+static LLAMA_BACKEND: std::sync::OnceLock<llama_cpp_2::llama_backend::LlamaBackend> = std::sync::OnceLock::new();
+
+#[cfg(feature = "llama")]
+impl LlamaEngine {
+    pub fn new(config: ModelConfig) -> Self {
+        if !std::path::Path::new(&config.model_path).exists() {
+            tracing::warn!("LLM model not found at: {}", config.model_path);
+        }
+
+        Self {
+            config,
+            model: None,
+            inference: std::sync::Arc::new(std::sync::Mutex::new(())),
+        }
+    }
+
+    fn backend() -> Result<&'static llama_cpp_2::llama_backend::LlamaBackend> {
+        if let Some(backend) = LLAMA_BACKEND.get() {
+            return Ok(backend);
+        }
+        let mut backend = llama_cpp_2::llama_backend::LlamaBackend::init()
+            .map_err(|e| anyhow::anyhow!("Failed to initialize llama.cpp: {}", e))?;
+        backend.void_logs();
+        Ok(LLAMA_BACKEND.get_or_init(|| backend))
+    }
+
+    // Runs llama.cpp on the calling thread, streaming the reply through
+    // `sender`; callers keep it off the async runtime. Returns whether the
+    // model ended the reply itself.
+    fn run_inference(
+        model: &llama_cpp_2::model::LlamaModel,
+        config: &ModelConfig,
+        system: &str,
+        prompt: &str,
+        sender: &TokenSender,
+    ) -> Result<bool> {
+        use llama_cpp_2::context::params::LlamaContextParams;
+        use llama_cpp_2::llama_batch::LlamaBatch;
+        use llama_cpp_2::model::{AddBos, LlamaChatMessage};
+        use llama_cpp_2::sampling::LlamaSampler;
+        use std::num::NonZeroU32;
+
+        // Leave cores for audio capture and transcription
+        let threads = std::thread::available_parallelism()
+            .map(|n| (n.get() / 2).max(1))
+            .unwrap_or(2) as i32;
+        let context_params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(config.context_size as u32))
+            .with_n_batch(PROMPT_BATCH_TOKENS as u32)
+            .with_n_threads(threads)
+            .with_n_threads_batch(threads);
+        let mut context = model.new_context(Self::backend()?, context_params)
+            .map_err(|e| anyhow::anyhow!("Failed to create llama.cpp context: {}", e))?;
+
+        // Instruction-tuned models expect their own chat format; models
+        // without one get the instructions as plain text
+        let text = match model.chat_template(None) {
+            Ok(template) => {
+                let messages = vec![
+                    LlamaChatMessage::new("system".to_string(), system.to_string())?,
+                    LlamaChatMessage::new("user".to_string(), prompt.to_string())?,
+                ];
+                model.apply_chat_template(&template, &messages, true)
+                    .map_err(|e| anyhow::anyhow!("Failed to apply chat template: {}", e))?
+            },
+            Err(_) => format!("{}\n\n{}\n\n", system, prompt),
+        };
+        let tokens = model.str_to_token(&text, AddBos::Always)
+            .map_err(|e| anyhow::anyhow!("Failed to tokenize prompt: {}", e))?;
+        if tokens.len() + config.max_tokens as usize > config.context_size {
+            return Err(anyhow::anyhow!(
+                "Prompt of {} tokens leaves no room for a {} token reply in a {} token context",
+                tokens.len(),
+                config.max_tokens,
+                config.context_size
+            ));
+        }
+
+        let mut batch = LlamaBatch::new(PROMPT_BATCH_TOKENS, 1);
+        let last = tokens.len() as i32 - 1;
+        let mut position = 0;
+        for chunk in tokens.chunks(PROMPT_BATCH_TOKENS) {
+            if sender.is_cancelled() {
+                return Ok(false);
+            }
+            batch.clear();
+            for token in chunk {
+                batch.add(*token, position, &[0], position == last)?;
+                position += 1;
+            }
+            context.decode(&mut batch)
+                .map_err(|e| anyhow::anyhow!("Failed to evaluate prompt: {}", e))?;
+        }
+
+        let mut sampler = if config.temperature <= 0.0 {
+            LlamaSampler::greedy()
+        } else {
+            let seed = chrono::Utc::now().timestamp_subsec_nanos();
+            LlamaSampler::chain_simple([LlamaSampler::temp(config.temperature), LlamaSampler::dist(seed)])
+        };
+
+        let mut pending = Vec::new();
+        for _ in 0..config.max_tokens {
+            if sender.is_cancelled() {
+                return Ok(false);
+            }
+            let token = sampler.sample(&context, batch.n_tokens() - 1);
+            sampler.accept(token);
+            if model.is_eog_token(token) {
+                flush_utf8(&mut pending, sender);
+                return Ok(true);
+            }
+
+            pending.extend(token_bytes(model, token)
+                .map_err(|e| anyhow::anyhow!("Failed to decode token: {}", e))?);
+            if let Some(delta) = take_utf8(&mut pending) {
+                if !sender.send(delta) {
+                    return Ok(false);
+                }
+            }
+
+            batch.clear();
+            batch.add(token, position, &[0], true)?;
+            position += 1;
+            context.decode(&mut batch)
+                .map_err(|e| anyhow::anyhow!("Failed to evaluate token: {}", e))?;
+        }
+
+        flush_utf8(&mut pending, sender);
+        Ok(false)
+    }
+}
+
+// Text of a token, special tokens included; pieces longer than the first
+// guess are fetched again with the size llama.cpp asks for
+#[cfg(feature = "llama")]
+fn token_bytes(
+    model: &llama_cpp_2::model::LlamaModel,
+    token: llama_cpp_2::token::LlamaToken,
+) -> std::result::Result<Vec<u8>, llama_cpp_2::TokenToStringError> {
+    use llama_cpp_2::TokenToStringError;
+
+    match model.token_to_piece_bytes(token, 32, true, None) {
+        Err(TokenToStringError::InsufficientBufferSpace(needed)) => {
+            model.token_to_piece_bytes(token, needed.unsigned_abs() as usize, true, None)
+        },
+        result => result,
+    }
+}
+
+// The whole characters at the start of `pending`; a token can end partway
+// through a character, whose remaining bytes come with the next one
+#[cfg(feature = "llama")]
+fn take_utf8(pending: &mut Vec<u8>) -> Option<String> {
+    let valid = match std::str::from_utf8(pending) {
+        Ok(_) => pending.len(),
+        // Invalid bytes won't become valid by waiting
+        Err(e) if e.error_len().is_some() => pending.len(),
+        Err(e) => e.valid_up_to(),
+    };
+    if valid == 0 {
+        return None;
+    }
+
+    let text = String::from_utf8_lossy(&pending[..valid]).into_owned();
+    pending.drain(..valid);
+    Some(text)
+}
+
+#[cfg(feature = "llama")]
+fn flush_utf8(pending: &mut Vec<u8>, sender: &TokenSender) {
+    if !pending.is_empty() {
+        sender.send(String::from_utf8_lossy(pending).into_owned());
+        pending.clear();
+    }
+}
+
+#[cfg(feature = "llama")]
+#[async_trait]
+impl LlmBackend for LlamaEngine {
+    fn name(&self) -> &'static str {
+        "llama"
+    }
+
+    async fn load(&mut self) -> Result<()> {
+        info!("Loading LLM from: {}", self.config.model_path);
+
+        if !std::path::Path::new(&self.config.model_path).exists() {
+            return Err(anyhow::anyhow!("LLM model not found at: {}", self.config.model_path));
+        }
+
+        let model_path = self.config.model_path.clone();
+        let model = tokio::task::spawn_blocking(move || {
+            // CPU only: no layers are offloaded to a GPU
+            let params = llama_cpp_2::model::params::LlamaModelParams::default().with_n_gpu_layers(0);
+            llama_cpp_2::model::LlamaModel::load_from_file(Self::backend()?, &model_path, &params)
+                .map_err(|e| anyhow::anyhow!("Failed to load LLM: {}", e))
+        }).await??;
+
+        let trained_context = model.n_ctx_train() as usize;
+        if self.config.context_size > trained_context {
+            tracing::warn!(
+                "Context size {} exceeds the {} tokens {} was trained on; replies may degrade",
+                self.config.context_size,
+                trained_context,
+                self.config.model_path
+            );
+        }
+
+        self.model = Some(std::sync::Arc::new(model));
+        info!("LLM loaded successfully");
+        Ok(())
+    }
+
+    async fn generate(&self, system: &str, prompt: &str, cancel: CancellationToken) -> Result<TokenStream> {
+        let Some(model) = self.model.clone() else {
+            return Err(anyhow::anyhow!("LLM not loaded"));
+        };
+
+        let (sender, stream) = TokenStream::channel(cancel);
+        let config = self.config.clone();
+        let inference = self.inference.clone();
+        let system = system.to_string();
+        let prompt = prompt.to_string();
+        tokio::task::spawn_blocking(move || {
+            let _running = inference.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            match Self::run_inference(&model, &config, &system, &prompt, &sender) {
+                Ok(is_complete) => sender.finish(is_complete),
+                Err(e) => sender.fail(e),
+            }
+        });
+        Ok(stream)
+    }
+
+    fn model_info(&self) -> String {
+        format!("LLM: {} (loaded: {})", self.config.model_path, self.model.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn collect(stream: &mut TokenStream) -> Vec<String> {
+        let mut deltas = Vec::new();
+        while let Some(delta) = stream.next().await {
+            deltas.push(delta.unwrap());
+        }
+        deltas
+    }
+
+    #[test]
+    fn mock_is_the_default_backend() {
+        assert_eq!(LlmBackendKind::default(), LlmBackendKind::Mock);
+    }
+
+    #[tokio::test]
+    async fn mock_streams_the_same_deltas_every_time() {
+        let mut backend = MockLlmBackend::new();
+        backend.load().await.unwrap();
+
+        for _ in 0..2 {
+            let mut stream = backend.generate("system", "prompt", CancellationToken::new()).await.unwrap();
+            let deltas = collect(&mut stream).await;
+            assert_eq!(deltas, ["Generated", " suggestion", " based", " on", " context"]);
+            assert_eq!(stream.content(), "Generated suggestion based on context");
+            assert_eq!(stream.token_count(), 5);
+            assert!(stream.is_complete());
+            assert!(!stream.is_cancelled());
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelling_mid_stream_keeps_the_partial_reply() {
+        let backend = MockLlmBackend::new();
+        let mut stream = backend.generate("system", "prompt", CancellationToken::new()).await.unwrap();
+
+        assert_eq!(stream.next().await.unwrap().unwrap(), "Generated");
+        assert_eq!(stream.next().await.unwrap().unwrap(), " suggestion");
+        stream.cancel();
+
+        assert!(stream.next().await.is_none());
+        assert!(stream.is_cancelled());
+        assert_eq!(stream.content(), "Generated suggestion");
+        assert_eq!(stream.token_count(), 2);
+        assert!(!stream.is_complete());
+    }
+
+    #[tokio::test]
+    async fn cancelling_the_token_ends_the_stream() {
+        let cancel = CancellationToken::new();
+        let (sender, mut stream) = TokenStream::channel(cancel.clone());
+        assert!(sender.send("queued".to_string()));
+
+        // Pieces already queued are not delivered after a cancel
+        cancel.cancel();
+        assert!(stream.next().await.is_none());
+        assert!(stream.is_cancelled());
+        assert!(sender.is_cancelled());
+        assert!(!sender.send("late".to_string()));
+    }
+
+    #[tokio::test]
+    async fn dropping_the_stream_cancels_generation() {
+        let cancel = CancellationToken::new();
+        let (sender, stream) = TokenStream::channel(cancel.clone());
+        assert!(!sender.is_cancelled());
+
+        drop(stream);
+        assert!(cancel.is_cancelled());
+        assert!(sender.is_cancelled());
+        assert!(!sender.send("unread".to_string()));
+    }
+
+    #[tokio::test]
+    async fn dropping_a_mock_stream_cancels_its_token() {
+        let backend = MockLlmBackend::new();
+        let cancel = CancellationToken::new();
+        let stream = backend.generate("system", "prompt", cancel.clone()).await.unwrap();
+        drop(stream);
+        assert!(cancel.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn failures_reach_the_reader() {
+        let (sender, mut stream) = TokenStream::channel(CancellationToken::new());
+        assert!(sender.send("Partial".to_string()));
+        sender.fail(anyhow::anyhow!("model crashed"));
+
+        assert_eq!(stream.next().await.unwrap().unwrap(), "Partial");
+        let error = stream.next().await.unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "model crashed");
+        // The sender is gone, so the stream ends without completing
+        assert!(stream.next().await.is_none());
+        assert!(!stream.is_complete());
+        assert_eq!(stream.content(), "Partial");
+    }
+}
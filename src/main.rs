@@ -1,10 +1,43 @@
-use tauri::{Manager, AppHandle, Emitter};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{Manager, AppHandle, Emitter, State};
+use tokio::sync::{broadcast, Mutex};
+use tokio_util::sync::CancellationToken;
 
 mod audio;
 mod vad;
+mod detector;
+mod noise;
 mod asr;
+mod http_asr;
+mod source;
+mod resample;
+mod segment;
+mod diarize;
+mod embedding;
+mod queue;
+mod devices;
+mod settings;
+mod keys;
+mod storage;
+mod rag;
+mod extract;
+mod connector;
+mod router;
+mod llm;
 
+use asr::TranscriptResult;
 use audio::AudioPipeline;
+use connector::{DocumentConnector, SyncReport};
+use devices::{InputDeviceInfo, LevelMeter};
+use keys::KeySource;
+use llm::{LLMOrchestrator, LlmBackendKind, ModelConfig};
+use rag::{EmbedderKind, VectorStore};
+use router::{Citation, PromptBudget, StateIntentRouter};
+use settings::Settings;
+use storage::{Database, RetentionReport, RetentionSettings, SearchFilter, SearchHit, Suggestion};
+use vad::CaptureState;
 use tracing_subscriber;
 
 #[derive(Clone, serde::Serialize)]
@@ -13,32 +46,553 @@ struct SuggestionPayload {
     content: String,
     suggestion_type: String,
     confidence: f32,
+    /// Document passages the suggestion was grounded in, numbered as cited
+    /// in `content`.
+    citations: Vec<Citation>,
+}
+
+/// A piece of a suggestion still being generated.
+#[derive(Clone, serde::Serialize)]
+struct SuggestionDelta {
+    id: String,
+    delta: String,
+}
+
+/// The audio pipeline, created on the first start and kept for the lifetime
+/// of the app so it can be stopped, paused and restarted.
+#[derive(Default)]
+struct ListeningState {
+    pipeline: Mutex<Option<AudioPipeline>>,
+    meter: Mutex<Option<LevelMeter>>,
+    /// Meeting the running session is recorded under.
+    meeting_id: Mutex<Option<String>>,
+}
+
+/// Persisted settings and the file they live in.
+struct SettingsState {
+    path: PathBuf,
+    settings: Mutex<Settings>,
+    /// False while defaults stand in for a file that couldn't be read, so
+    /// retention deletes nothing until it is set again.
+    retention_loaded: AtomicBool,
+}
+
+impl SettingsState {
+    // A bad file never stops the app starting; the defaults are used instead
+    fn load(path: PathBuf) -> Self {
+        let (settings, retention_loaded) = match Settings::load(&path) {
+            Ok(settings) => (settings, true),
+            Err(e) => {
+                tracing::warn!("{}; using default settings", e);
+                (Settings::default(), false)
+            },
+        };
+        Self {
+            path,
+            settings: Mutex::new(settings),
+            retention_loaded: AtomicBool::new(retention_loaded),
+        }
+    }
+    
+    // The chosen microphone, or `MIC_DEVICE` when none was chosen in the HUD
+    async fn input_device(&self) -> Option<String> {
+        self.settings.lock().await.input_device.clone()
+            .or_else(|| std::env::var("MIC_DEVICE").ok())
+    }
+}
+
+/// Search results returned when the HUD doesn't ask for a number.
+const DEFAULT_SEARCH_LIMIT: u32 = 20;
+
+/// How often meeting data past its retention is deleted.
+const RETENTION_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often document folders are checked for changes. Polling works the
+/// same on every platform and for network drives, and only reads metadata
+/// for unchanged files.
+const DOCUMENT_SYNC_INTERVAL: Duration = Duration::from_secs(30);
+
+fn emit_state(app: &AppHandle, state: CaptureState) {
+    let _ = app.emit("listening-state", state);
+}
+
+// Start the pipeline, recording its transcripts under `meeting_id` and
+// forwarding them to the frontend
+async fn start_pipeline(app: &AppHandle, pipeline: &mut AudioPipeline, meeting_id: &str) -> Result<(), String> {
+    let mut rx = pipeline.start_streaming().await
+        .map_err(|e| format!("Failed to start listening: {}", e))?;
+    app.state::<Database>().record_transcripts(meeting_id, rx.resubscribe());
+    start_suggestions(app, meeting_id, rx.resubscribe());
+    
+    // The forwarding task ends with the session, when the ASR stage drops
+    // the sender
+    let transcript_app = app.clone();
+    let session_meeting_id = meeting_id.to_string();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(transcript) => {
+                    let _ = transcript_app.emit("transcript", transcript);
+                },
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("HUD fell behind, skipped {} transcripts", skipped);
+                },
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+        session_ended(&transcript_app, &session_meeting_id).await;
+    });
+    
+    emit_state(app, pipeline.state());
+    Ok(())
+}
+
+// Sessions also end by themselves, e.g. when a device goes away. Unless a
+// newer session took over the pipeline, tell the HUD and close the meeting.
+async fn session_ended(app: &AppHandle, meeting_id: &str) {
+    let listening = app.state::<ListeningState>();
+    let pipeline = listening.pipeline.lock().await;
+    let Some(pipeline) = pipeline.as_ref().filter(|pipeline| !pipeline.is_running()) else {
+        return;
+    };
+    emit_state(app, pipeline.state());
+    
+    let mut current = listening.meeting_id.lock().await;
+    if current.as_deref() == Some(meeting_id) {
+        *current = None;
+        if let Err(e) = app.state::<Database>().end_meeting(meeting_id).await {
+            tracing::warn!("Failed to end meeting {}: {}", meeting_id, e);
+        }
+    }
+}
+
+// Suggest on the other party's finished utterances until the session ends.
+// A newer utterance cancels the suggestion still being generated for an
+// older one; it would arrive too late to help.
+fn start_suggestions(app: &AppHandle, meeting_id: &str, mut rx: broadcast::Receiver<TranscriptResult>) {
+    let app = app.clone();
+    let meeting_id = meeting_id.to_string();
+    tokio::spawn(async move {
+        let mut in_flight: Option<CancellationToken> = None;
+        loop {
+            match rx.recv().await {
+                Ok(transcript) => {
+                    if !app.state::<StateIntentRouter>().should_suggest(&transcript) {
+                        continue;
+                    }
+                    if let Some(previous) = in_flight.take() {
+                        previous.cancel();
+                    }
+                    let cancel = CancellationToken::new();
+                    in_flight = Some(cancel.clone());
+                    
+                    let app = app.clone();
+                    let meeting_id = meeting_id.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = suggest(&app, &meeting_id, &transcript, cancel).await {
+                            tracing::error!("Suggestion failed: {}", e);
+                        }
+                    });
+                },
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Suggestions fell behind, skipped {} transcripts", skipped);
+                },
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+// Generate a suggestion for `transcript`, streaming it to the HUD as
+// `suggestion-delta` events keyed by its ID, then save it. The HUD gets
+// `suggestion-started` with the citations first, and `suggestion` with the
+// whole text or `suggestion-cancelled` last.
+async fn suggest(app: &AppHandle, meeting_id: &str, transcript: &TranscriptResult, cancel: CancellationToken) -> anyhow::Result<()> {
+    let database = app.state::<Database>();
+    // Nothing captures the screen yet, so prompts carry no screen context
+    let decision = app.state::<StateIntentRouter>().route_request(transcript, None).await?;
+    let meeting_type = database.meeting(meeting_id).await?
+        .map(|meeting| meeting.meeting_type)
+        .unwrap_or_default();
+    if cancel.is_cancelled() {
+        return Ok(());
+    }
+    
+    let mut suggestion = Suggestion {
+        id: uuid::Uuid::new_v4().to_string(),
+        meeting_id: meeting_id.to_string(),
+        suggestion_type: decision.intent.as_str().to_string(),
+        content: String::new(),
+        confidence: transcript.confidence,
+        created_at: chrono::Utc::now(),
+    };
+    let payload = |content: String| SuggestionPayload {
+        id: suggestion.id.clone(),
+        content,
+        suggestion_type: suggestion.suggestion_type.clone(),
+        confidence: suggestion.confidence,
+        citations: decision.citations.clone(),
+    };
+    let _ = app.emit("suggestion-started", payload(String::new()));
+    
+    let mut stream = match app.state::<LLMOrchestrator>()
+        .generate_stream(&decision.suggested_prompt, &meeting_type, cancel)
+        .await
+    {
+        Ok(stream) => stream,
+        Err(e) => {
+            let _ = app.emit("suggestion-cancelled", &suggestion.id);
+            return Err(e);
+        },
+    };
+    while let Some(delta) = stream.next().await {
+        let delta = match delta {
+            Ok(delta) => delta,
+            Err(e) => {
+                let _ = app.emit("suggestion-cancelled", &suggestion.id);
+                return Err(e);
+            },
+        };
+        let _ = app.emit("suggestion-delta", SuggestionDelta { id: suggestion.id.clone(), delta });
+    }
+    if stream.is_cancelled() {
+        let _ = app.emit("suggestion-cancelled", &suggestion.id);
+        return Ok(());
+    }
+    
+    suggestion.content = stream.content().trim().to_string();
+    let _ = app.emit("suggestion", payload(suggestion.content.clone()));
+    database.save_suggestion(&suggestion).await
+}
+
+// Apply the saved retention settings, sparing the meeting being recorded
+async fn sweep_retention(app: &AppHandle) -> Result<RetentionReport, String> {
+    let settings = app.state::<SettingsState>();
+    if !settings.retention_loaded.load(Ordering::Relaxed) {
+        return Err("Retention sweep skipped: the settings file could not be read".to_string());
+    }
+    let retention = settings.settings.lock().await.retention.clone();
+    let active_meeting = app.state::<ListeningState>().meeting_id.lock().await.clone();
+    app.state::<Database>()
+        .apply_retention(&retention, active_meeting.as_deref())
+        .await
+        .map_err(|e| format!("Retention sweep failed: {}", e))
+}
+
+// Sweep at startup and then every `RETENTION_SWEEP_INTERVAL`
+fn start_retention_sweeper(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(RETENTION_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = sweep_retention(&app).await {
+                tracing::error!("{}", e);
+            }
+        }
+    });
+}
+
+// Index the configured document folders into the vector store
+async fn sync_documents(app: &AppHandle) -> Result<SyncReport, String> {
+    let folders = app.state::<SettingsState>().settings.lock().await.document_folders.clone();
+    app.state::<DocumentConnector>()
+        .sync(&folders)
+        .await
+        .map_err(|e| format!("Document sync failed: {}", e))
+}
+
+fn start_document_sync(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(DOCUMENT_SYNC_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = sync_documents(&app).await {
+                tracing::error!("{}", e);
+            }
+        }
+    });
+}
+
+// Load the model in the background; suggestions wait for it
+fn load_llm(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = app.state::<LLMOrchestrator>().load_model().await {
+            tracing::error!("Failed to load LLM: {}", e);
+        }
+    });
+}
+
+fn start_meter(app: &AppHandle, device: Option<&str>) -> Result<LevelMeter, String> {
+    let app = app.clone();
+    LevelMeter::start(device, move |level| {
+        let _ = app.emit("input-level", level);
+    })
+    .map_err(|e| format!("Failed to open input device: {}", e))
+}
+
+#[tauri::command]
+async fn start_listening(
+    app: AppHandle,
+    listening: State<'_, ListeningState>,
+    settings: State<'_, SettingsState>,
+    database: State<'_, Database>,
+) -> Result<String, String> {
+    // The meter is for checking the mic before a meeting; release it
+    *listening.meter.lock().await = None;
+    
+    let mut pipeline = listening.pipeline.lock().await;
+    if pipeline.is_none() {
+        let device = settings.input_device().await;
+        *pipeline = Some(AudioPipeline::with_microphone(device.as_deref()).map_err(|e| e.to_string())?);
+    }
+    let Some(pipeline) = pipeline.as_mut() else {
+        return Err("Audio pipeline unavailable".to_string());
+    };
+    if pipeline.is_running() {
+        return Err("Already listening".to_string());
+    }
+    // A session that ended by itself may not have closed its meeting yet
+    if let Some(previous) = listening.meeting_id.lock().await.take() {
+        database.end_meeting(&previous).await.map_err(|e| e.to_string())?;
+    }
+    
+    let title = format!("Meeting {}", chrono::Local::now().format("%Y-%m-%d %H:%M"));
+    let meeting = database.create_meeting(&title).await.map_err(|e| e.to_string())?;
+    if let Err(e) = start_pipeline(&app, pipeline, &meeting.id).await {
+        let _ = database.end_meeting(&meeting.id).await;
+        return Err(e);
+    }
+    *listening.meeting_id.lock().await = Some(meeting.id);
+    Ok("Started listening".to_string())
+}
+
+#[tauri::command]
+async fn stop_listening(app: AppHandle, listening: State<'_, ListeningState>, database: State<'_, Database>) -> Result<(), String> {
+    if let Some(pipeline) = listening.pipeline.lock().await.as_mut() {
+        pipeline.stop().await;
+        emit_state(&app, pipeline.state());
+    }
+    if let Some(meeting_id) = listening.meeting_id.lock().await.take() {
+        database.end_meeting(&meeting_id).await.map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn pause_listening(app: AppHandle, listening: State<'_, ListeningState>) -> Result<(), String> {
+    let pipeline = listening.pipeline.lock().await;
+    let pipeline = pipeline.as_ref().ok_or("Not listening")?;
+    pipeline.pause().map_err(|e| e.to_string())?;
+    emit_state(&app, pipeline.state());
+    Ok(())
+}
+
+#[tauri::command]
+async fn resume_listening(app: AppHandle, listening: State<'_, ListeningState>) -> Result<(), String> {
+    let pipeline = listening.pipeline.lock().await;
+    let pipeline = pipeline.as_ref().ok_or("Not listening")?;
+    pipeline.resume().map_err(|e| e.to_string())?;
+    emit_state(&app, pipeline.state());
+    Ok(())
 }
 
 #[tauri::command]
-async fn start_listening(app: AppHandle) -> Result<String, String> {
-    let mut pipeline = AudioPipeline::new().map_err(|e| e.to_string())?;
+async fn list_input_devices() -> Result<Vec<InputDeviceInfo>, String> {
+    devices::list_input_devices().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn selected_input_device(settings: State<'_, SettingsState>) -> Result<Option<String>, String> {
+    Ok(settings.input_device().await)
+}
+
+/// Remember the microphone to use, or the system default for `None`. A
+/// running session or level meter switches over straight away.
+#[tauri::command]
+async fn select_input_device(
+    app: AppHandle,
+    name: Option<String>,
+    listening: State<'_, ListeningState>,
+    settings: State<'_, SettingsState>,
+    database: State<'_, Database>,
+) -> Result<(), String> {
+    if let Some(name) = &name {
+        source::find_input_device(Some(name)).map_err(|e| e.to_string())?;
+    }
+    
+    {
+        let mut current = settings.settings.lock().await;
+        current.input_device = name.clone();
+        current.save(&settings.path).map_err(|e| format!("Failed to save settings: {}", e))?;
+    }
+    tracing::info!("Input device selected: {}", name.as_deref().unwrap_or("system default"));
+    let device = settings.input_device().await;
     
-    match pipeline.start_streaming().await {
-        Ok(mut rx) => {
-            // Spawn task to listen for transcripts and emit to frontend
-            tokio::spawn(async move {
-                while let Ok(transcript) = rx.recv().await {
-                    let _ = app.emit("transcript", transcript);
-                }
-            });
-            Ok("Started listening".to_string())
+    let mut meter = listening.meter.lock().await;
+    if meter.is_some() {
+        *meter = None;
+        *meter = Some(start_meter(&app, device.as_deref())?);
+    }
+    
+    let mut pipeline = listening.pipeline.lock().await;
+    let Some(pipeline) = pipeline.as_mut() else {
+        return Ok(());
+    };
+    let running = pipeline.is_running();
+    pipeline.switch_microphone(device.as_deref()).await;
+    
+    if let Some(meeting_id) = listening.meeting_id.lock().await.clone().filter(|_| running) {
+        if let Err(e) = start_pipeline(&app, pipeline, &meeting_id).await {
+            // The session can't go on without a device, so close its meeting
+            listening.meeting_id.lock().await.take();
+            if let Err(e) = database.end_meeting(&meeting_id).await {
+                tracing::warn!("Failed to end meeting {}: {}", meeting_id, e);
+            }
+            emit_state(&app, pipeline.state());
+            return Err(e);
         }
-        Err(e) => Err(format!("Failed to start listening: {}", e))
     }
+    
+    Ok(())
 }
 
+/// Emit `input-level` events for the named device, or the selected one,
+/// until `stop_level_meter`.
 #[tauri::command]
-async fn stop_listening() -> Result<(), String> {
-    // Stop audio processing
+async fn start_level_meter(
+    app: AppHandle,
+    device: Option<String>,
+    listening: State<'_, ListeningState>,
+    settings: State<'_, SettingsState>,
+) -> Result<(), String> {
+    let device = match device {
+        Some(device) => Some(device),
+        None => settings.input_device().await,
+    };
+    
+    let mut meter = listening.meter.lock().await;
+    // Close the previous device before opening the next
+    *meter = None;
+    *meter = Some(start_meter(&app, device.as_deref())?);
     Ok(())
 }
 
+#[tauri::command]
+async fn stop_level_meter(listening: State<'_, ListeningState>) -> Result<(), String> {
+    *listening.meter.lock().await = None;
+    Ok(())
+}
+
+/// Search every past meeting's transcript, best matches first.
+#[tauri::command]
+async fn search_transcripts(
+    query: String,
+    filter: Option<SearchFilter>,
+    limit: Option<u32>,
+    database: State<'_, Database>,
+) -> Result<Vec<SearchHit>, String> {
+    database
+        .search_transcripts(&query, &filter.unwrap_or_default(), limit.unwrap_or(DEFAULT_SEARCH_LIMIT))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Describe a meeting for search filters; the current one unless an ID is given.
+#[tauri::command]
+async fn set_meeting_details(
+    meeting_id: Option<String>,
+    meeting_type: String,
+    participants: Vec<String>,
+    listening: State<'_, ListeningState>,
+    database: State<'_, Database>,
+) -> Result<(), String> {
+    let meeting_id = match meeting_id {
+        Some(meeting_id) => meeting_id,
+        None => listening.meeting_id.lock().await.clone().ok_or("No meeting in progress")?,
+    };
+    database
+        .set_meeting_details(&meeting_id, &meeting_type, &participants)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Delete a meeting with its transcript, suggestions and action items.
+#[tauri::command]
+async fn forget_meeting(
+    meeting_id: String,
+    listening: State<'_, ListeningState>,
+    database: State<'_, Database>,
+) -> Result<(), String> {
+    if listening.meeting_id.lock().await.as_deref() == Some(meeting_id.as_str()) {
+        return Err("Stop listening before forgetting the current meeting".to_string());
+    }
+    database.forget_meeting(&meeting_id).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn retention_settings(settings: State<'_, SettingsState>) -> Result<RetentionSettings, String> {
+    Ok(settings.settings.lock().await.retention.clone())
+}
+
+/// Save new retention settings and apply them right away.
+#[tauri::command]
+async fn set_retention_settings(
+    app: AppHandle,
+    retention: RetentionSettings,
+    settings: State<'_, SettingsState>,
+) -> Result<RetentionReport, String> {
+    {
+        let mut current = settings.settings.lock().await;
+        current.retention = retention;
+        current.save(&settings.path).map_err(|e| format!("Failed to save settings: {}", e))?;
+    }
+    settings.retention_loaded.store(true, Ordering::Relaxed);
+    sweep_retention(&app).await
+}
+
+#[tauri::command]
+async fn document_folders(settings: State<'_, SettingsState>) -> Result<Vec<PathBuf>, String> {
+    Ok(settings.settings.lock().await.document_folders.clone())
+}
+
+/// Choose the folders to index and index them right away.
+#[tauri::command]
+async fn set_document_folders(
+    app: AppHandle,
+    folders: Vec<PathBuf>,
+    settings: State<'_, SettingsState>,
+) -> Result<SyncReport, String> {
+    // Absolute paths, so moving the working directory doesn't orphan them
+    let folders = folders.iter()
+        .map(|folder| match folder.canonicalize() {
+            Ok(path) if path.is_dir() => Ok(path),
+            _ => Err(format!("Not a folder: {}", folder.display())),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    {
+        let mut current = settings.settings.lock().await;
+        current.document_folders = folders;
+        current.save(&settings.path).map_err(|e| format!("Failed to save settings: {}", e))?;
+    }
+    sync_documents(&app).await
+}
+
+/// Re-encrypt the meeting database with a new key: a new random key in the
+/// key file, or `passphrase` when the key comes from `DB_PASSPHRASE`, which
+/// must then be updated before the next start.
+#[tauri::command]
+async fn rotate_database_key(
+    passphrase: Option<String>,
+    keys: State<'_, KeySource>,
+    database: State<'_, Database>,
+) -> Result<(), String> {
+    let key = keys.next_key(passphrase).map_err(|e| e.to_string())?;
+    database
+        .rotate_key(&keys, key)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 async fn copy_suggestion(_suggestion_id: String) -> Result<(), String> {
     // Copy suggestion to clipboard
@@ -50,14 +604,51 @@ fn main() {
     tracing_subscriber::fmt::init();
     
     tauri::Builder::default()
+        .manage(ListeningState::default())
         .invoke_handler(tauri::generate_handler![
             start_listening,
             stop_listening,
+            pause_listening,
+            resume_listening,
+            list_input_devices,
+            selected_input_device,
+            select_input_device,
+            start_level_meter,
+            stop_level_meter,
+            search_transcripts,
+            set_meeting_details,
+            forget_meeting,
+            retention_settings,
+            set_retention_settings,
+            document_folders,
+            set_document_folders,
+            rotate_database_key,
             copy_suggestion
         ])
-        .setup(|_app| {
+        .setup(|app| {
             tracing::info!("Meeting Copilot MVP starting up...");
             
+            let settings_path = app.path().app_config_dir()?.join("settings.json");
+            app.manage(SettingsState::load(settings_path));
+            
+            let keys = KeySource::from_env(app.path().app_config_dir()?.join("database.key"));
+            let database_path = app.path().app_data_dir()?.join("meetings.db");
+            let database = tauri::async_runtime::block_on(Database::new(&database_path.to_string_lossy(), &keys))?;
+            let store = VectorStore::new(&database, EmbedderKind::from_env()?.build()?);
+            app.manage(DocumentConnector::new(&database, store.clone()));
+            let model_config = ModelConfig::from_env()?;
+            let llm = LLMOrchestrator::new(model_config.clone(), LlmBackendKind::from_env()?.build(&model_config)?);
+            app.manage(StateIntentRouter::new()
+                .with_store(store.clone())
+                .with_budget(PromptBudget { max_tokens: llm.prompt_tokens(), ..PromptBudget::default() }));
+            app.manage(llm);
+            app.manage(store);
+            app.manage(database);
+            app.manage(keys);
+            start_retention_sweeper(app.handle().clone());
+            start_document_sync(app.handle().clone());
+            load_llm(app.handle().clone());
+            
             // Request microphone permissions on macOS
             #[cfg(target_os = "macos")]
             {
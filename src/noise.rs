@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+
+/// Tuning for the adaptive speech threshold.
+#[derive(Debug, Clone)]
+pub struct NoiseFloorConfig {
+    /// Audio at session start assumed to be room noise only.
+    pub calibration_ms: u32,
+    /// Time constant for following changes in ambient noise.
+    pub adapt_secs: f32,
+    /// Window over which the quietest frame bounds the floor from below.
+    pub min_window_secs: f32,
+    /// Speech must exceed the noise floor by this margin.
+    pub snr_db: f32,
+    pub min_threshold: f32,
+    pub max_threshold: f32,
+}
+
+impl Default for NoiseFloorConfig {
+    fn default() -> Self {
+        Self {
+            calibration_ms: 1500,
+            adapt_secs: 5.0,
+            min_window_secs: 5.0,
+            snr_db: 12.0,
+            min_threshold: 0.002,
+            max_threshold: 0.2,
+        }
+    }
+}
+
+/// Snapshot of the estimator, reported through the pipeline status.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct VadLevels {
+    pub noise_floor: f32,
+    pub threshold: f32,
+    pub last_rms: f32,
+    pub calibrating: bool,
+    pub has_voice: bool,
+}
+
+/// Tracks the ambient RMS level and derives a speech threshold from it.
+///
+/// The floor is seeded from a calibration period, then follows non-speech
+/// frames with a slow exponential average. Because a sudden rise in noise
+/// (a fan spinning up) would be classified as speech and never adapted to,
+/// the floor is also pulled up towards the quietest frame seen recently.
+pub struct NoiseFloorEstimator {
+    config: NoiseFloorConfig,
+    calibration_left: f32,
+    calibration_levels: Vec<f32>,
+    floor: f32,
+    recent: VecDeque<(f32, f32)>,
+    recent_secs: f32,
+}
+
+impl NoiseFloorEstimator {
+    pub fn new(config: NoiseFloorConfig) -> Self {
+        Self {
+            calibration_left: config.calibration_ms as f32 / 1000.0,
+            config,
+            calibration_levels: Vec::new(),
+            floor: 0.0,
+            recent: VecDeque::new(),
+            recent_secs: 0.0,
+        }
+    }
+
+    pub fn is_calibrating(&self) -> bool {
+        self.calibration_left > 0.0
+    }
+
+    pub fn floor(&self) -> f32 {
+        self.floor
+    }
+
+    pub fn threshold(&self) -> f32 {
+        let margin = 10f32.powf(self.config.snr_db / 20.0);
+        (self.floor * margin).clamp(self.config.min_threshold, self.config.max_threshold)
+    }
+
+    /// Feed the RMS of a frame lasting `duration_secs` and whether it was
+    /// judged to contain speech.
+    pub fn observe(&mut self, rms: f32, duration_secs: f32, is_speech: bool) {
+        if self.is_calibrating() {
+            self.calibration_levels.push(rms);
+            self.calibration_left -= duration_secs;
+            if !self.is_calibrating() {
+                // Median so a cough during calibration doesn't skew the floor
+                self.calibration_levels.sort_by(f32::total_cmp);
+                self.floor = self.calibration_levels[self.calibration_levels.len() / 2];
+                self.calibration_levels.clear();
+            }
+            return;
+        }
+
+        let alpha = (duration_secs / self.config.adapt_secs).min(1.0);
+
+        self.recent.push_back((rms, duration_secs));
+        self.recent_secs += duration_secs;
+        while let Some(&(_, secs)) = self.recent.front() {
+            if self.recent_secs - secs < self.config.min_window_secs {
+                break;
+            }
+            self.recent.pop_front();
+            self.recent_secs -= secs;
+        }
+
+        if !is_speech {
+            self.floor += alpha * (rms - self.floor);
+        }
+
+        let recent_min = self.recent.iter().map(|&(level, _)| level).fold(f32::MAX, f32::min);
+        if self.recent_secs >= self.config.min_window_secs && recent_min > self.floor {
+            self.floor += alpha * (recent_min - self.floor);
+        }
+    }
+}
@@ -0,0 +1,437 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tracing::warn;
+use crate::vad::AudioChunk;
+
+/// What to do with a new utterance when the ASR queue is full.
+///
+/// Partials are always expendable: an incoming partial that finds the queue
+/// full is dropped whatever the policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Evict the oldest queued utterance to make room.
+    #[default]
+    DropOldest,
+    /// Append the new utterance's audio to the newest queued one from the
+    /// same input, so ASR catches up with fewer, longer calls.
+    Merge,
+    /// Evict queued partials and non-speech first; when only final speech is
+    /// queued, the new utterance is dropped instead.
+    SkipNonSpeech,
+}
+
+/// Sizing and overflow behaviour of the queue between VAD and ASR.
+#[derive(Debug, Clone)]
+pub struct QueueConfig {
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 8,
+            overflow: OverflowPolicy::default(),
+        }
+    }
+}
+
+impl QueueConfig {
+    /// Read `ASR_QUEUE_CAPACITY` and `ASR_OVERFLOW` (`drop-oldest`, `merge`
+    /// or `skip-non-speech`).
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut config = Self::default();
+        if let Ok(capacity) = std::env::var("ASR_QUEUE_CAPACITY") {
+            config.capacity = capacity.parse()
+                .map_err(|_| anyhow::anyhow!("Invalid ASR_QUEUE_CAPACITY: {}", capacity))?;
+        }
+        config.overflow = match std::env::var("ASR_OVERFLOW").ok().as_deref() {
+            None | Some("") | Some("drop-oldest") => OverflowPolicy::DropOldest,
+            Some("merge") => OverflowPolicy::Merge,
+            Some("skip-non-speech") => OverflowPolicy::SkipNonSpeech,
+            Some(other) => return Err(anyhow::anyhow!("Unknown ASR overflow policy: {}", other)),
+        };
+        Ok(config)
+    }
+}
+
+/// Counters for the VAD to ASR queue, reported through the pipeline status.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct QueueMetrics {
+    pub depth: usize,
+    pub peak_depth: usize,
+    pub capacity: usize,
+    pub enqueued: u64,
+    /// Partials replaced by newer audio of the same utterance before ASR
+    /// reached them; expected, not a sign of overload.
+    pub superseded: u64,
+    pub merged: u64,
+    pub dropped: u64,
+    pub dropped_audio_secs: f32,
+}
+
+/// Longest silence inserted between merged utterances.
+const MERGE_GAP_SECS: f32 = 0.3;
+
+struct Shared {
+    config: QueueConfig,
+    queue: Mutex<VecDeque<AudioChunk>>,
+    metrics: Arc<Mutex<QueueMetrics>>,
+    notify: Notify,
+    senders: AtomicUsize,
+    receiver_alive: AtomicBool,
+}
+
+/// Create a bounded queue of utterances for the ASR stage.
+///
+/// Sending never blocks, so the queue can be fed from audio callbacks and
+/// std threads as well as async tasks; when full, `config.overflow` decides
+/// what is lost. The receiver sees the end of the stream once every sender
+/// has been dropped.
+pub fn channel(config: QueueConfig) -> (QueueSender, QueueReceiver) {
+    let capacity = config.capacity.max(1);
+    let shared = Arc::new(Shared {
+        config: QueueConfig { capacity, ..config },
+        queue: Mutex::new(VecDeque::with_capacity(capacity)),
+        metrics: Arc::new(Mutex::new(QueueMetrics { capacity, ..Default::default() })),
+        notify: Notify::new(),
+        senders: AtomicUsize::new(1),
+        receiver_alive: AtomicBool::new(true),
+    });
+
+    (QueueSender { shared: shared.clone() }, QueueReceiver { shared })
+}
+
+pub struct QueueSender {
+    shared: Arc<Shared>,
+}
+
+impl QueueSender {
+    /// Queue an utterance; fails only once the receiver has gone away.
+    pub fn send(&self, chunk: AudioChunk) -> Result<(), AudioChunk> {
+        if !self.shared.receiver_alive.load(Ordering::Acquire) {
+            return Err(chunk);
+        }
+
+        let mut queue = self.shared.queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut metrics = self.shared.metrics.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        // Queued partials of this utterance are stale once newer audio arrives
+        let before = queue.len();
+        queue.retain(|queued| queued.is_final || queued.utterance_id != chunk.utterance_id);
+        metrics.superseded += (before - queue.len()) as u64;
+
+        let chunk = if queue.len() >= self.shared.config.capacity {
+            self.make_room(&mut queue, &mut metrics, chunk)
+        } else {
+            Some(chunk)
+        };
+
+        if let Some(chunk) = chunk {
+            queue.push_back(chunk);
+            metrics.enqueued += 1;
+        }
+        metrics.depth = queue.len();
+        metrics.peak_depth = metrics.peak_depth.max(queue.len());
+
+        drop(metrics);
+        drop(queue);
+        self.shared.notify.notify_one();
+        Ok(())
+    }
+
+    // Frees a slot according to the overflow policy. Returns the chunk to
+    // enqueue, or `None` if it was dropped or merged into a queued one.
+    fn make_room(&self, queue: &mut VecDeque<AudioChunk>, metrics: &mut QueueMetrics, chunk: AudioChunk) -> Option<AudioChunk> {
+        if !chunk.is_final {
+            record_drop(metrics, &chunk);
+            return None;
+        }
+
+        match self.shared.config.overflow {
+            OverflowPolicy::DropOldest => {},
+            OverflowPolicy::Merge => {
+                let target = queue.iter_mut()
+                    .rev()
+                    .find(|queued| queued.is_final && queued.source == chunk.source);
+                if let Some(target) = target {
+                    let gap = (chunk.start_time - target.end_time).clamp(0.0, MERGE_GAP_SECS);
+                    let gap_samples = (gap * target.sample_rate as f32) as usize;
+                    target.data.extend(std::iter::repeat_n(0.0, gap_samples));
+                    target.data.extend_from_slice(&chunk.data);
+                    target.end_time = chunk.end_time;
+                    // The queued utterance keeps its ID so its live caption
+                    // still gets a final transcript
+                    target.speaker_id = None;
+                    metrics.merged += 1;
+                    return None;
+                }
+            },
+            OverflowPolicy::SkipNonSpeech => {
+                match queue.iter().position(|queued| !queued.is_final || !queued.has_voice) {
+                    Some(index) => {
+                        if let Some(evicted) = queue.remove(index) {
+                            record_drop(metrics, &evicted);
+                        }
+                        return Some(chunk);
+                    },
+                    None => {
+                        record_drop(metrics, &chunk);
+                        return None;
+                    },
+                }
+            },
+        }
+
+        if let Some(evicted) = queue.pop_front() {
+            record_drop(metrics, &evicted);
+        }
+        Some(chunk)
+    }
+}
+
+impl Clone for QueueSender {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::AcqRel);
+        Self { shared: self.shared.clone() }
+    }
+}
+
+impl Drop for QueueSender {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.notify.notify_one();
+        }
+    }
+}
+
+pub struct QueueReceiver {
+    shared: Arc<Shared>,
+}
+
+impl QueueReceiver {
+    /// Next utterance in arrival order, or `None` once every sender is gone
+    /// and the queue has drained.
+    pub async fn recv(&self) -> Option<AudioChunk> {
+        loop {
+            {
+                let mut queue = self.shared.queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                if let Some(chunk) = queue.pop_front() {
+                    if let Ok(mut metrics) = self.shared.metrics.lock() {
+                        metrics.depth = queue.len();
+                    }
+                    return Some(chunk);
+                }
+                if self.shared.senders.load(Ordering::Acquire) == 0 {
+                    return None;
+                }
+            }
+
+            // `notify_one` stores a permit, so a send between the check and
+            // here still wakes us
+            self.shared.notify.notified().await;
+        }
+    }
+
+    /// Shared view of the queue counters.
+    pub fn metrics(&self) -> Arc<Mutex<QueueMetrics>> {
+        self.shared.metrics.clone()
+    }
+}
+
+impl Drop for QueueReceiver {
+    fn drop(&mut self) {
+        self.shared.receiver_alive.store(false, Ordering::Release);
+    }
+}
+
+fn record_drop(metrics: &mut QueueMetrics, chunk: &AudioChunk) {
+    let secs = chunk.data.len() as f32 / chunk.sample_rate.max(1) as f32;
+    metrics.dropped += 1;
+    metrics.dropped_audio_secs += secs;
+    if chunk.is_final {
+        warn!("ASR queue full, dropped {:.1}s of audio (utterance {})", secs, chunk.utterance_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::SourceRole;
+
+    const RATE: u32 = 16000;
+
+    fn utterance(utterance_id: u64, is_final: bool, start_time: f32, secs: f32) -> AudioChunk {
+        AudioChunk {
+            data: vec![0.1; (secs * RATE as f32) as usize],
+            sample_rate: RATE,
+            channels: 1,
+            timestamp: std::time::Instant::now(),
+            has_voice: true,
+            start_time,
+            end_time: start_time + secs,
+            utterance_id,
+            is_final,
+            speaker_id: Some(1),
+            source: "mic".to_string(),
+            source_role: SourceRole::default(),
+        }
+    }
+
+    fn config(capacity: usize, overflow: OverflowPolicy) -> QueueConfig {
+        QueueConfig { capacity, overflow }
+    }
+
+    fn queued_ids(receiver: &QueueReceiver) -> Vec<(u64, bool)> {
+        let queue = receiver.shared.queue.lock().unwrap();
+        queue.iter().map(|chunk| (chunk.utterance_id, chunk.is_final)).collect()
+    }
+
+    fn metrics(receiver: &QueueReceiver) -> QueueMetrics {
+        receiver.metrics().lock().unwrap().clone()
+    }
+
+    #[test]
+    fn drop_oldest_evicts_the_oldest_utterance() {
+        let (tx, rx) = channel(config(2, OverflowPolicy::DropOldest));
+        tx.send(utterance(1, true, 0.0, 1.0)).unwrap();
+        tx.send(utterance(2, true, 1.0, 0.5)).unwrap();
+        tx.send(utterance(3, true, 2.0, 0.5)).unwrap();
+
+        assert_eq!(queued_ids(&rx), vec![(2, true), (3, true)]);
+        let metrics = metrics(&rx);
+        assert_eq!(metrics.enqueued, 3);
+        assert_eq!(metrics.dropped, 1);
+        assert!((metrics.dropped_audio_secs - 1.0).abs() < 1e-4);
+        assert_eq!(metrics.depth, 2);
+        assert_eq!(metrics.peak_depth, 2);
+        assert_eq!(metrics.capacity, 2);
+    }
+
+    #[test]
+    fn merge_appends_to_the_newest_utterance_and_keeps_its_id() {
+        let (tx, rx) = channel(config(2, OverflowPolicy::Merge));
+        tx.send(utterance(1, true, 0.0, 1.0)).unwrap();
+        tx.send(utterance(2, true, 1.0, 1.0)).unwrap();
+        // A 0.25 s pause before the next utterance is kept as silence
+        tx.send(utterance(3, true, 2.25, 0.5)).unwrap();
+
+        assert_eq!(queued_ids(&rx), vec![(1, true), (2, true)]);
+        let queue = rx.shared.queue.lock().unwrap();
+        let merged = &queue[1];
+        assert_eq!(merged.data.len(), 16000 + 4000 + 8000);
+        assert!(merged.data[16000..20000].iter().all(|&sample| sample == 0.0));
+        assert!((merged.end_time - 2.75).abs() < 1e-4);
+        assert_eq!(merged.speaker_id, None);
+        drop(queue);
+
+        let metrics = metrics(&rx);
+        assert_eq!(metrics.merged, 1);
+        assert_eq!(metrics.dropped, 0);
+        assert_eq!(metrics.dropped_audio_secs, 0.0);
+    }
+
+    #[test]
+    fn merge_caps_the_gap_and_falls_back_to_dropping_the_oldest() {
+        let (tx, rx) = channel(config(1, OverflowPolicy::Merge));
+        tx.send(utterance(1, true, 0.0, 1.0)).unwrap();
+        tx.send(utterance(2, true, 10.0, 1.0)).unwrap();
+        {
+            let queue = rx.shared.queue.lock().unwrap();
+            let gap_samples = (MERGE_GAP_SECS * RATE as f32) as usize;
+            assert_eq!(queue[0].data.len(), 2 * 16000 + gap_samples);
+        }
+
+        // Nothing queued from another input to merge into
+        let mut other = utterance(3, true, 12.0, 0.5);
+        other.source = "loopback".to_string();
+        tx.send(other).unwrap();
+        assert_eq!(queued_ids(&rx), vec![(3, true)]);
+        assert_eq!(metrics(&rx).dropped, 1);
+    }
+
+    #[test]
+    fn skip_non_speech_evicts_partials_and_silence_before_speech() {
+        let (tx, rx) = channel(config(3, OverflowPolicy::SkipNonSpeech));
+        tx.send(utterance(1, true, 0.0, 1.0)).unwrap();
+        let mut silence = utterance(2, true, 1.0, 0.5);
+        silence.has_voice = false;
+        tx.send(silence).unwrap();
+        tx.send(utterance(3, false, 2.0, 0.5)).unwrap();
+
+        tx.send(utterance(4, true, 3.0, 1.0)).unwrap();
+        assert_eq!(queued_ids(&rx), vec![(1, true), (3, false), (4, true)]);
+        tx.send(utterance(5, true, 4.0, 1.0)).unwrap();
+        assert_eq!(queued_ids(&rx), vec![(1, true), (4, true), (5, true)]);
+
+        // Only final speech is left, so the newcomer is the one dropped
+        tx.send(utterance(6, true, 5.0, 2.0)).unwrap();
+        assert_eq!(queued_ids(&rx), vec![(1, true), (4, true), (5, true)]);
+        let metrics = metrics(&rx);
+        assert_eq!(metrics.dropped, 3);
+        assert!((metrics.dropped_audio_secs - 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn newer_audio_replaces_queued_partials_even_when_full() {
+        let (tx, rx) = channel(config(2, OverflowPolicy::DropOldest));
+        tx.send(utterance(1, true, 0.0, 1.0)).unwrap();
+        tx.send(utterance(2, false, 1.0, 0.5)).unwrap();
+        tx.send(utterance(2, false, 1.0, 1.0)).unwrap();
+        assert_eq!(queued_ids(&rx), vec![(1, true), (2, false)]);
+        tx.send(utterance(2, true, 1.0, 1.5)).unwrap();
+        assert_eq!(queued_ids(&rx), vec![(1, true), (2, true)]);
+
+        let metrics = metrics(&rx);
+        assert_eq!(metrics.superseded, 2);
+        assert_eq!(metrics.dropped, 0);
+    }
+
+    #[test]
+    fn partial_is_dropped_when_the_queue_is_full() {
+        for overflow in [OverflowPolicy::DropOldest, OverflowPolicy::Merge, OverflowPolicy::SkipNonSpeech] {
+            let (tx, rx) = channel(config(1, overflow));
+            tx.send(utterance(1, true, 0.0, 1.0)).unwrap();
+            tx.send(utterance(2, false, 1.0, 0.5)).unwrap();
+
+            assert_eq!(queued_ids(&rx), vec![(1, true)]);
+            let metrics = metrics(&rx);
+            assert_eq!(metrics.dropped, 1);
+            assert!((metrics.dropped_audio_secs - 0.5).abs() < 1e-4);
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_ends_after_the_last_sender_drops() {
+        let (tx, rx) = channel(QueueConfig::default());
+        let second = tx.clone();
+        tx.send(utterance(1, true, 0.0, 0.5)).unwrap();
+        drop(tx);
+        second.send(utterance(2, true, 1.0, 0.5)).unwrap();
+
+        let reader = tokio::spawn(async move {
+            let mut ids = Vec::new();
+            while let Some(chunk) = rx.recv().await {
+                ids.push(chunk.utterance_id);
+            }
+            ids
+        });
+        tokio::task::yield_now().await;
+        drop(second);
+
+        let ids = tokio::time::timeout(std::time::Duration::from_secs(5), reader)
+            .await
+            .expect("receiver did not see the end of the stream")
+            .unwrap();
+        assert_eq!(ids, vec![1, 2]);
+    }
+
+    #[test]
+    fn send_fails_once_the_receiver_is_gone() {
+        let (tx, rx) = channel(QueueConfig::default());
+        drop(rx);
+        assert!(tx.send(utterance(1, true, 0.0, 0.5)).is_err());
+    }
+}
@@ -1,19 +1,761 @@
-/// Minimal in-memory vector store placeholder used for RAG queries.
-#[derive(Debug, Clone)]
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::info;
+use crate::embedding::{dot, normalize};
+use crate::storage::{timestamp, Database};
+
+/// Dimensions of the hashing embedder unless `RAG_EMBEDDER` says otherwise.
+const HASHING_DIMENSIONS: usize = 512;
+
+/// Rank offset in reciprocal rank fusion; the usual 60 keeps one retriever's
+/// top hit from drowning out agreement further down both lists.
+const RRF_K: f32 = 60.0;
+
+/// A stored chunk matching a query, with where it came from so suggestions
+/// can cite it.
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct VectorItem {
     pub content: String,
-    pub score: f32,
+    pub score: Score,
+    pub document_id: String,
+    /// Where the document came from, e.g. a file path.
+    pub source: String,
+    /// Position of the chunk within its document.
+    pub chunk_index: u32,
+    /// Byte offset of the chunk within the document.
+    pub start_offset: u64,
+}
+
+/// How a retrieved chunk was scored, for debugging relevance.
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct Score {
+    /// What results are ranked and cut off by: the rerank score when a
+    /// reranker ran, else the fused score.
+    pub value: f32,
+    /// Reciprocal rank fusion of both retrievers, scaled so 1 means ranked
+    /// first by both.
+    pub fused: f32,
+    /// Cosine similarity, if the chunk was among the vector candidates.
+    pub vector: Option<f32>,
+    /// BM25 relevance (higher is better), if the chunk was among the
+    /// keyword candidates.
+    pub bm25: Option<f32>,
+    pub rerank: Option<f32>,
+}
+
+/// Narrows retrieval; empty fields match everything.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct RetrievalFilter {
+    pub document_ids: Vec<String>,
+    /// Only documents whose source starts with one of these, e.g. a folder.
+    pub source_prefixes: Vec<String>,
+    /// Only documents made from this meeting.
+    pub meeting_id: Option<String>,
+}
+
+/// What a hybrid search returns and how it ranks it.
+#[derive(Debug, Clone)]
+pub struct RetrievalOptions {
+    pub k: usize,
+    pub filter: RetrievalFilter,
+    /// Drop results whose `Score::value` is lower.
+    pub min_score: Option<f32>,
+    /// Chunks taken from each retriever, and given to the reranker.
+    pub candidates: usize,
+}
+
+impl Default for RetrievalOptions {
+    fn default() -> Self {
+        Self {
+            k: 5,
+            filter: RetrievalFilter::default(),
+            min_score: None,
+            candidates: 50,
+        }
+    }
+}
+
+/// Rescores retrieved passages against the query, e.g. with a cross-encoder
+/// that reads both together; slower but more precise than retrieval.
+pub trait Reranker: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// A relevance score per passage, in order; higher is better.
+    fn rerank(&self, query: &str, passages: &[&str]) -> Result<Vec<f32>>;
+}
+
+/// A document as indexed; every chunk carries it.
+#[derive(Debug, Clone, Default)]
+pub struct DocumentInfo {
+    pub id: String,
+    pub source: String,
+    /// Meeting the document was made from, if any. Its chunks are deleted
+    /// with the meeting's transcript.
+    pub meeting_id: Option<String>,
+}
+
+/// A piece of a document small enough to embed.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub content: String,
+    /// Byte offset of `content` within the document.
+    pub start_offset: usize,
+}
+
+/// Maps text to a fixed-length embedding for similarity search.
+pub trait Embedder: Send + Sync {
+    /// Identifies the embedder and its settings; embeddings from embedders
+    /// with different names are not comparable.
+    fn name(&self) -> &str;
+
+    fn embed(&self, text: &str) -> Result<Vec<f32>>;
+}
+
+/// Which embedder the vector store should use, chosen at runtime.
+#[derive(Debug, Clone)]
+pub enum EmbedderKind {
+    Hashing { dimensions: usize },
+}
+
+impl Default for EmbedderKind {
+    fn default() -> Self {
+        EmbedderKind::Hashing { dimensions: HASHING_DIMENSIONS }
+    }
+}
+
+impl EmbedderKind {
+    /// Read `RAG_EMBEDDER` (`hashing` or `hashing:<dimensions>`).
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("RAG_EMBEDDER").ok().as_deref() {
+            None | Some("") | Some("hashing") => Ok(Self::default()),
+            Some(spec) => match spec.strip_prefix("hashing:").map(str::parse::<usize>) {
+                Some(Ok(dimensions)) if dimensions > 0 => Ok(EmbedderKind::Hashing { dimensions }),
+                _ => Err(anyhow::anyhow!("Unknown RAG embedder: {}", spec)),
+            },
+        }
+    }
+
+    pub fn build(&self) -> Result<Box<dyn Embedder>> {
+        match self {
+            EmbedderKind::Hashing { dimensions } => Ok(Box::new(HashingEmbedder::new(*dimensions))),
+        }
+    }
 }
 
-pub struct VectorStore;
+/// Embeds text by hashing its words and their character trigrams into a
+/// fixed number of buckets.
+///
+/// Deterministic and needs no model, so it suits tests and machines without
+/// one, but it matches wording rather than meaning: "car" finds "cars" and
+/// "carpool", not "vehicle".
+pub struct HashingEmbedder {
+    dimensions: usize,
+    name: String,
+}
+
+impl HashingEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: dimensions.max(1),
+            name: format!("hashing-{}", dimensions.max(1)),
+        }
+    }
+
+    fn add(&self, embedding: &mut [f32], kind: u8, feature: &str, weight: f32) {
+        let hash = fnv1a(kind, feature.as_bytes());
+        // The hash's top bit picks a sign so collisions tend to cancel out
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        embedding[(hash % self.dimensions as u64) as usize] += sign * weight;
+    }
+}
+
+impl Embedder for HashingEmbedder {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let mut embedding = vec![0.0; self.dimensions];
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+            let word = word.to_lowercase();
+            self.add(&mut embedding, b'w', &word, 1.0);
+
+            // Trigrams let inflections and typos still overlap
+            let padded: Vec<char> = format!("<{}>", word).chars().collect();
+            for trigram in padded.windows(3) {
+                self.add(&mut embedding, b't', &trigram.iter().collect::<String>(), 0.5);
+            }
+        }
+
+        normalize(&mut embedding);
+        Ok(embedding)
+    }
+}
+
+/// Document chunks and their embeddings, kept in the meeting database.
+///
+/// Cheap to clone; clones share the database and embedder. Chunks embedded
+/// by a different embedder than this store's are ignored until reindexed.
+#[derive(Clone)]
+pub struct VectorStore {
+    pool: SqlitePool,
+    embedder: Arc<dyn Embedder>,
+    reranker: Option<Arc<dyn Reranker>>,
+}
 
 impl VectorStore {
-    pub fn new() -> Self {
-        Self
+    pub fn new(database: &Database, embedder: Box<dyn Embedder>) -> Self {
+        info!("Vector store using the {} embedder", embedder.name());
+        Self {
+            pool: database.pool().clone(),
+            embedder: Arc::from(embedder),
+            reranker: None,
+        }
+    }
+
+    /// Rerank the fused candidates of every search with `reranker`.
+    pub fn with_reranker(mut self, reranker: Box<dyn Reranker>) -> Self {
+        info!("Vector store reranking with {}", reranker.name());
+        self.reranker = Some(Arc::from(reranker));
+        self
+    }
+
+    /// Index a new document; fails if one with the same ID is stored.
+    pub async fn insert(&self, document: &DocumentInfo, chunks: &[Chunk]) -> Result<()> {
+        self.write(document, chunks, false).await
+    }
+
+    /// Index a document, replacing every chunk stored for its ID.
+    pub async fn upsert(&self, document: &DocumentInfo, chunks: &[Chunk]) -> Result<()> {
+        self.write(document, chunks, true).await
+    }
+
+    /// Remove a document's chunks, returning how many there were.
+    pub async fn delete(&self, document_id: &str) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM rag_chunks WHERE document_id = ?")
+            .bind(document_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// The `k` chunks most relevant to `query`, best first, with default
+    /// options.
+    pub async fn query(&self, query: &str, k: usize) -> Result<Vec<VectorItem>> {
+        self.search(query, &RetrievalOptions { k, ..RetrievalOptions::default() }).await
+    }
+
+    /// Hybrid search: chunks are retrieved by embedding similarity and by
+    /// BM25 keyword relevance, which catches exact terms such as SKUs,
+    /// ticket numbers and acronyms, and the two rankings are fused with
+    /// reciprocal rank fusion before the optional rerank.
+    pub async fn search(&self, query: &str, options: &RetrievalOptions) -> Result<Vec<VectorItem>> {
+        if options.k == 0 {
+            return Ok(Vec::new());
+        }
+        let candidates = options.candidates.max(options.k);
+        let vector_hits = self.vector_candidates(query, &options.filter, candidates).await?;
+        let keyword_hits = self.keyword_candidates(query, &options.filter, candidates).await?;
+
+        let mut scores: HashMap<i64, Score> = HashMap::new();
+        for (rank, (id, similarity)) in vector_hits.into_iter().enumerate() {
+            let score = scores.entry(id).or_default();
+            score.fused += 1.0 / (RRF_K + rank as f32 + 1.0);
+            score.vector = Some(similarity);
+        }
+        for (rank, (id, relevance)) in keyword_hits.into_iter().enumerate() {
+            let score = scores.entry(id).or_default();
+            score.fused += 1.0 / (RRF_K + rank as f32 + 1.0);
+            score.bm25 = Some(relevance);
+        }
+
+        let best_possible = 2.0 / (RRF_K + 1.0);
+        let mut ranked: Vec<(i64, Score)> = scores.into_iter()
+            .map(|(id, mut score)| {
+                score.fused /= best_possible;
+                score.value = score.fused;
+                (id, score)
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.1.fused.total_cmp(&a.1.fused).then(a.0.cmp(&b.0)));
+        ranked.truncate(if self.reranker.is_some() { candidates } else { options.k });
+
+        let mut items = self.load(&ranked).await?;
+        if let Some(reranker) = &self.reranker {
+            let passages: Vec<&str> = items.iter().map(|item| item.content.as_str()).collect();
+            let reranked = reranker.rerank(query, &passages)?;
+            if reranked.len() != items.len() {
+                return Err(anyhow::anyhow!("Reranker {} returned {} scores for {} passages",
+                    reranker.name(), reranked.len(), items.len()));
+            }
+            for (item, score) in items.iter_mut().zip(reranked) {
+                item.score.rerank = Some(score);
+                item.score.value = score;
+            }
+            items.sort_by(|a, b| b.score.value.total_cmp(&a.score.value));
+        }
+
+        if let Some(min_score) = options.min_score {
+            items.retain(|item| item.score.value >= min_score);
+        }
+        items.truncate(options.k);
+        Ok(items)
+    }
+
+    // Chunks with a positive cosine similarity to the query, best first
+    async fn vector_candidates(&self, query: &str, filter: &RetrievalFilter, limit: usize) -> Result<Vec<(i64, f32)>> {
+        let query_embedding = self.embedder.embed(query)?;
+        if query_embedding.iter().all(|&value| value == 0.0) {
+            return Ok(Vec::new());
+        }
+
+        // Score every chunk on its embedding alone; content is loaded later
+        // for the winners only
+        let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new("SELECT c.id, c.embedding FROM rag_chunks c WHERE c.embedder = ");
+        builder.push_bind(self.embedder.name().to_string());
+        push_filter(&mut builder, filter);
+        let rows = builder.build().fetch_all(&self.pool).await?;
+
+        let mut hits = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let similarity = cosine_similarity(&query_embedding, &from_blob(row.try_get("embedding")?));
+            if similarity > 0.0 {
+                hits.push((row.try_get::<i64, _>("id")?, similarity));
+            }
+        }
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1));
+        hits.truncate(limit);
+        Ok(hits)
+    }
+
+    // Chunks containing any of the query's words, by BM25, best first
+    async fn keyword_candidates(&self, query: &str, filter: &RetrievalFilter, limit: usize) -> Result<Vec<(i64, f32)>> {
+        let Some(match_expression) = any_word_expression(query) else {
+            return Ok(Vec::new());
+        };
+
+        let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "SELECT c.id, bm25(rag_search) AS rank
+             FROM rag_search JOIN rag_chunks c ON c.id = rag_search.rowid
+             WHERE rag_search MATCH ");
+        builder.push_bind(match_expression);
+        builder.push(" AND c.embedder = ").push_bind(self.embedder.name().to_string());
+        push_filter(&mut builder, filter);
+        builder.push(" ORDER BY rank LIMIT ").push_bind(limit as i64);
+
+        let rows = builder.build().fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| {
+                let rank: f64 = row.try_get("rank")?;
+                // FTS5 ranks better matches more negative
+                Ok((row.try_get("id")?, -rank as f32))
+            })
+            .collect()
+    }
+
+    // Chunk contents and metadata for scored IDs, in the given order
+    async fn load(&self, scored: &[(i64, Score)]) -> Result<Vec<VectorItem>> {
+        if scored.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "SELECT id, document_id, chunk_index, source, start_offset, content FROM rag_chunks WHERE id IN (");
+        let mut ids = builder.separated(", ");
+        for (id, _) in scored {
+            ids.push_bind(*id);
+        }
+        builder.push(")");
+        let rows = builder.build().fetch_all(&self.pool).await?;
+
+        let mut items = Vec::with_capacity(scored.len());
+        for (id, score) in scored {
+            let Some(row) = rows.iter().find(|row| row.try_get::<i64, _>("id").ok() == Some(*id)) else {
+                // Deleted since it was scored
+                continue;
+            };
+            items.push(VectorItem {
+                content: row.try_get("content")?,
+                score: *score,
+                document_id: row.try_get("document_id")?,
+                source: row.try_get("source")?,
+                chunk_index: row.try_get::<i64, _>("chunk_index")? as u32,
+                start_offset: row.try_get::<i64, _>("start_offset")? as u64,
+            });
+        }
+        Ok(items)
+    }
+
+    async fn write(&self, document: &DocumentInfo, chunks: &[Chunk], replace: bool) -> Result<()> {
+        // Embed first so the transaction isn't held open while it runs
+        let embeddings = chunks.iter()
+            .map(|chunk| self.embedder.embed(&chunk.content))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut transaction = self.pool.begin().await?;
+        let existing = sqlx::query("DELETE FROM rag_chunks WHERE document_id = ?")
+            .bind(&document.id)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+        if existing > 0 && !replace {
+            return Err(anyhow::anyhow!("Document {} is already indexed", document.id));
+        }
+
+        let created_at = timestamp(Utc::now());
+        for (index, (chunk, embedding)) in chunks.iter().zip(&embeddings).enumerate() {
+            sqlx::query(
+                "INSERT INTO rag_chunks
+                    (document_id, chunk_index, source, start_offset, content, embedder, embedding, meeting_id, created_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)")
+                .bind(&document.id)
+                .bind(index as i64)
+                .bind(&document.source)
+                .bind(chunk.start_offset as i64)
+                .bind(&chunk.content)
+                .bind(self.embedder.name())
+                .bind(to_blob(embedding))
+                .bind(&document.meeting_id)
+                .bind(&created_at)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+}
+
+fn push_filter(builder: &mut QueryBuilder<Sqlite>, filter: &RetrievalFilter) {
+    if !filter.document_ids.is_empty() {
+        builder.push(" AND c.document_id IN (");
+        let mut ids = builder.separated(", ");
+        for id in &filter.document_ids {
+            ids.push_bind(id.clone());
+        }
+        builder.push(")");
+    }
+    if !filter.source_prefixes.is_empty() {
+        builder.push(" AND (");
+        for (i, prefix) in filter.source_prefixes.iter().enumerate() {
+            if i > 0 {
+                builder.push(" OR ");
+            }
+            builder.push("substr(c.source, 1, length(").push_bind(prefix.clone())
+                .push(")) = ").push_bind(prefix.clone());
+        }
+        builder.push(")");
+    }
+    if let Some(meeting_id) = &filter.meeting_id {
+        builder.push(" AND c.meeting_id = ").push_bind(meeting_id.clone());
+    }
+}
+
+// Matches chunks with any of the query's words. Each word is quoted, so
+// FTS syntax in the query is taken literally and "AB-1234" is matched as
+// the phrase "ab 1234" rather than two unrelated tokens.
+fn any_word_expression(query: &str) -> Option<String> {
+    let words: Vec<String> = query.split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" OR "))
+    }
+}
+
+// 64-bit FNV-1a, stable across platforms and releases unlike std's hasher
+fn fnv1a(kind: u8, bytes: &[u8]) -> u64 {
+    std::iter::once(&kind).chain(bytes).fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn to_blob(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+fn from_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let norms = (dot(a, a) * dot(b, b)).sqrt();
+    if norms > 0.0 {
+        dot(a, b) / norms
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts of a few known words, so similarities are easy to work out
+    struct WordCountEmbedder;
+
+    const VOCABULARY: [&str; 4] = ["budget", "forecast", "hiring", "travel"];
+
+    impl Embedder for WordCountEmbedder {
+        fn name(&self) -> &str {
+            "word-count"
+        }
+
+        fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            let mut embedding = vec![0.0; VOCABULARY.len()];
+            for word in text.split(|c: char| !c.is_alphanumeric()) {
+                if let Some(index) = VOCABULARY.iter().position(|known| word.eq_ignore_ascii_case(known)) {
+                    embedding[index] += 1.0;
+                }
+            }
+            Ok(embedding)
+        }
+    }
+
+    async fn store() -> VectorStore {
+        let database = Database::in_memory().await.unwrap();
+        VectorStore::new(&database, Box::new(WordCountEmbedder))
+    }
+
+    fn document(id: &str, source: &str) -> DocumentInfo {
+        DocumentInfo {
+            id: id.to_string(),
+            source: source.to_string(),
+            meeting_id: None,
+        }
     }
 
-    /// Perform a vector similarity search. Returns an empty result for now.
-    pub fn query(&self, _query: &str) -> Vec<VectorItem> {
-        Vec::new()
+    fn chunks(contents: &[&str]) -> Vec<Chunk> {
+        let mut start_offset = 0;
+        contents.iter()
+            .map(|content| {
+                let chunk = Chunk { content: content.to_string(), start_offset };
+                start_offset += content.len() + 1;
+                chunk
+            })
+            .collect()
+    }
+
+    fn documents(items: &[VectorItem]) -> Vec<&str> {
+        items.iter().map(|item| item.document_id.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn insert_upsert_and_delete_by_document() {
+        let store = store().await;
+        store.insert(&document("plan", "/docs/plan.md"), &chunks(&["budget review", "budget owners"])).await.unwrap();
+        store.insert(&document("trip", "/docs/trip.md"), &chunks(&["travel budget"])).await.unwrap();
+        assert_eq!(store.query("budget", 10).await.unwrap().len(), 3);
+
+        // Upsert replaces every chunk of the document
+        store.upsert(&document("plan", "/docs/plan.md"), &chunks(&["hiring plan"])).await.unwrap();
+        let budget = store.query("budget", 10).await.unwrap();
+        assert_eq!(documents(&budget), ["trip"]);
+        assert_eq!(documents(&store.query("hiring", 10).await.unwrap()), ["plan"]);
+
+        assert_eq!(store.delete("plan").await.unwrap(), 1);
+        assert!(store.query("hiring", 10).await.unwrap().is_empty());
+        assert_eq!(store.delete("plan").await.unwrap(), 0);
+        assert_eq!(store.query("budget", 10).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn insert_rejects_an_indexed_document() {
+        let store = store().await;
+        store.insert(&document("plan", "/docs/plan.md"), &chunks(&["budget review"])).await.unwrap();
+
+        let result = store.insert(&document("plan", "/docs/other.md"), &chunks(&["hiring plan"])).await;
+        assert!(result.is_err());
+        // The failed insert left the original alone
+        let items = store.query("budget hiring", 10).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].content, "budget review");
+    }
+
+    #[tokio::test]
+    async fn vector_candidates_rank_by_cosine_similarity() {
+        let store = store().await;
+        store.insert(&document("a", "a"), &chunks(&["budget forecast"])).await.unwrap();
+        store.insert(&document("b", "b"), &chunks(&["budget"])).await.unwrap();
+        store.insert(&document("c", "c"), &chunks(&["budget budget forecast hiring"])).await.unwrap();
+        store.insert(&document("d", "d"), &chunks(&["travel"])).await.unwrap();
+
+        let hits = store.vector_candidates("budget forecast", &RetrievalFilter::default(), 10).await.unwrap();
+        let similarities: Vec<f32> = hits.iter().map(|&(_, similarity)| similarity).collect();
+        // "travel" is orthogonal to the query and left out
+        assert_eq!(similarities.len(), 3);
+        assert!((similarities[0] - 1.0).abs() < 1e-6);
+        assert!((similarities[1] - 3.0 / (2.0f32.sqrt() * 6.0f32.sqrt())).abs() < 1e-6);
+        assert!((similarities[2] - 1.0 / 2.0f32.sqrt()).abs() < 1e-6);
+
+        let top = store.vector_candidates("budget forecast", &RetrievalFilter::default(), 2).await.unwrap();
+        assert_eq!(top, hits[..2]);
+    }
+
+    #[tokio::test]
+    async fn items_carry_their_source() {
+        let store = store().await;
+        store.insert(&document("plan", "/docs/plan.md"), &chunks(&["hiring plan", "travel budget"])).await.unwrap();
+
+        let items = store.query("travel", 1).await.unwrap();
+        assert_eq!(items.len(), 1);
+        let item = &items[0];
+        assert_eq!(item.content, "travel budget");
+        assert_eq!(item.document_id, "plan");
+        assert_eq!(item.source, "/docs/plan.md");
+        assert_eq!(item.chunk_index, 1);
+        assert_eq!(item.start_offset, "hiring plan".len() as u64 + 1);
+        assert!(item.score.vector.is_some());
+    }
+
+    #[test]
+    fn embeddings_round_trip_through_blobs() {
+        let embedding = vec![0.0, -1.5, f32::MIN_POSITIVE, 3.25e7];
+        let blob = to_blob(&embedding);
+        assert_eq!(blob.len(), embedding.len() * 4);
+        assert_eq!(from_blob(&blob), embedding);
+        assert!(from_blob(&[]).is_empty());
+    }
+
+    // Scores passages in order from a list, which may run out early
+    struct FixedReranker {
+        scores: Vec<f32>,
+    }
+
+    impl Reranker for FixedReranker {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn rerank(&self, _query: &str, passages: &[&str]) -> Result<Vec<f32>> {
+            Ok(self.scores.iter().copied().take(passages.len()).collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn search_fuses_both_rankings() {
+        let store = store().await;
+        store.insert(&document("exact", "exact"), &chunks(&["budget forecast"])).await.unwrap();
+        store.insert(&document("partial", "partial"), &chunks(&["forecast for travel and hiring"])).await.unwrap();
+
+        let items = store.query("budget forecast", 5).await.unwrap();
+        assert_eq!(documents(&items), ["exact", "partial"]);
+
+        // First in both rankings is the best possible fused score
+        let best = items[0].score;
+        assert!((best.fused - 1.0).abs() < 1e-6);
+        assert_eq!(best.value, best.fused);
+        assert!((best.vector.unwrap() - 1.0).abs() < 1e-6);
+        assert!(best.bm25.unwrap() > 0.0);
+
+        // Second in both: 1/62 twice, scaled by 61/2
+        let second = items[1].score;
+        assert!((second.fused - 61.0 / 62.0).abs() < 1e-6);
+        assert!(second.vector.is_some() && second.bm25.is_some());
+    }
+
+    #[tokio::test]
+    async fn exact_terms_are_found_by_keywords_alone() {
+        let store = store().await;
+        store.insert(&document("order", "order"), &chunks(&["Order AB-1234 shipped late"])).await.unwrap();
+        store.insert(&document("other", "other"), &chunks(&["Order AB-9999 is on budget"])).await.unwrap();
+
+        // The embedder knows no SKUs, so only BM25 can find the order
+        let items = store.query("AB-1234", 5).await.unwrap();
+        assert_eq!(documents(&items), ["order"]);
+        assert_eq!(items[0].score.vector, None);
+        assert!(items[0].score.bm25.is_some());
+        assert!((items[0].score.fused - 0.5).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn filters_narrow_both_retrievers() {
+        let database = Database::in_memory().await.unwrap();
+        let meeting = database.create_meeting("Planning").await.unwrap();
+        let store = VectorStore::new(&database, Box::new(WordCountEmbedder));
+        store.insert(&document("plan", "/docs/plan.md"), &chunks(&["budget plan"])).await.unwrap();
+        store.insert(&document("notes", "/notes/monday.md"), &chunks(&["budget notes"])).await.unwrap();
+        store.insert(&document("docsearch", "/docsearch/index.md"), &chunks(&["budget index"])).await.unwrap();
+        let transcript = DocumentInfo {
+            meeting_id: Some(meeting.id.clone()),
+            ..document("transcript", "meeting")
+        };
+        store.insert(&transcript, &chunks(&["budget transcript"])).await.unwrap();
+
+        let search = |filter: RetrievalFilter| {
+            let store = store.clone();
+            async move {
+                let options = RetrievalOptions { k: 10, filter, ..RetrievalOptions::default() };
+                let mut found: Vec<String> = store.search("budget", &options).await.unwrap()
+                    .into_iter()
+                    .map(|item| item.document_id)
+                    .collect();
+                found.sort();
+                found
+            }
+        };
+
+        assert_eq!(search(RetrievalFilter::default()).await.len(), 4);
+        assert_eq!(search(RetrievalFilter {
+            document_ids: vec!["plan".to_string(), "transcript".to_string()],
+            ..RetrievalFilter::default()
+        }).await, ["plan", "transcript"]);
+        assert_eq!(search(RetrievalFilter {
+            source_prefixes: vec!["/docs/".to_string(), "/notes/".to_string()],
+            ..RetrievalFilter::default()
+        }).await, ["notes", "plan"]);
+        assert_eq!(search(RetrievalFilter {
+            meeting_id: Some(meeting.id.clone()),
+            ..RetrievalFilter::default()
+        }).await, ["transcript"]);
+    }
+
+    #[tokio::test]
+    async fn min_score_drops_weak_results() {
+        let store = store().await;
+        store.insert(&document("exact", "exact"), &chunks(&["budget forecast"])).await.unwrap();
+        store.insert(&document("partial", "partial"), &chunks(&["forecast for travel and hiring"])).await.unwrap();
+
+        let options = RetrievalOptions { min_score: Some(0.99), ..RetrievalOptions::default() };
+        let items = store.search("budget forecast", &options).await.unwrap();
+        assert_eq!(documents(&items), ["exact"]);
+    }
+
+    #[tokio::test]
+    async fn reranker_scores_decide_the_order() {
+        let database = Database::in_memory().await.unwrap();
+        let store = VectorStore::new(&database, Box::new(WordCountEmbedder))
+            .with_reranker(Box::new(FixedReranker { scores: vec![0.1, 0.9] }));
+        store.insert(&document("exact", "exact"), &chunks(&["budget forecast"])).await.unwrap();
+        store.insert(&document("partial", "partial"), &chunks(&["forecast for travel and hiring"])).await.unwrap();
+
+        let items = store.query("budget forecast", 5).await.unwrap();
+        assert_eq!(documents(&items), ["partial", "exact"]);
+        assert_eq!(items[0].score.rerank, Some(0.9));
+        assert_eq!(items[0].score.value, 0.9);
+        assert!(items[0].score.fused < items[1].score.fused);
+    }
+
+    #[tokio::test]
+    async fn reranker_returning_the_wrong_number_of_scores_fails() {
+        let database = Database::in_memory().await.unwrap();
+        let store = VectorStore::new(&database, Box::new(WordCountEmbedder))
+            .with_reranker(Box::new(FixedReranker { scores: vec![1.0] }));
+        store.insert(&document("exact", "exact"), &chunks(&["budget forecast"])).await.unwrap();
+        store.insert(&document("partial", "partial"), &chunks(&["forecast for travel and hiring"])).await.unwrap();
+
+        let error = store.query("budget forecast", 5).await.unwrap_err();
+        assert!(error.to_string().contains("returned 1 scores for 2 passages"), "{}", error);
     }
 }
@@ -0,0 +1,205 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+/// Sample rate expected by Whisper and the rest of the pipeline.
+pub const TARGET_SAMPLE_RATE: u32 = 16000;
+
+/// Half-width of the interpolation kernel, in input samples.
+const KERNEL_HALF_WIDTH: usize = 16;
+
+/// Fraction of the output Nyquist frequency kept by the anti-aliasing filter.
+const CUTOFF_RATIO: f64 = 0.92;
+
+/// Streaming windowed-sinc resampler.
+///
+/// Keeps enough history between calls that consecutive buffers from an audio
+/// callback resample into one continuous signal without clicks at the edges.
+/// When downsampling the sinc cutoff is lowered to the output Nyquist
+/// frequency, so the kernel doubles as the anti-aliasing low-pass filter.
+pub struct Resampler {
+    from_rate: u32,
+    to_rate: u32,
+    step: f64,
+    cutoff: f64,
+    buffer: Vec<f32>,
+    position: f64,
+}
+
+impl Resampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> Self {
+        let step = from_rate as f64 / to_rate as f64;
+        // Normalised to the input rate: 0.5 is the input Nyquist frequency
+        let cutoff = 0.5 * (1.0 / step).min(1.0) * CUTOFF_RATIO;
+
+        Self {
+            from_rate,
+            to_rate,
+            step,
+            cutoff,
+            // Leading silence lets the first output sample see a full kernel
+            buffer: vec![0.0; KERNEL_HALF_WIDTH],
+            position: KERNEL_HALF_WIDTH as f64,
+        }
+    }
+
+    pub fn from_rate(&self) -> u32 {
+        self.from_rate
+    }
+
+    pub fn to_rate(&self) -> u32 {
+        self.to_rate
+    }
+
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        if self.from_rate == self.to_rate {
+            return input.to_vec();
+        }
+
+        self.buffer.extend_from_slice(input);
+
+        let mut output = Vec::with_capacity((input.len() as f64 / self.step).ceil() as usize + 1);
+        while self.position + (KERNEL_HALF_WIDTH as f64) < self.buffer.len() as f64 {
+            output.push(self.interpolate(self.position));
+            self.position += self.step;
+        }
+
+        // Drop history the next kernel can no longer reach
+        let consumed = (self.position.floor() as usize).saturating_sub(KERNEL_HALF_WIDTH);
+        if consumed > 0 {
+            self.buffer.drain(..consumed);
+            self.position -= consumed as f64;
+        }
+
+        output
+    }
+
+    fn interpolate(&self, position: f64) -> f32 {
+        let center = position.floor() as isize;
+        let half = KERNEL_HALF_WIDTH as isize;
+
+        let mut sum = 0.0f64;
+        for index in (center - half + 1)..=(center + half) {
+            if index < 0 || index as usize >= self.buffer.len() {
+                continue;
+            }
+            let distance = position - index as f64;
+            sum += self.buffer[index as usize] as f64 * self.kernel(distance);
+        }
+
+        sum as f32
+    }
+
+    fn kernel(&self, distance: f64) -> f64 {
+        let half = KERNEL_HALF_WIDTH as f64;
+        if distance.abs() >= half {
+            return 0.0;
+        }
+
+        let x = 2.0 * self.cutoff * distance;
+        let sinc = if x.abs() < 1e-9 { 1.0 } else { (PI * x).sin() / (PI * x) };
+
+        // Blackman window over the kernel span
+        let w = (distance + half) / (2.0 * half);
+        let window = 0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos();
+
+        2.0 * self.cutoff * sinc * window
+    }
+}
+
+/// Converts interleaved device audio into 16 kHz mono for the VAD and ASR.
+pub struct AudioConverter {
+    channels: u16,
+    resampler: Resampler,
+}
+
+impl AudioConverter {
+    pub fn new(from_rate: u32, channels: u16) -> Self {
+        Self {
+            channels: channels.max(1),
+            resampler: Resampler::new(from_rate, TARGET_SAMPLE_RATE),
+        }
+    }
+
+    /// Whether this converter was built for the given input format.
+    pub fn matches(&self, from_rate: u32, channels: u16) -> bool {
+        self.resampler.from_rate() == from_rate && self.channels == channels.max(1)
+    }
+
+    pub fn process(&mut self, interleaved: &[f32]) -> Vec<f32> {
+        let mono = downmix(interleaved, self.channels);
+        self.resampler.process(&mono)
+    }
+}
+
+/// Most audio a mixer input may run ahead of the others before its oldest
+/// samples are dropped, bounding the latency added by device clock drift.
+const MIXER_MAX_LAG: usize = TARGET_SAMPLE_RATE as usize;
+
+/// Sums several 16 kHz mono streams into one.
+///
+/// Inputs arrive in independently sized buffers, so samples are held until
+/// every live input has supplied them. Inputs that have ended stop holding
+/// the others back.
+pub struct Mixer {
+    buffers: Vec<VecDeque<f32>>,
+    live: Vec<bool>,
+}
+
+impl Mixer {
+    pub fn new(inputs: usize) -> Self {
+        Self {
+            buffers: vec![VecDeque::new(); inputs],
+            live: vec![true; inputs],
+        }
+    }
+
+    /// Add samples from one input; returns whatever can now be mixed.
+    pub fn push(&mut self, input: usize, samples: &[f32]) -> Vec<f32> {
+        let buffer = &mut self.buffers[input];
+        buffer.extend(samples.iter().copied());
+        if buffer.len() > MIXER_MAX_LAG {
+            let excess = buffer.len() - MIXER_MAX_LAG;
+            buffer.drain(..excess);
+        }
+        self.drain_ready()
+    }
+
+    /// Mark an input as ended.
+    pub fn finish(&mut self, input: usize) -> Vec<f32> {
+        self.live[input] = false;
+        self.buffers[input].clear();
+        self.drain_ready()
+    }
+
+    fn drain_ready(&mut self) -> Vec<f32> {
+        let ready = self.buffers.iter()
+            .zip(&self.live)
+            .filter(|(_, &live)| live)
+            .map(|(buffer, _)| buffer.len())
+            .min()
+            .unwrap_or(0);
+
+        let mut mixed = vec![0.0f32; ready];
+        for (buffer, &live) in self.buffers.iter_mut().zip(&self.live) {
+            if !live {
+                continue;
+            }
+            for (out, sample) in mixed.iter_mut().zip(buffer.drain(..ready)) {
+                *out += sample;
+            }
+        }
+        mixed.iter_mut().for_each(|sample| *sample = sample.clamp(-1.0, 1.0));
+        mixed
+    }
+}
+
+/// Average interleaved channels into a single mono channel.
+pub fn downmix(data: &[f32], channels: u16) -> Vec<f32> {
+    if channels <= 1 {
+        return data.to_vec();
+    }
+
+    data.chunks(channels as usize)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
@@ -1,5 +1,17 @@
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
+use std::path::Path;
+use tracing::warn;
+use crate::asr::{language_name, TranscriptResult};
+use crate::rag::{RetrievalOptions, VectorItem, VectorStore};
+use crate::source::SourceRole;
+
+/// Rough bytes per token of English text; prompts are budgeted by estimate
+/// since the model's tokenizer isn't at hand here.
+const BYTES_PER_TOKEN: usize = 4;
+
+/// A passage is left out rather than cut shorter than this.
+const MIN_PASSAGE_TOKENS: usize = 48;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Intent {
@@ -10,12 +22,29 @@ pub enum Intent {
     GeneralAssistance,
 }
 
+impl Intent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Intent::FollowUpQuestion => "follow_up_question",
+            Intent::Definition => "definition",
+            Intent::ActionItem => "action_item",
+            Intent::DraftReply => "draft_reply",
+            Intent::GeneralAssistance => "general_assistance",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Context {
     pub transcript_window: String,
+    /// ISO 639-1 code of the conversation, as detected by ASR.
+    pub language: String,
+    /// Who said the transcript, e.g. "Speaker 2".
     pub speaker_context: Option<String>,
     pub screen_context: Option<String>,
     pub meeting_metadata: Option<MeetingMetadata>,
+    /// Indexed document passages relevant to the transcript, best first.
+    pub passages: Vec<VectorItem>,
 }
 
 #[derive(Debug, Clone)]
@@ -25,16 +54,53 @@ pub struct MeetingMetadata {
     pub duration_minutes: u32,
 }
 
+/// A passage given to the model, cited in the prompt as `[number]`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Citation {
+    pub number: usize,
+    pub document_id: String,
+    /// Where the document came from, e.g. a file path.
+    pub source: String,
+    pub chunk_index: u32,
+    pub start_offset: u64,
+    pub score: f32,
+}
+
+/// How many tokens a prompt may use, and how much of that the transcript
+/// and screen context may take. Passages get whatever is left.
+#[derive(Debug, Clone)]
+pub struct PromptBudget {
+    pub max_tokens: usize,
+    pub transcript_tokens: usize,
+    pub screen_tokens: usize,
+}
+
+impl Default for PromptBudget {
+    fn default() -> Self {
+        Self {
+            max_tokens: 1536,
+            transcript_tokens: 384,
+            screen_tokens: 256,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct RoutingDecision {
     pub intent: Intent,
     pub priority: u8, // 1-10, 10 = highest
     pub context: Context,
     pub suggested_prompt: String,
+    /// Passages that made it into the prompt, for the HUD to show with the
+    /// suggestion.
+    pub citations: Vec<Citation>,
 }
 
 pub struct StateIntentRouter {
     classification_rules: Vec<ClassificationRule>,
+    store: Option<VectorStore>,
+    retrieval: RetrievalOptions,
+    budget: PromptBudget,
 }
 
 struct ClassificationRule {
@@ -65,9 +131,39 @@ impl StateIntentRouter {
         
         Self {
             classification_rules: rules,
+            store: None,
+            retrieval: RetrievalOptions {
+                k: 4,
+                // Found by both retrievers, or ranked first by one
+                min_score: Some(0.5),
+                ..RetrievalOptions::default()
+            },
+            budget: PromptBudget::default(),
         }
     }
     
+    /// Ground prompts in passages from `store`.
+    pub fn with_store(mut self, store: VectorStore) -> Self {
+        self.store = Some(store);
+        self
+    }
+    
+    pub fn with_retrieval(mut self, retrieval: RetrievalOptions) -> Self {
+        self.retrieval = retrieval;
+        self
+    }
+    
+    pub fn with_budget(mut self, budget: PromptBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+    
+    /// Only the other party's finished utterances prompt suggestions; the
+    /// user doesn't need help with what they just said themselves.
+    pub fn should_suggest(&self, transcript: &TranscriptResult) -> bool {
+        transcript.is_final && transcript.source_role != SourceRole::Me
+    }
+    
     pub fn classify_intent(&self, transcript: &str) -> Intent {
         for rule in &self.classification_rules {
             if regex::Regex::new(&rule.pattern)
@@ -79,18 +175,21 @@ impl StateIntentRouter {
         Intent::GeneralAssistance
     }
     
-    pub fn build_context(&self, transcript: &str, screen_text: Option<String>) -> Context {
+    pub fn build_context(&self, transcript: &TranscriptResult, screen_text: Option<String>) -> Context {
         Context {
-            transcript_window: transcript.to_string(),
-            speaker_context: None, // TODO: Speaker identification
+            transcript_window: transcript.text.clone(),
+            language: transcript.language.clone(),
+            speaker_context: transcript.speaker.clone(),
             screen_context: screen_text,
             meeting_metadata: None, // TODO: Extract from meeting state
+            passages: Vec::new(),
         }
     }
     
-    pub fn route_request(&self, transcript: &str, screen_context: Option<String>) -> Result<RoutingDecision> {
-        let intent = self.classify_intent(transcript);
-        let context = self.build_context(transcript, screen_context);
+    pub async fn route_request(&self, transcript: &TranscriptResult, screen_context: Option<String>) -> Result<RoutingDecision> {
+        let intent = self.classify_intent(&transcript.text);
+        let mut context = self.build_context(transcript, screen_context);
+        context.passages = self.retrieve(&transcript.text).await;
         
         let priority = match intent {
             Intent::ActionItem => 9,
@@ -100,35 +199,293 @@ impl StateIntentRouter {
             Intent::GeneralAssistance => 5,
         };
         
-        let suggested_prompt = self.build_prompt(&intent, &context);
+        let (suggested_prompt, citations) = self.build_prompt(&intent, &context);
         
         Ok(RoutingDecision {
             intent,
             priority,
             context,
             suggested_prompt,
+            citations,
         })
     }
     
-    fn build_prompt(&self, intent: &Intent, context: &Context) -> String {
-        let base_context = format!("Recent conversation: {}", context.transcript_window);
-        
-        match intent {
-            Intent::FollowUpQuestion => {
-                format!("{}\n\nGenerate a relevant follow-up question to deepen understanding.", base_context)
-            },
-            Intent::Definition => {
-                format!("{}\n\nProvide a concise definition or explanation of the key concept mentioned.", base_context)
-            },
-            Intent::ActionItem => {
-                format!("{}\n\nExtract and format any action items or next steps mentioned.", base_context)
-            },
-            Intent::DraftReply => {
-                format!("{}\n\nSuggest a professional response or reply.", base_context)
-            },
-            Intent::GeneralAssistance => {
-                format!("{}\n\nProvide helpful context or suggestions.", base_context)
+    // A suggestion without passages beats no suggestion, so retrieval
+    // failures are only logged
+    async fn retrieve(&self, text: &str) -> Vec<VectorItem> {
+        let Some(store) = &self.store else {
+            return Vec::new();
+        };
+        match store.search(text, &self.retrieval).await {
+            Ok(passages) => passages,
+            Err(e) => {
+                warn!("Passage retrieval failed: {}", e);
+                Vec::new()
             },
         }
     }
+    
+    // The prompt, fitted to the budget: the latest part of the transcript
+    // and the start of the screen context each up to their share, then as
+    // many passages as fit, best first
+    fn build_prompt(&self, intent: &Intent, context: &Context) -> (String, Vec<Citation>) {
+        let transcript = keep_tail(&context.transcript_window, self.budget.transcript_tokens);
+        let base_context = match &context.speaker_context {
+            Some(speaker) => format!("Recent conversation:\n{}: {}", speaker, transcript),
+            None => format!("Recent conversation: {}", transcript),
+        };
+        let screen = context.screen_context.as_deref()
+            .map(str::trim)
+            .filter(|screen| !screen.is_empty())
+            .map(|screen| format!("On screen:\n{}", keep_head(screen, self.budget.screen_tokens)));
+        
+        let task = match intent {
+            Intent::FollowUpQuestion => "Generate a relevant follow-up question to deepen understanding.",
+            Intent::Definition => "Provide a concise definition or explanation of the key concept mentioned.",
+            Intent::ActionItem => "Extract and format any action items or next steps mentioned.",
+            Intent::DraftReply => "Suggest a professional response or reply.",
+            Intent::GeneralAssistance => "Provide helpful context or suggestions.",
+        };
+        let mut instruction = task.to_string();
+        if !context.passages.is_empty() {
+            instruction.push_str(" Use the reference passages where relevant and cite them by number, e.g. [1].");
+        }
+        if let Some(language) = self.language_instruction(&context.language) {
+            instruction = format!("{}\n{}", instruction, language);
+        }
+        
+        let header = "Reference passages:";
+        let used = [Some(&base_context), screen.as_ref(), Some(&instruction)].into_iter()
+            .flatten()
+            .map(|section| estimate_tokens(section))
+            .sum::<usize>()
+            + estimate_tokens(header);
+        let mut remaining = self.budget.max_tokens.saturating_sub(used);
+        
+        let mut passages = Vec::new();
+        let mut citations = Vec::new();
+        for item in &context.passages {
+            let number = citations.len() + 1;
+            let label = format!("[{}] ({}) ", number, source_label(&item.source));
+            let available = remaining.saturating_sub(estimate_tokens(&label));
+            if available < MIN_PASSAGE_TOKENS.min(estimate_tokens(&item.content)) {
+                break;
+            }
+            
+            let content = keep_head(&item.content, available);
+            remaining = remaining.saturating_sub(estimate_tokens(&label) + estimate_tokens(&content));
+            passages.push(format!("{}{}", label, content));
+            citations.push(Citation {
+                number,
+                document_id: item.document_id.clone(),
+                source: item.source.clone(),
+                chunk_index: item.chunk_index,
+                start_offset: item.start_offset,
+                score: item.score.value,
+            });
+        }
+        
+        let mut sections = vec![base_context];
+        if !passages.is_empty() {
+            sections.push(format!("{}\n{}", header, passages.join("\n")));
+        }
+        sections.extend(screen);
+        sections.push(instruction);
+        (sections.join("\n\n"), citations)
+    }
+    
+    // Suggestions should be readable without switching language mid-meeting
+    fn language_instruction(&self, language: &str) -> Option<String> {
+        if language.is_empty() || language.eq_ignore_ascii_case("en") {
+            return None;
+        }
+        
+        let name = language_name(language).unwrap_or(language);
+        let mut chars = name.chars();
+        let name = match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+            None => return None,
+        };
+        Some(format!("The conversation is in {}; respond in {}.", name, name))
+    }
+}
+
+/// Tokens `text` is expected to take, by the byte estimate prompts are budgeted with.
+pub(crate) fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(BYTES_PER_TOKEN)
+}
+
+// The start of `text` within `tokens`, cut at a word
+fn keep_head(text: &str, tokens: usize) -> String {
+    let max_bytes = tokens * BYTES_PER_TOKEN;
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    
+    let mut end = max_bytes.saturating_sub(3);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = text[..end].rfind(char::is_whitespace).unwrap_or(end);
+    format!("{}...", text[..end].trim_end())
+}
+
+// The end of `text` within `tokens`, cut at a word; the latest words of a
+// conversation matter most
+fn keep_tail(text: &str, tokens: usize) -> String {
+    let max_bytes = tokens * BYTES_PER_TOKEN;
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    
+    let mut start = text.len() - max_bytes.saturating_sub(3);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    let start = text[start..].find(char::is_whitespace).map_or(start, |at| start + at);
+    format!("...{}", text[start..].trim_start())
+}
+
+// File name rather than full path; shorter, and all the model needs
+fn source_label(source: &str) -> &str {
+    Path::new(source).file_name().and_then(|name| name.to_str()).unwrap_or(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rag::Score;
+
+    fn passage(document_id: &str, source: &str, content: &str) -> VectorItem {
+        VectorItem {
+            content: content.to_string(),
+            score: Score { value: 0.8, fused: 0.8, ..Score::default() },
+            document_id: document_id.to_string(),
+            source: source.to_string(),
+            chunk_index: 2,
+            start_offset: 640,
+        }
+    }
+
+    // `count` distinct words of 8 bytes each with their separators
+    fn words(prefix: char, count: usize) -> String {
+        (0..count).map(|i| format!("{}{:06}", prefix, i)).collect::<Vec<_>>().join(" ")
+    }
+
+    fn context_for(transcript: &str, passages: Vec<VectorItem>) -> Context {
+        Context {
+            transcript_window: transcript.to_string(),
+            language: "en".to_string(),
+            speaker_context: None,
+            screen_context: None,
+            meeting_metadata: None,
+            passages,
+        }
+    }
+
+    fn router(max_tokens: usize) -> StateIntentRouter {
+        StateIntentRouter::new().with_budget(PromptBudget { max_tokens, ..PromptBudget::default() })
+    }
+
+    #[test]
+    fn passages_are_cited_by_number_in_rank_order() {
+        let context = context_for("How should we price the enterprise tier?", vec![
+            passage("pricing", "/docs/pricing.md", "Enterprise is billed per seat."),
+            passage("faq", "/docs/sales/faq.pdf", "Discounts need VP approval."),
+        ]);
+        let (prompt, citations) = router(1536).build_prompt(&Intent::FollowUpQuestion, &context);
+
+        assert!(prompt.contains("Reference passages:\n[1] (pricing.md) Enterprise is billed per seat.\n[2] (faq.pdf) Discounts need VP approval."));
+        assert!(prompt.contains("cite them by number"));
+        assert!(prompt.starts_with("Recent conversation: How should we price the enterprise tier?"));
+
+        let numbers: Vec<usize> = citations.iter().map(|citation| citation.number).collect();
+        assert_eq!(numbers, vec![1, 2]);
+        assert_eq!(citations[1].document_id, "faq");
+        assert_eq!(citations[1].source, "/docs/sales/faq.pdf");
+        assert_eq!(citations[1].chunk_index, 2);
+        assert_eq!(citations[1].start_offset, 640);
+        assert_eq!(citations[1].score, 0.8);
+    }
+
+    #[test]
+    fn passages_that_overflow_the_budget_are_cut_then_left_out() {
+        let first = words('a', 100);
+        let second = words('b', 100);
+        let third = words('c', 100);
+        let context = context_for("How should we price it?", vec![
+            passage("a", "a.md", &first),
+            passage("b", "b.md", &second),
+            passage("c", "c.md", &third),
+        ]);
+        let (prompt, citations) = router(400).build_prompt(&Intent::FollowUpQuestion, &context);
+
+        // The first passage fits whole, the second is cut short at a word
+        // and the third no longer fits
+        assert_eq!(citations.len(), 2);
+        assert!(prompt.contains(&format!("[1] (a.md) {}\n", first)));
+        let kept = prompt.split("[2] (b.md) ").nth(1).unwrap().split("\n").next().unwrap();
+        let cut = kept.strip_suffix("...").expect("second passage was not cut");
+        assert!(second.starts_with(cut));
+        assert!(cut.ends_with(|c: char| c.is_ascii_digit()));
+        assert!(estimate_tokens(kept) >= MIN_PASSAGE_TOKENS);
+        assert!(!prompt.contains("[3]"));
+        assert!(estimate_tokens(&prompt) <= 400 + 4);
+    }
+
+    #[test]
+    fn passage_is_left_out_rather_than_cut_below_the_minimum() {
+        let context = context_for("How should we price it?", vec![passage("a", "a.md", &words('a', 100))]);
+        let (prompt, citations) = router(90).build_prompt(&Intent::FollowUpQuestion, &context);
+
+        assert!(citations.is_empty());
+        assert!(!prompt.contains("Reference passages:"));
+
+        // A passage shorter than the minimum still fits in what's left
+        let context = context_for("How should we price it?", vec![passage("a", "a.md", "Seats are billed monthly.")]);
+        let (prompt, citations) = router(90).build_prompt(&Intent::FollowUpQuestion, &context);
+        assert_eq!(citations.len(), 1);
+        assert!(prompt.contains("[1] (a.md) Seats are billed monthly."));
+    }
+
+    #[test]
+    fn transcript_keeps_its_latest_words_and_screen_its_first() {
+        let transcript = words('t', 100);
+        let mut context = context_for(&transcript, Vec::new());
+        context.speaker_context = Some("Speaker 2".to_string());
+        context.screen_context = Some(format!("  {}  ", words('s', 100)));
+        let router = StateIntentRouter::new().with_budget(PromptBudget {
+            max_tokens: 1536,
+            transcript_tokens: 40,
+            screen_tokens: 20,
+        });
+        let (prompt, _) = router.build_prompt(&Intent::GeneralAssistance, &context);
+
+        let conversation = prompt.strip_prefix("Recent conversation:\nSpeaker 2: ...").unwrap();
+        let conversation = conversation.split("\n\n").next().unwrap();
+        assert!(transcript.ends_with(conversation));
+        assert!(conversation.ends_with("t000099"));
+        assert!(conversation.len() <= 40 * BYTES_PER_TOKEN);
+
+        let screen = prompt.split("On screen:\n").nth(1).unwrap().split("\n\n").next().unwrap();
+        assert!(screen.starts_with("s000000"));
+        assert!(screen.ends_with("..."));
+        assert!(screen.len() <= 20 * BYTES_PER_TOKEN);
+
+        context.screen_context = Some("   ".to_string());
+        let (prompt, _) = router.build_prompt(&Intent::GeneralAssistance, &context);
+        assert!(!prompt.contains("On screen:"));
+    }
+
+    #[test]
+    fn non_english_conversations_ask_for_a_reply_in_their_language() {
+        let mut context = context_for("Wie sollen wir das bepreisen?", Vec::new());
+        context.language = "de".to_string();
+        let (prompt, _) = router(1536).build_prompt(&Intent::FollowUpQuestion, &context);
+        assert!(prompt.ends_with("The conversation is in German; respond in German."));
+
+        context.language = "en".to_string();
+        let (prompt, _) = router(1536).build_prompt(&Intent::FollowUpQuestion, &context);
+        assert!(!prompt.contains("respond in"));
+    }
 }
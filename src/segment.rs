@@ -0,0 +1,324 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::info;
+use crate::source::SourceRole;
+use crate::vad::AudioChunk;
+
+/// Tuning for how voiced frames are grouped into utterances.
+#[derive(Debug, Clone)]
+pub struct SegmenterConfig {
+    /// Audio kept from before speech onset so the first syllable isn't clipped.
+    pub pre_roll_ms: u32,
+    /// Silence tolerated inside an utterance before it is closed.
+    pub hangover_ms: u32,
+    /// Utterances shorter than this are treated as clicks or noise and dropped.
+    pub min_utterance_ms: u32,
+    /// Long monologues are split at this length so ASR latency stays bounded.
+    pub max_utterance_ms: u32,
+    /// How often the audio of an utterance still in progress is re-emitted for
+    /// partial transcription. Zero disables partials.
+    pub partial_interval_ms: u32,
+}
+
+impl Default for SegmenterConfig {
+    fn default() -> Self {
+        Self {
+            pre_roll_ms: 300,
+            hangover_ms: 600,
+            min_utterance_ms: 250,
+            max_utterance_ms: 15000,
+            partial_interval_ms: 500,
+        }
+    }
+}
+
+struct ActiveUtterance {
+    id: u64,
+    data: Vec<f32>,
+    start_sample: u64,
+    silence_samples: usize,
+    /// Samples in frames classified as voice, excluding pre-roll and hangover.
+    voiced_samples: usize,
+    last_partial_len: usize,
+}
+
+/// Turns a stream of 16 kHz mono frames with per-frame voice decisions into
+/// complete utterances with session-relative timestamps.
+pub struct UtteranceSegmenter {
+    config: SegmenterConfig,
+    sample_rate: u32,
+    session_start: Instant,
+    samples_seen: u64,
+    pre_roll: VecDeque<f32>,
+    active: Option<ActiveUtterance>,
+    ids: Arc<AtomicU64>,
+}
+
+impl UtteranceSegmenter {
+    pub fn new(config: SegmenterConfig, sample_rate: u32) -> Self {
+        Self {
+            config,
+            sample_rate,
+            session_start: Instant::now(),
+            samples_seen: 0,
+            pre_roll: VecDeque::new(),
+            active: None,
+            ids: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    /// Draw utterance IDs from a counter shared with other segmenters, so
+    /// IDs stay unique when several inputs feed the same ASR stage.
+    pub fn with_shared_ids(mut self, ids: Arc<AtomicU64>) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Feed one frame; returns any utterances completed by it, plus partial
+    /// snapshots (`is_final == false`) of the utterance still in progress.
+    pub fn push(&mut self, frame: &[f32], is_voice: bool) -> Vec<AudioChunk> {
+        let mut completed = Vec::new();
+
+        match self.active.as_mut() {
+            Some(active) => {
+                active.data.extend_from_slice(frame);
+                if is_voice {
+                    active.silence_samples = 0;
+                    active.voiced_samples += frame.len();
+                } else {
+                    active.silence_samples += frame.len();
+                }
+            },
+            None if is_voice => {
+                let pre_roll: Vec<f32> = self.pre_roll.drain(..).collect();
+                let start_sample = self.samples_seen - pre_roll.len() as u64;
+                let mut data = pre_roll;
+                data.extend_from_slice(frame);
+
+                self.active = Some(ActiveUtterance {
+                    id: self.allocate_id(),
+                    data,
+                    start_sample,
+                    silence_samples: 0,
+                    voiced_samples: frame.len(),
+                    last_partial_len: 0,
+                });
+            },
+            None => {
+                self.pre_roll.extend(frame.iter().copied());
+                let limit = self.ms_to_samples(self.config.pre_roll_ms);
+                while self.pre_roll.len() > limit {
+                    self.pre_roll.pop_front();
+                }
+            },
+        }
+
+        self.samples_seen += frame.len() as u64;
+
+        let hangover = self.ms_to_samples(self.config.hangover_ms);
+        let max_len = self.ms_to_samples(self.config.max_utterance_ms);
+
+        if let Some(active) = self.active.as_ref() {
+            if active.silence_samples >= hangover {
+                if let Some(chunk) = self.finish_active() {
+                    completed.push(chunk);
+                }
+            } else if active.data.len() >= max_len {
+                info!("Splitting utterance at {} ms", self.config.max_utterance_ms);
+                let still_speaking = active.silence_samples == 0;
+                if let Some(chunk) = self.finish_active() {
+                    completed.push(chunk);
+                }
+                // Keep recording straight into the next segment
+                if still_speaking {
+                    self.active = Some(ActiveUtterance {
+                        id: self.allocate_id(),
+                        data: Vec::new(),
+                        start_sample: self.samples_seen,
+                        silence_samples: 0,
+                        voiced_samples: 0,
+                        last_partial_len: 0,
+                    });
+                }
+            }
+        }
+
+        let partial_interval = self.ms_to_samples(self.config.partial_interval_ms);
+        let emit_partial = match self.active.as_mut() {
+            // Only while speech is ongoing; the hangover tail adds no words
+            Some(active) if partial_interval > 0
+                && active.silence_samples == 0
+                && active.data.len() - active.last_partial_len >= partial_interval => {
+                active.last_partial_len = active.data.len();
+                true
+            },
+            _ => false,
+        };
+        if emit_partial {
+            if let Some(active) = self.active.as_ref() {
+                completed.push(self.snapshot(active, false));
+            }
+        }
+
+        completed
+    }
+
+    /// Close any utterance still open, e.g. when the source ends.
+    pub fn flush(&mut self) -> Option<AudioChunk> {
+        self.finish_active()
+    }
+
+    fn finish_active(&mut self) -> Option<AudioChunk> {
+        let active = self.active.take()?;
+        self.pre_roll.clear();
+
+        // Only voiced frames count towards the minimum; pre-roll and hangover
+        // silence would otherwise let a single click through
+        if active.voiced_samples < self.ms_to_samples(self.config.min_utterance_ms) {
+            return None;
+        }
+
+        Some(self.snapshot(&active, true))
+    }
+
+    fn snapshot(&self, active: &ActiveUtterance, is_final: bool) -> AudioChunk {
+        let start_time = active.start_sample as f32 / self.sample_rate as f32;
+        let end_time = (active.start_sample + active.data.len() as u64) as f32 / self.sample_rate as f32;
+
+        AudioChunk {
+            data: active.data.clone(),
+            sample_rate: self.sample_rate,
+            channels: 1,
+            timestamp: self.session_start + Duration::from_secs_f32(start_time),
+            has_voice: true,
+            start_time,
+            end_time,
+            utterance_id: active.id,
+            is_final,
+            speaker_id: None,
+            source: String::new(),
+            source_role: SourceRole::default(),
+        }
+    }
+
+    fn allocate_id(&mut self) -> u64 {
+        self.ids.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn ms_to_samples(&self, ms: u32) -> usize {
+        (self.sample_rate as u64 * ms as u64 / 1000) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16000;
+    // 20 ms frames, as the VAD produces them
+    const FRAME: usize = 320;
+
+    fn feed(segmenter: &mut UtteranceSegmenter, frames: usize, is_voice: bool) -> Vec<AudioChunk> {
+        let frame = vec![if is_voice { 0.5 } else { 0.0 }; FRAME];
+        (0..frames).flat_map(|_| segmenter.push(&frame, is_voice)).collect()
+    }
+
+    fn finals(chunks: Vec<AudioChunk>) -> Vec<AudioChunk> {
+        chunks.into_iter().filter(|chunk| chunk.is_final).collect()
+    }
+
+    fn segmenter() -> UtteranceSegmenter {
+        let config = SegmenterConfig { partial_interval_ms: 0, ..SegmenterConfig::default() };
+        UtteranceSegmenter::new(config, RATE)
+    }
+
+    #[test]
+    fn utterance_includes_pre_roll_and_hangover() {
+        let mut segmenter = segmenter();
+        assert!(feed(&mut segmenter, 50, false).is_empty());
+        assert!(feed(&mut segmenter, 25, true).is_empty());
+        // 600 ms hangover is 30 frames; the utterance closes on the last one
+        assert!(feed(&mut segmenter, 29, false).is_empty());
+        let chunks = finals(feed(&mut segmenter, 1, false));
+
+        assert_eq!(chunks.len(), 1);
+        let chunk = &chunks[0];
+        // 300 ms pre-roll + 500 ms speech + 600 ms hangover
+        assert_eq!(chunk.data.len(), (15 + 25 + 30) * FRAME);
+        assert!((chunk.start_time - 0.7).abs() < 1e-4);
+        assert!((chunk.end_time - 2.1).abs() < 1e-4);
+        assert!(chunk.data[..15 * FRAME].iter().all(|&s| s == 0.0));
+        assert!(chunk.data[15 * FRAME..40 * FRAME].iter().all(|&s| s == 0.5));
+    }
+
+    #[test]
+    fn pause_shorter_than_hangover_keeps_one_utterance() {
+        let mut segmenter = segmenter();
+        feed(&mut segmenter, 20, true);
+        assert!(feed(&mut segmenter, 20, false).is_empty());
+        feed(&mut segmenter, 20, true);
+        let chunks = finals(feed(&mut segmenter, 30, false));
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].data.len(), (20 + 20 + 20 + 30) * FRAME);
+    }
+
+    #[test]
+    fn short_burst_is_dropped_despite_pre_roll_and_hangover() {
+        let mut segmenter = segmenter();
+        let mut chunks = feed(&mut segmenter, 50, false);
+        chunks.extend(feed(&mut segmenter, 1, true));
+        chunks.extend(feed(&mut segmenter, 50, false));
+        assert!(chunks.is_empty());
+
+        // Bursts just under the 250 ms minimum are dropped too, and flushing
+        // mid-burst doesn't let them through
+        feed(&mut segmenter, 12, true);
+        assert!(finals(feed(&mut segmenter, 30, false)).is_empty());
+        feed(&mut segmenter, 12, true);
+        assert!(segmenter.flush().is_none());
+    }
+
+    #[test]
+    fn burst_at_the_minimum_is_kept() {
+        let mut segmenter = segmenter();
+        feed(&mut segmenter, 13, true);
+        assert_eq!(finals(feed(&mut segmenter, 30, false)).len(), 1);
+    }
+
+    #[test]
+    fn long_speech_is_split_at_max_length() {
+        let config = SegmenterConfig {
+            max_utterance_ms: 1000,
+            partial_interval_ms: 0,
+            ..SegmenterConfig::default()
+        };
+        let mut segmenter = UtteranceSegmenter::new(config, RATE);
+        let mut chunks = finals(feed(&mut segmenter, 120, true));
+        chunks.extend(segmenter.flush());
+
+        let lengths: Vec<usize> = chunks.iter().map(|chunk| chunk.data.len()).collect();
+        assert_eq!(lengths, vec![50 * FRAME, 50 * FRAME, 20 * FRAME]);
+        // Segments follow on without gaps and get fresh IDs
+        assert!((chunks[1].start_time - chunks[0].end_time).abs() < 1e-4);
+        assert!((chunks[2].start_time - chunks[1].end_time).abs() < 1e-4);
+        assert!(chunks[0].utterance_id < chunks[1].utterance_id);
+        assert!(chunks[1].utterance_id < chunks[2].utterance_id);
+    }
+
+    #[test]
+    fn partials_share_the_utterance_id_of_the_final() {
+        let mut segmenter = UtteranceSegmenter::new(SegmenterConfig::default(), RATE);
+        let mut chunks = feed(&mut segmenter, 60, true);
+        chunks.extend(feed(&mut segmenter, 30, false));
+
+        let partials: Vec<&AudioChunk> = chunks.iter().filter(|chunk| !chunk.is_final).collect();
+        let finals: Vec<&AudioChunk> = chunks.iter().filter(|chunk| chunk.is_final).collect();
+        // One partial every 500 ms of speech, none during the hangover
+        assert_eq!(partials.len(), 2);
+        assert_eq!(finals.len(), 1);
+        assert!(partials.iter().all(|chunk| chunk.utterance_id == finals[0].utterance_id));
+    }
+}
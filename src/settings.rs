@@ -0,0 +1,81 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use crate::storage::RetentionSettings;
+
+/// Choices made in the HUD that should survive a restart, stored as JSON in
+/// the app config directory.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Microphone to capture from, matched like `MIC_DEVICE`; the system
+    /// default when unset.
+    pub input_device: Option<String>,
+    /// How long meeting data is kept before the sweeper deletes it.
+    pub retention: RetentionSettings,
+    /// Folders whose documents are indexed for suggestions.
+    pub document_folders: Vec<PathBuf>,
+}
+
+impl Settings {
+    /// Read settings from `path`; a missing file gives the defaults.
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(anyhow::anyhow!("Failed to read settings from {}: {}", path.display(), e)),
+        };
+
+        serde_json::from_str(&json)
+            .map_err(|e| anyhow::anyhow!("Invalid settings in {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("settings-{}-{}.json", name, uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn missing_file_gives_defaults() {
+        let settings = Settings::load(&settings_path("missing")).unwrap();
+        assert_eq!(settings.input_device, None);
+        assert_eq!(settings.retention, RetentionSettings::default());
+    }
+
+    #[test]
+    fn invalid_file_is_an_error() {
+        let path = settings_path("invalid");
+        std::fs::write(&path, r#"{"retention": {"default": {"transcript_days": "soon"}}}"#).unwrap();
+        let result = Settings::load(&path);
+        let _ = std::fs::remove_file(&path);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn saved_settings_load_back() {
+        let path = settings_path("saved");
+        let mut settings = Settings {
+            input_device: Some("USB Microphone".to_string()),
+            ..Settings::default()
+        };
+        settings.retention.default.transcript_days = Some(7);
+        settings.save(&path).unwrap();
+
+        let loaded = Settings::load(&path);
+        let _ = std::fs::remove_file(&path);
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.input_device.as_deref(), Some("USB Microphone"));
+        assert_eq!(loaded.retention, settings.retention);
+    }
+}
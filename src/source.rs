@@ -0,0 +1,438 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{SampleFormat, StreamConfig};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{info, warn, error};
+use crate::vad::AudioChunk;
+
+/// A producer of raw audio frames for the VAD stage.
+///
+/// Sources run on their own thread and push frames into `tx` until they are
+/// stopped or run out of audio, at which point they drop the sender so the
+/// consumer sees the end of the stream.
+pub trait AudioSource: Send {
+    fn describe(&self) -> String;
+
+    fn start(&mut self, tx: UnboundedSender<AudioChunk>) -> Result<()>;
+
+    fn stop(&mut self);
+}
+
+/// Whose speech an input carries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceRole {
+    /// The user's own microphone.
+    Me,
+    /// Loopback of the call audio, i.e. the other participants.
+    Them,
+    /// Can't tell, e.g. a single microphone hearing the whole room.
+    #[default]
+    Mixed,
+}
+
+impl SourceRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceRole::Me => "me",
+            SourceRole::Them => "them",
+            SourceRole::Mixed => "mixed",
+        }
+    }
+
+    /// Inverse of `as_str`; anything unrecognised is `Mixed`.
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "me" => SourceRole::Me,
+            "them" => SourceRole::Them,
+            _ => SourceRole::Mixed,
+        }
+    }
+}
+
+/// A labelled source for the pipeline.
+pub struct AudioInput {
+    pub name: String,
+    pub role: SourceRole,
+    pub source: Box<dyn AudioSource>,
+}
+
+impl AudioInput {
+    pub fn new(name: &str, role: SourceRole, source: Box<dyn AudioSource>) -> Self {
+        Self {
+            name: name.to_string(),
+            role,
+            source,
+        }
+    }
+}
+
+/// How often a live input checks whether its device went away or the system
+/// default changed.
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Live capture from a cpal input device, the default one unless named.
+///
+/// The stream is rebuilt when the device disconnects, and follows the system
+/// default when no device is named, so unplugging a headset mid-meeting
+/// pauses capture instead of ending it.
+pub struct MicrophoneSource {
+    frame_size: usize,
+    device_name: Option<String>,
+    stop_tx: Option<mpsc::Sender<()>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl MicrophoneSource {
+    pub fn new(frame_size: usize) -> Self {
+        Self {
+            frame_size,
+            device_name: None,
+            stop_tx: None,
+            worker: None,
+        }
+    }
+
+    /// Capture from the first input device whose name contains `name`, e.g.
+    /// "Monitor of" for a PulseAudio/PipeWire loopback of the speakers.
+    pub fn with_device(mut self, name: &str) -> Self {
+        self.device_name = Some(name.to_string());
+        self
+    }
+}
+
+impl AudioSource for MicrophoneSource {
+    fn describe(&self) -> String {
+        let name = find_input_device(self.device_name.as_deref())
+            .ok()
+            .and_then(|device| device.name().ok())
+            .unwrap_or("Unknown".to_string());
+        format!("Microphone: {}", name)
+    }
+
+    fn start(&mut self, tx: UnboundedSender<AudioChunk>) -> Result<()> {
+        let frame_size = self.frame_size;
+        let device_name = self.device_name.clone();
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let (ready_tx, ready_rx) = mpsc::channel::<Result<()>>();
+
+        // cpal streams are not `Send`, so the stream is built and kept alive
+        // on a dedicated thread until `stop` is called.
+        let worker = std::thread::spawn(move || {
+            let mut ready_tx = Some(ready_tx);
+            loop {
+                let device_lost = Arc::new(AtomicBool::new(false));
+                let started = build_input_stream(frame_size, device_name.as_deref(), tx.clone(), device_lost.clone())
+                    .and_then(|(stream, name)| {
+                        stream.play()?;
+                        Ok((stream, name))
+                    });
+
+                let (stream, current_device) = match started {
+                    Ok(started) => started,
+                    Err(e) => {
+                        // Failing to open the device at all is the caller's
+                        // problem; losing it later is retried until it returns
+                        if let Some(ready_tx) = ready_tx.take() {
+                            let _ = ready_tx.send(Err(e));
+                            return;
+                        }
+                        warn!("Audio input unavailable, retrying: {}", e);
+                        match stop_rx.recv_timeout(DEVICE_POLL_INTERVAL) {
+                            Err(RecvTimeoutError::Timeout) => continue,
+                            _ => return,
+                        }
+                    }
+                };
+
+                if let Some(ready_tx) = ready_tx.take() {
+                    let _ = ready_tx.send(Ok(()));
+                }
+
+                loop {
+                    match stop_rx.recv_timeout(DEVICE_POLL_INTERVAL) {
+                        Err(RecvTimeoutError::Timeout) => {},
+                        _ => return,
+                    }
+
+                    if device_lost.load(Ordering::SeqCst) {
+                        warn!("Audio device disconnected: {}, reconnecting...", current_device);
+                        break;
+                    }
+
+                    if device_name.is_none() {
+                        let default_device = find_input_device(None).ok().and_then(|device| device.name().ok());
+                        if let Some(default_device) = default_device.filter(|name| *name != current_device) {
+                            info!("Default input device changed to: {}", default_device);
+                            break;
+                        }
+                    }
+                }
+                drop(stream);
+            }
+        });
+
+        ready_rx.recv()
+            .map_err(|_| anyhow::anyhow!("Microphone thread exited unexpectedly"))??;
+
+        self.stop_tx = Some(stop_tx);
+        self.worker = Some(worker);
+        info!("Microphone source started");
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.stop_tx = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// The first input device whose name contains `name`, ignoring case, or the
+/// default input device when `None`.
+pub fn find_input_device(name: Option<&str>) -> Result<cpal::Device> {
+    let host = cpal::default_host();
+    let Some(name) = name else {
+        return host.default_input_device()
+            .ok_or_else(|| anyhow::anyhow!("No input device available"));
+    };
+
+    let wanted = name.to_lowercase();
+    host.input_devices()?
+        .find(|device| device.name().is_ok_and(|device_name| device_name.to_lowercase().contains(&wanted)))
+        .ok_or_else(|| anyhow::anyhow!("No input device matching: {}", name))
+}
+
+// Returns the stream along with the name of the device it captures from.
+// `device_lost` is raised once the device stops being available.
+fn build_input_stream(frame_size: usize, device_name: Option<&str>, tx: UnboundedSender<AudioChunk>, device_lost: Arc<AtomicBool>) -> Result<(cpal::Stream, String)> {
+    let device = find_input_device(device_name)?;
+    let name = device.name().unwrap_or("Unknown".to_string());
+
+    info!("Audio device: {}", name);
+
+    let supported_configs = device.supported_input_configs()?;
+    info!("Supported audio configs: {:?}", supported_configs.collect::<Vec<_>>());
+
+    let config = device.default_input_config()?;
+    let sample_format = config.sample_format();
+    let channels = config.channels();
+    let sample_rate = config.sample_rate().0;
+
+    info!("Audio config - Sample rate: {}, Channels: {}, Format: {:?}",
+          sample_rate, channels, sample_format);
+
+    // Capture at the device's native format; the VAD converts to 16 kHz mono
+    let config: StreamConfig = StreamConfig {
+        channels,
+        sample_rate: config.sample_rate(),
+        buffer_size: cpal::BufferSize::Fixed(frame_size as u32),
+    };
+
+    let on_error = move |err: cpal::StreamError| {
+        error!("Audio input error: {}", err);
+        if matches!(err, cpal::StreamError::DeviceNotAvailable) {
+            device_lost.store(true, Ordering::SeqCst);
+        }
+    };
+
+    let stream = match sample_format {
+        SampleFormat::F32 => {
+            device.build_input_stream(
+                &config,
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    send_frame(&tx, data.to_vec(), sample_rate, channels, Instant::now());
+                },
+                on_error,
+                None,
+            )?
+        },
+        SampleFormat::I16 => {
+            device.build_input_stream(
+                &config,
+                move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                    let f32_data: Vec<f32> = data.iter()
+                        .map(|&sample| sample as f32 / i16::MAX as f32)
+                        .collect();
+                    send_frame(&tx, f32_data, sample_rate, channels, Instant::now());
+                },
+                on_error,
+                None,
+            )?
+        },
+        _ => return Err(anyhow::anyhow!("Unsupported sample format: {:?}", sample_format)),
+    };
+
+    Ok((stream, name))
+}
+
+/// Replays a recorded WAV or FLAC file as if it were a live input.
+///
+/// `speed` controls pacing: `1.0` plays in real time, `4.0` four times
+/// faster, and `0.0` pushes frames as fast as the consumer accepts them.
+/// A replay that is stopped part-way resumes from the same frame when
+/// started again; a finished one starts over.
+pub struct FileSource {
+    path: PathBuf,
+    frame_size: usize,
+    speed: f32,
+    stop_flag: Arc<AtomicBool>,
+    next_frame: Arc<AtomicUsize>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl FileSource {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            frame_size: 1024,
+            speed: 1.0,
+            stop_flag: Arc::new(AtomicBool::new(false)),
+            next_frame: Arc::new(AtomicUsize::new(0)),
+            worker: None,
+        }
+    }
+
+    pub fn with_frame_size(mut self, frame_size: usize) -> Self {
+        self.frame_size = frame_size.max(1);
+        self
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed.max(0.0);
+        self
+    }
+}
+
+impl AudioSource for FileSource {
+    fn describe(&self) -> String {
+        format!("File: {} ({}x)", self.path.display(), self.speed)
+    }
+
+    fn start(&mut self, tx: UnboundedSender<AudioChunk>) -> Result<()> {
+        let (samples, sample_rate, channels) = read_audio_file(&self.path)?;
+        info!("Replaying {} ({} Hz, {} channels)", self.path.display(), sample_rate, channels);
+
+        let frame_size = self.frame_size;
+        let frame_len = frame_size * channels as usize;
+        let speed = self.speed;
+        let stop_flag = self.stop_flag.clone();
+        stop_flag.store(false, Ordering::SeqCst);
+        let next_frame = self.next_frame.clone();
+        let first_frame = next_frame.load(Ordering::SeqCst);
+
+        let worker = std::thread::spawn(move || {
+            let started = Instant::now();
+            let frame_duration = Duration::from_secs_f64(frame_size as f64 / sample_rate as f64);
+
+            for (index, frame) in samples.chunks(frame_len).enumerate().skip(first_frame) {
+                if stop_flag.load(Ordering::SeqCst) {
+                    return;
+                }
+
+                let offset = frame_duration * (index - first_frame) as u32;
+                if speed > 0.0 {
+                    let due = started + offset.div_f32(speed);
+                    let now = Instant::now();
+                    if due > now {
+                        std::thread::sleep(due - now);
+                    }
+                }
+
+                // Timestamps follow the file timeline rather than the wall
+                // clock so accelerated replays keep their original spacing.
+                if !send_frame(&tx, frame.to_vec(), sample_rate, channels, started + offset) {
+                    return;
+                }
+                next_frame.store(index + 1, Ordering::SeqCst);
+            }
+
+            next_frame.store(0, Ordering::SeqCst);
+            info!("File replay finished");
+        });
+
+        self.worker = Some(worker);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.stop_flag.store(true, Ordering::SeqCst);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Decode a WAV or FLAC file into interleaved f32 samples, its sample rate
+/// and channel count.
+pub fn read_audio_file(path: &Path) -> Result<(Vec<f32>, u32, u16)> {
+    let extension = path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "wav" => read_wav(path),
+        "flac" => read_flac(path),
+        _ => Err(anyhow::anyhow!("Unsupported audio file format: {}", path.display())),
+    }
+}
+
+fn read_wav(path: &Path) -> Result<(Vec<f32>, u32, u16)> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader.samples::<i32>()
+                .map(|sample| sample.map(|s| s as f32 / scale))
+                .collect::<Result<_, _>>()?
+        },
+    };
+
+    Ok((samples, spec.sample_rate, spec.channels))
+}
+
+fn read_flac(path: &Path) -> Result<(Vec<f32>, u32, u16)> {
+    let mut reader = claxon::FlacReader::open(path)?;
+    let info = reader.streaminfo();
+    let scale = (1i64 << (info.bits_per_sample - 1)) as f32;
+
+    let samples: Vec<f32> = reader.samples()
+        .map(|sample| sample.map(|s| s as f32 / scale))
+        .collect::<Result<_, _>>()?;
+
+    Ok((samples, info.sample_rate, info.channels as u16))
+}
+
+fn send_frame(tx: &UnboundedSender<AudioChunk>, data: Vec<f32>, sample_rate: u32, channels: u16, timestamp: Instant) -> bool {
+    let chunk = AudioChunk {
+        data,
+        sample_rate,
+        channels,
+        timestamp,
+        has_voice: false,
+        start_time: 0.0,
+        end_time: 0.0,
+        utterance_id: 0,
+        is_final: false,
+        speaker_id: None,
+        source: String::new(),
+        source_role: SourceRole::default(),
+    };
+
+    if tx.send(chunk).is_err() {
+        warn!("Audio consumer dropped, stopping source");
+        return false;
+    }
+    true
+}
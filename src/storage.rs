@@ -1,13 +1,1323 @@
 use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::{ConnectOptions, Connection, QueryBuilder, Row, Sqlite};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{info, warn, error};
+use crate::asr::{TranscriptResult, WordTiming};
+use crate::keys::{DatabaseKey, KeySource};
+use crate::source::SourceRole;
 
-/// Lightweight wrapper around persistent storage.
-pub struct Database;
+/// Schema migrations, embedded at build time from `migrations/`.
+static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations");
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Meeting {
+    pub id: String,
+    pub title: String,
+    pub started_at: DateTime<Utc>,
+    /// `None` while the meeting is still being recorded.
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Free-form kind of meeting, e.g. "standup" or "sales call".
+    pub meeting_type: String,
+    pub participants: Vec<String>,
+    /// Post-call summary, if one was saved.
+    pub summary: Option<String>,
+}
+
+/// A final transcript as stored for a meeting.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TranscriptChunk {
+    pub id: i64,
+    pub meeting_id: String,
+    pub utterance_id: u64,
+    /// Seconds since listening started.
+    pub start_time: f32,
+    pub end_time: f32,
+    pub text: String,
+    pub speaker: Option<String>,
+    pub source: String,
+    pub source_role: SourceRole,
+    pub language: String,
+    pub confidence: f32,
+    pub words: Vec<WordTiming>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Narrows a transcript search; empty fields match everything.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct SearchFilter {
+    /// Only meetings that started at or after this time.
+    pub from: Option<DateTime<Utc>>,
+    /// Only meetings that started before this time.
+    pub to: Option<DateTime<Utc>>,
+    /// Only meetings attended by at least one of these people, ignoring case.
+    pub participants: Vec<String>,
+    pub meeting_type: Option<String>,
+}
+
+/// A transcript chunk matching a search, best matches first.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SearchHit {
+    pub chunk_id: i64,
+    pub meeting_id: String,
+    pub meeting_title: String,
+    pub meeting_started_at: DateTime<Utc>,
+    /// When the words were spoken, from the meeting start and `start_time`.
+    pub spoken_at: DateTime<Utc>,
+    pub start_time: f32,
+    pub end_time: f32,
+    pub speaker: Option<String>,
+    pub text: String,
+    /// Excerpt around the match with matched terms wrapped in `<mark>`.
+    pub snippet: String,
+    /// BM25 relevance; higher is better.
+    pub score: f32,
+}
+
+/// Words of context either side of a match in `SearchHit::snippet`.
+const SNIPPET_TOKENS: u32 = 12;
+
+// Meeting columns plus participants as a JSON array
+const MEETING_COLUMNS: &str = "id, title, started_at, ended_at, meeting_type, summary,
+    (SELECT json_group_array(name) FROM meeting_participants p WHERE p.meeting_id = meetings.id) AS participants";
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Suggestion {
+    pub id: String,
+    pub meeting_id: String,
+    pub suggestion_type: String,
+    pub content: String,
+    pub confidence: f32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ActionItem {
+    pub id: i64,
+    pub meeting_id: String,
+    pub text: String,
+    pub owner: Option<String>,
+    pub done: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How many days each kind of meeting data is kept, counted from when the
+/// meeting started. `None`, the default, keeps it until the meeting is
+/// forgotten.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    pub transcript_days: Option<u32>,
+    pub suggestion_days: Option<u32>,
+    pub action_item_days: Option<u32>,
+    /// Forget the whole meeting, summary and action items included.
+    pub meeting_days: Option<u32>,
+}
+
+/// Retention per meeting type, with a policy for meetings of any other type.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RetentionSettings {
+    pub default: RetentionPolicy,
+    /// Keyed by meeting type, matched ignoring case.
+    pub meeting_types: HashMap<String, RetentionPolicy>,
+}
+
+impl RetentionSettings {
+    pub fn policy(&self, meeting_type: &str) -> &RetentionPolicy {
+        self.meeting_types.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(meeting_type))
+            .map_or(&self.default, |(_, policy)| policy)
+    }
+}
+
+/// What a retention sweep deleted.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct RetentionReport {
+    pub meetings: u64,
+    pub transcript_chunks: u64,
+    /// Vector store chunks made from expired transcripts.
+    pub rag_chunks: u64,
+    pub suggestions: u64,
+    pub action_items: u64,
+}
+
+impl RetentionReport {
+    pub fn is_empty(&self) -> bool {
+        self.meetings + self.transcript_chunks + self.rag_chunks + self.suggestions + self.action_items == 0
+    }
+}
+
+/// SQLite store for meetings and everything recorded during them. Cheap to
+/// clone; clones share the connection pool.
+#[derive(Clone)]
+pub struct Database {
+    pool: SqlitePool,
+}
 
 impl Database {
-    /// Create a new database at the provided path.
-    pub fn new(_path: &str) -> Result<Self> {
-        // In a full implementation, this would initialize a connection using
-        // `sqlx` and run any pending migrations.
-        Ok(Self)
+    /// Open the encrypted database at `path` with the key from `keys`,
+    /// creating both if needed, and apply any pending migrations.
+    ///
+    /// A database written before encryption is encrypted in place, and a key
+    /// rotation cut short is finished. A wrong or missing key is an error;
+    /// nothing is read from or written to the file without the right one.
+    pub async fn new(path: &str, keys: &KeySource) -> Result<Self> {
+        if let Some(parent) = Path::new(path).parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Only a database that isn't encrypted yet may get a new key
+        let exists = Path::new(path).exists();
+        let plaintext = exists && is_plaintext(Path::new(path))?;
+        let key = keys.load(!exists || plaintext)?;
+        if plaintext {
+            encrypt_plaintext(path, &key).await
+                .map_err(|e| anyhow::anyhow!("Failed to encrypt existing database {}: {}", path, e))?;
+        }
+
+        let pool = match Self::connect(path, &key).await {
+            Ok(pool) => {
+                // The database never switched to a staged key
+                keys.discard()?;
+                pool
+            },
+            Err(e) if is_wrong_key(&e) => {
+                let Some(pending) = keys.pending()? else {
+                    return Err(open_error(path, e));
+                };
+                let pool = Self::connect(path, &pending).await.map_err(|e| open_error(path, e))?;
+                keys.commit()?;
+                info!("Finished interrupted database key rotation");
+                pool
+            },
+            Err(e) => return Err(open_error(path, e)),
+        };
+
+        info!("Opened encrypted database: {}", path);
+        Self::migrate(pool).await
+    }
+
+    async fn connect(path: &str, key: &DatabaseKey) -> Result<SqlitePool, sqlx::Error> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .pragma("key", key.pragma())
+            // Zero deleted content instead of leaving it in free pages
+            .pragma("secure_delete", "ON")
+            .create_if_missing(true)
+            .foreign_keys(true)
+            .journal_mode(SqliteJournalMode::Wal);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(4)
+            .connect_with(options)
+            .await?;
+
+        // SQLite without SQLCipher ignores the key and would write clear text
+        if sqlx::query("PRAGMA cipher_version").fetch_optional(&pool).await?.is_none() {
+            return Err(sqlx::Error::Configuration("SQLite was built without SQLCipher".into()));
+        }
+        Ok(pool)
+    }
+
+    /// A private database that lives as long as this handle, e.g. for tests.
+    pub async fn in_memory() -> Result<Self> {
+        // Each connection to `:memory:` is a separate database, so the pool
+        // must hold on to exactly one
+        let options = SqliteConnectOptions::from_str("sqlite::memory:")?
+            .foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await?;
+
+        Self::migrate(pool).await
+    }
+
+    /// Connections for other stores kept in the same database.
+    pub(crate) fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    async fn migrate(pool: SqlitePool) -> Result<Self> {
+        MIGRATOR.run(&pool).await
+            .map_err(|e| anyhow::anyhow!("Database migration failed: {}", e))?;
+        Ok(Self { pool })
+    }
+
+    /// Re-encrypt the database with `key`, which `keys` then holds. Queries
+    /// wait until the rotation is done.
+    ///
+    /// The new key is staged before the database changes, so if the app
+    /// stops half-way the next start opens it with whichever key it has.
+    pub async fn rotate_key(&self, keys: &KeySource, key: DatabaseKey) -> Result<()> {
+        // Hold every connection, so none is mid-query during the rekey and
+        // none is left behind with the old key
+        let mut connections = Vec::new();
+        for _ in 0..self.pool.options().get_max_connections() {
+            connections.push(self.pool.acquire().await?);
+        }
+
+        keys.discard()?;
+        keys.stage(&key)?;
+        if let Err(e) = sqlx::query(&format!("PRAGMA rekey = {}", key.pragma())).execute(&mut *connections[0]).await {
+            keys.discard()?;
+            return Err(anyhow::anyhow!("Failed to rotate database key: {}", e));
+        }
+
+        let options = (*self.pool.connect_options()).clone().pragma("key", key.pragma());
+        self.pool.set_connect_options(options);
+        for connection in connections.drain(1..) {
+            connection.close().await?;
+        }
+        drop(connections);
+
+        keys.commit()?;
+        info!("Rotated database key");
+        Ok(())
+    }
+
+    pub async fn create_meeting(&self, title: &str) -> Result<Meeting> {
+        let meeting = Meeting {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: title.to_string(),
+            started_at: Utc::now(),
+            ended_at: None,
+            meeting_type: String::new(),
+            participants: Vec::new(),
+            summary: None,
+        };
+
+        sqlx::query("INSERT INTO meetings (id, title, started_at) VALUES (?, ?, ?)")
+            .bind(&meeting.id)
+            .bind(&meeting.title)
+            .bind(timestamp(meeting.started_at))
+            .execute(&self.pool)
+            .await?;
+
+        info!("Meeting started: {}", meeting.id);
+        Ok(meeting)
+    }
+
+    pub async fn end_meeting(&self, meeting_id: &str) -> Result<()> {
+        let result = sqlx::query("UPDATE meetings SET ended_at = ? WHERE id = ? AND ended_at IS NULL")
+            .bind(timestamp(Utc::now()))
+            .bind(meeting_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            warn!("Meeting {} was not running", meeting_id);
+        }
+        Ok(())
+    }
+
+    /// Record what kind of meeting this is and who attended, replacing any
+    /// earlier participants.
+    pub async fn set_meeting_details(&self, meeting_id: &str, meeting_type: &str, participants: &[String]) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+
+        let result = sqlx::query("UPDATE meetings SET meeting_type = ? WHERE id = ?")
+            .bind(meeting_type)
+            .bind(meeting_id)
+            .execute(&mut *transaction)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("No meeting with id {}", meeting_id));
+        }
+
+        sqlx::query("DELETE FROM meeting_participants WHERE meeting_id = ?")
+            .bind(meeting_id)
+            .execute(&mut *transaction)
+            .await?;
+        for name in participants.iter().map(|name| name.trim()).filter(|name| !name.is_empty()) {
+            sqlx::query("INSERT OR IGNORE INTO meeting_participants (meeting_id, name) VALUES (?, ?)")
+                .bind(meeting_id)
+                .bind(name)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn meeting(&self, meeting_id: &str) -> Result<Option<Meeting>> {
+        let row = sqlx::query(&format!("SELECT {} FROM meetings WHERE id = ?", MEETING_COLUMNS))
+            .bind(meeting_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.as_ref().map(meeting_from_row).transpose()
+    }
+
+    /// Every meeting, most recent first.
+    pub async fn meetings(&self) -> Result<Vec<Meeting>> {
+        let rows = sqlx::query(&format!("SELECT {} FROM meetings ORDER BY started_at DESC", MEETING_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(meeting_from_row).collect()
+    }
+
+    pub async fn save_summary(&self, meeting_id: &str, summary: &str) -> Result<()> {
+        let result = sqlx::query("UPDATE meetings SET summary = ? WHERE id = ?")
+            .bind(summary)
+            .bind(meeting_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("No meeting with id {}", meeting_id));
+        }
+        Ok(())
+    }
+
+    /// Delete a meeting and everything recorded for or derived from it.
+    pub async fn forget_meeting(&self, meeting_id: &str) -> Result<()> {
+        // Transcripts, suggestions, action items, participants and indexed
+        // chunks cascade, and the search index follows the transcripts
+        // through its triggers
+        let result = sqlx::query("DELETE FROM meetings WHERE id = ?")
+            .bind(meeting_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("No meeting with id {}", meeting_id));
+        }
+
+        self.scrub().await?;
+        info!("Forgot meeting {}", meeting_id);
+        Ok(())
+    }
+
+    /// Delete whatever has outlived its retention policy. `active_meeting`,
+    /// the one being recorded, is left alone.
+    pub async fn apply_retention(&self, retention: &RetentionSettings, active_meeting: Option<&str>) -> Result<RetentionReport> {
+        let now = Utc::now();
+        let expired = |started_at: DateTime<Utc>, days: Option<u32>| {
+            days.is_some_and(|days| started_at + chrono::Duration::days(days as i64) <= now)
+        };
+
+        let rows = sqlx::query("SELECT id, meeting_type, started_at FROM meetings")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut report = RetentionReport::default();
+        let mut transaction = self.pool.begin().await?;
+        for row in &rows {
+            let meeting_id: String = row.try_get("id")?;
+            if active_meeting == Some(meeting_id.as_str()) {
+                continue;
+            }
+            let meeting_type: &str = row.try_get("meeting_type")?;
+            let policy = retention.policy(meeting_type);
+            let started_at = parse_time(row.try_get("started_at")?)?;
+
+            if expired(started_at, policy.meeting_days) {
+                report.meetings += sqlx::query("DELETE FROM meetings WHERE id = ?")
+                    .bind(&meeting_id)
+                    .execute(&mut *transaction)
+                    .await?
+                    .rows_affected();
+                continue;
+            }
+            if expired(started_at, policy.transcript_days) {
+                report.transcript_chunks += sqlx::query("DELETE FROM transcript_chunks WHERE meeting_id = ?")
+                    .bind(&meeting_id)
+                    .execute(&mut *transaction)
+                    .await?
+                    .rows_affected();
+                // Retrievable chunks made from the meeting would keep what
+                // was said alive
+                report.rag_chunks += sqlx::query("DELETE FROM rag_chunks WHERE meeting_id = ?")
+                    .bind(&meeting_id)
+                    .execute(&mut *transaction)
+                    .await?
+                    .rows_affected();
+            }
+            if expired(started_at, policy.suggestion_days) {
+                report.suggestions += sqlx::query("DELETE FROM suggestions WHERE meeting_id = ?")
+                    .bind(&meeting_id)
+                    .execute(&mut *transaction)
+                    .await?
+                    .rows_affected();
+            }
+            if expired(started_at, policy.action_item_days) {
+                report.action_items += sqlx::query("DELETE FROM action_items WHERE meeting_id = ?")
+                    .bind(&meeting_id)
+                    .execute(&mut *transaction)
+                    .await?
+                    .rows_affected();
+            }
+        }
+        transaction.commit().await?;
+
+        if !report.is_empty() {
+            self.scrub().await?;
+            info!("Retention removed {:?}", report);
+        }
+        Ok(report)
+    }
+
+    // Deleted text lingers in the search index until its segments are
+    // merged, and in the WAL until it is checkpointed
+    async fn scrub(&self) -> Result<()> {
+        sqlx::query("INSERT INTO transcript_search (transcript_search) VALUES ('optimize')")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("INSERT INTO rag_search (rag_search) VALUES ('optimize')")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Store a final transcript; partials are rejected since they are
+    /// superseded by the final result for the same utterance.
+    pub async fn append_transcript(&self, meeting_id: &str, transcript: &TranscriptResult) -> Result<i64> {
+        if !transcript.is_final {
+            return Err(anyhow::anyhow!("Only final transcripts are stored"));
+        }
+
+        let result = sqlx::query(
+            "INSERT INTO transcript_chunks
+                (meeting_id, utterance_id, start_time, end_time, text, speaker, source,
+                 source_role, language, confidence, words, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
+            .bind(meeting_id)
+            .bind(transcript.utterance_id as i64)
+            .bind(transcript.start_time)
+            .bind(transcript.end_time)
+            .bind(&transcript.text)
+            .bind(&transcript.speaker)
+            .bind(&transcript.source)
+            .bind(transcript.source_role.as_str())
+            .bind(&transcript.language)
+            .bind(transcript.confidence)
+            .bind(serde_json::to_string(&transcript.words)?)
+            .bind(timestamp(Utc::now()))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// A meeting's transcript in spoken order.
+    pub async fn transcript(&self, meeting_id: &str) -> Result<Vec<TranscriptChunk>> {
+        let rows = sqlx::query(
+            "SELECT id, meeting_id, utterance_id, start_time, end_time, text, speaker, source,
+                    source_role, language, confidence, words, created_at
+             FROM transcript_chunks WHERE meeting_id = ? ORDER BY start_time, id")
+            .bind(meeting_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(transcript_chunk_from_row).collect()
+    }
+
+    /// Full-text search across every meeting's transcript. `query` is plain
+    /// text: all words must match, the last one as a prefix so results can
+    /// follow typing.
+    pub async fn search_transcripts(&self, query: &str, filter: &SearchFilter, limit: u32) -> Result<Vec<SearchHit>> {
+        let Some(match_expression) = match_expression(query) else {
+            return Ok(Vec::new());
+        };
+
+        let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new(format!(
+            "SELECT c.id, c.meeting_id, m.title, m.started_at, c.start_time, c.end_time, c.speaker, c.text,
+                    snippet(transcript_search, 0, '<mark>', '</mark>', '…', {}) AS snippet,
+                    bm25(transcript_search) AS rank
+             FROM transcript_search
+             JOIN transcript_chunks c ON c.id = transcript_search.rowid
+             JOIN meetings m ON m.id = c.meeting_id
+             WHERE transcript_search MATCH ", SNIPPET_TOKENS));
+        builder.push_bind(match_expression);
+
+        if let Some(from) = filter.from {
+            builder.push(" AND m.started_at >= ").push_bind(timestamp(from));
+        }
+        if let Some(to) = filter.to {
+            builder.push(" AND m.started_at < ").push_bind(timestamp(to));
+        }
+        if let Some(meeting_type) = filter.meeting_type.as_deref().filter(|meeting_type| !meeting_type.is_empty()) {
+            builder.push(" AND m.meeting_type = ").push_bind(meeting_type.to_string()).push(" COLLATE NOCASE");
+        }
+        if !filter.participants.is_empty() {
+            builder.push(" AND EXISTS (SELECT 1 FROM meeting_participants p WHERE p.meeting_id = m.id AND p.name IN (");
+            let mut names = builder.separated(", ");
+            for name in &filter.participants {
+                names.push_bind(name.trim().to_string());
+            }
+            builder.push("))");
+        }
+
+        builder.push(" ORDER BY rank LIMIT ").push_bind(limit as i64);
+
+        let rows = builder.build().fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| {
+                let meeting_started_at = parse_time(row.try_get("started_at")?)?;
+                let start_time: f32 = row.try_get("start_time")?;
+                let rank: f64 = row.try_get("rank")?;
+                Ok(SearchHit {
+                    chunk_id: row.try_get("id")?,
+                    meeting_id: row.try_get("meeting_id")?,
+                    meeting_title: row.try_get("title")?,
+                    spoken_at: meeting_started_at + chrono::Duration::milliseconds((start_time * 1000.0) as i64),
+                    meeting_started_at,
+                    start_time,
+                    end_time: row.try_get("end_time")?,
+                    speaker: row.try_get("speaker")?,
+                    text: row.try_get("text")?,
+                    snippet: row.try_get("snippet")?,
+                    // FTS5 ranks better matches more negative
+                    score: -rank as f32,
+                })
+            })
+            .collect()
+    }
+
+    /// Store every final transcript received on `rx` under `meeting_id`,
+    /// until the channel closes.
+    pub fn record_transcripts(&self, meeting_id: &str, mut rx: broadcast::Receiver<TranscriptResult>) -> JoinHandle<()> {
+        let database = self.clone();
+        let meeting_id = meeting_id.to_string();
+
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(transcript) if transcript.is_final => {
+                        if let Err(e) = database.append_transcript(&meeting_id, &transcript).await {
+                            error!("Failed to store transcript: {}", e);
+                        }
+                    },
+                    Ok(_) => {},
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Transcript store fell behind, skipped {} results", skipped);
+                    },
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    pub async fn add_suggestion(&self, meeting_id: &str, suggestion_type: &str, content: &str, confidence: f32) -> Result<Suggestion> {
+        let suggestion = Suggestion {
+            id: uuid::Uuid::new_v4().to_string(),
+            meeting_id: meeting_id.to_string(),
+            suggestion_type: suggestion_type.to_string(),
+            content: content.to_string(),
+            confidence,
+            created_at: Utc::now(),
+        };
+        self.save_suggestion(&suggestion).await?;
+        Ok(suggestion)
+    }
+
+    /// Store a suggestion whose ID was handed out earlier, e.g. to the HUD
+    /// while it streamed in.
+    pub async fn save_suggestion(&self, suggestion: &Suggestion) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO suggestions (id, meeting_id, suggestion_type, content, confidence, created_at)
+             VALUES (?, ?, ?, ?, ?, ?)")
+            .bind(&suggestion.id)
+            .bind(&suggestion.meeting_id)
+            .bind(&suggestion.suggestion_type)
+            .bind(&suggestion.content)
+            .bind(suggestion.confidence)
+            .bind(timestamp(suggestion.created_at))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn suggestions(&self, meeting_id: &str) -> Result<Vec<Suggestion>> {
+        let rows = sqlx::query(
+            "SELECT id, meeting_id, suggestion_type, content, confidence, created_at
+             FROM suggestions WHERE meeting_id = ? ORDER BY created_at")
+            .bind(meeting_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter()
+            .map(|row| Ok(Suggestion {
+                id: row.try_get("id")?,
+                meeting_id: row.try_get("meeting_id")?,
+                suggestion_type: row.try_get("suggestion_type")?,
+                content: row.try_get("content")?,
+                confidence: row.try_get("confidence")?,
+                created_at: parse_time(row.try_get("created_at")?)?,
+            }))
+            .collect()
+    }
+
+    pub async fn add_action_item(&self, meeting_id: &str, text: &str, owner: Option<&str>) -> Result<ActionItem> {
+        let created_at = Utc::now();
+        let result = sqlx::query("INSERT INTO action_items (meeting_id, text, owner, created_at) VALUES (?, ?, ?, ?)")
+            .bind(meeting_id)
+            .bind(text)
+            .bind(owner)
+            .bind(timestamp(created_at))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(ActionItem {
+            id: result.last_insert_rowid(),
+            meeting_id: meeting_id.to_string(),
+            text: text.to_string(),
+            owner: owner.map(str::to_string),
+            done: false,
+            created_at,
+        })
+    }
+
+    pub async fn set_action_item_done(&self, action_item_id: i64, done: bool) -> Result<()> {
+        let result = sqlx::query("UPDATE action_items SET done = ? WHERE id = ?")
+            .bind(done)
+            .bind(action_item_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("No action item with id {}", action_item_id));
+        }
+        Ok(())
+    }
+
+    pub async fn action_items(&self, meeting_id: &str) -> Result<Vec<ActionItem>> {
+        let rows = sqlx::query(
+            "SELECT id, meeting_id, text, owner, done, created_at
+             FROM action_items WHERE meeting_id = ? ORDER BY id")
+            .bind(meeting_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter()
+            .map(|row| Ok(ActionItem {
+                id: row.try_get("id")?,
+                meeting_id: row.try_get("meeting_id")?,
+                text: row.try_get("text")?,
+                owner: row.try_get("owner")?,
+                done: row.try_get("done")?,
+                created_at: parse_time(row.try_get("created_at")?)?,
+            }))
+            .collect()
+    }
+}
+
+fn meeting_from_row(row: &SqliteRow) -> Result<Meeting> {
+    let ended_at: Option<String> = row.try_get("ended_at")?;
+    let participants: String = row.try_get("participants")?;
+    Ok(Meeting {
+        id: row.try_get("id")?,
+        title: row.try_get("title")?,
+        started_at: parse_time(row.try_get("started_at")?)?,
+        ended_at: ended_at.as_deref().map(parse_time).transpose()?,
+        meeting_type: row.try_get("meeting_type")?,
+        participants: serde_json::from_str(&participants)?,
+        summary: row.try_get("summary")?,
+    })
+}
+
+fn transcript_chunk_from_row(row: &SqliteRow) -> Result<TranscriptChunk> {
+    let utterance_id: i64 = row.try_get("utterance_id")?;
+    let words: String = row.try_get("words")?;
+    Ok(TranscriptChunk {
+        id: row.try_get("id")?,
+        meeting_id: row.try_get("meeting_id")?,
+        utterance_id: utterance_id as u64,
+        start_time: row.try_get("start_time")?,
+        end_time: row.try_get("end_time")?,
+        text: row.try_get("text")?,
+        speaker: row.try_get("speaker")?,
+        source: row.try_get("source")?,
+        source_role: SourceRole::from_name(row.try_get("source_role")?),
+        language: row.try_get("language")?,
+        confidence: row.try_get("confidence")?,
+        words: serde_json::from_str(&words)?,
+        created_at: parse_time(row.try_get("created_at")?)?,
+    })
+}
+
+// Build an FTS5 query from plain text: every word quoted so punctuation
+// can't be read as query syntax, and the last one matched as a prefix.
+// `None` when there is nothing to search for.
+fn match_expression(query: &str) -> Option<String> {
+    let words: Vec<&str> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let (last, rest) = words.split_last()?;
+
+    let mut terms: Vec<String> = rest.iter().map(|word| format!("\"{}\"", word)).collect();
+    terms.push(format!("\"{}\"*", last));
+    Some(terms.join(" "))
+}
+
+// Fixed-width UTC timestamps, so stored times also sort and compare as text
+pub(crate) fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)?.with_timezone(&Utc))
+}
+
+/// Start of every unencrypted SQLite file.
+const PLAINTEXT_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+fn is_plaintext(path: &Path) -> Result<bool> {
+    let mut header = [0u8; 16];
+    let mut file = std::fs::File::open(path)?;
+    Ok(file.read_exact(&mut header).is_ok() && &header == PLAINTEXT_HEADER)
+}
+
+// Copies a database from before encryption into an encrypted file and swaps
+// it in; the plain file is only replaced once the copy is complete
+async fn encrypt_plaintext(path: &str, key: &DatabaseKey) -> Result<()> {
+    let encrypted = format!("{}.encrypting", path);
+    let _ = std::fs::remove_file(&encrypted);
+
+    // Attached files are opened with the same flags, so this creates the copy
+    let mut connection = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true)
+        .connect()
+        .await?;
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&mut connection).await?;
+    sqlx::query(&format!("ATTACH DATABASE ? AS encrypted KEY {}", key.pragma()))
+        .bind(&encrypted)
+        .execute(&mut connection)
+        .await?;
+    sqlx::query("SELECT sqlcipher_export('encrypted')").fetch_all(&mut connection).await?;
+    sqlx::query("DETACH DATABASE encrypted").execute(&mut connection).await?;
+    connection.close().await?;
+
+    for suffix in ["-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+    }
+    std::fs::rename(&encrypted, path)?;
+    warn!("Encrypted existing unencrypted database: {}", path);
+    Ok(())
+}
+
+// SQLCipher reports a wrong key as SQLITE_NOTADB once the first page fails
+// to decrypt
+fn is_wrong_key(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .and_then(|e| e.code())
+        .is_some_and(|code| code == "26")
+}
+
+fn open_error(path: &str, e: sqlx::Error) -> anyhow::Error {
+    if is_wrong_key(&e) {
+        anyhow::anyhow!("Cannot decrypt database {}: the key is wrong or the file is not a meeting database", path)
+    } else {
+        anyhow::anyhow!("Failed to open database {}: {}", path, e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rag::{Chunk, DocumentInfo, EmbedderKind, VectorStore};
+
+    fn final_transcript(utterance_id: u64, start_time: f32, text: &str) -> TranscriptResult {
+        TranscriptResult {
+            text: text.to_string(),
+            confidence: 0.9,
+            start_time,
+            end_time: start_time + 1.0,
+            language: "en".to_string(),
+            words: vec![WordTiming {
+                word: text.split_whitespace().next().unwrap_or_default().to_string(),
+                start_time,
+                end_time: start_time + 0.2,
+                probability: 0.9,
+            }],
+            utterance_id,
+            speaker: Some("Speaker 1".to_string()),
+            source: "loopback".to_string(),
+            source_role: SourceRole::Them,
+            is_final: true,
+            ..TranscriptResult::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn transcript_returns_finals_in_spoken_order() {
+        let database = Database::in_memory().await.unwrap();
+        let meeting = database.create_meeting("Planning").await.unwrap();
+        let other = database.create_meeting("Other").await.unwrap();
+
+        database.append_transcript(&meeting.id, &final_transcript(2, 4.0, "ship it friday")).await.unwrap();
+        database.append_transcript(&meeting.id, &final_transcript(1, 1.5, "where are we")).await.unwrap();
+        database.append_transcript(&other.id, &final_transcript(3, 0.0, "unrelated")).await.unwrap();
+
+        let transcript = database.transcript(&meeting.id).await.unwrap();
+        let texts: Vec<&str> = transcript.iter().map(|chunk| chunk.text.as_str()).collect();
+        assert_eq!(texts, ["where are we", "ship it friday"]);
+
+        let first = &transcript[0];
+        assert_eq!(first.meeting_id, meeting.id);
+        assert_eq!(first.utterance_id, 1);
+        assert_eq!(first.start_time, 1.5);
+        assert_eq!(first.end_time, 2.5);
+        assert_eq!(first.speaker.as_deref(), Some("Speaker 1"));
+        assert_eq!(first.source, "loopback");
+        assert_eq!(first.source_role, SourceRole::Them);
+        assert_eq!(first.language, "en");
+        assert_eq!(first.words.len(), 1);
+        assert_eq!(first.words[0].word, "where");
+    }
+
+    #[tokio::test]
+    async fn partial_transcripts_are_rejected() {
+        let database = Database::in_memory().await.unwrap();
+        let meeting = database.create_meeting("Planning").await.unwrap();
+
+        let mut partial = final_transcript(1, 0.0, "where are");
+        partial.is_final = false;
+        assert!(database.append_transcript(&meeting.id, &partial).await.is_err());
+        assert!(database.transcript(&meeting.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn end_meeting_sets_end_time_once() {
+        let database = Database::in_memory().await.unwrap();
+        let meeting = database.create_meeting("Planning").await.unwrap();
+        let stored = database.meeting(&meeting.id).await.unwrap().unwrap();
+        assert!(stored.ended_at.is_none());
+
+        database.end_meeting(&meeting.id).await.unwrap();
+        let ended_at = database.meeting(&meeting.id).await.unwrap().unwrap().ended_at;
+        assert!(ended_at.is_some_and(|ended_at| ended_at >= stored.started_at));
+
+        // Ending again keeps the first end time
+        database.end_meeting(&meeting.id).await.unwrap();
+        assert_eq!(database.meeting(&meeting.id).await.unwrap().unwrap().ended_at, ended_at);
+    }
+
+    // Backdate a meeting so retention policies see it as old
+    async fn started_days_ago(database: &Database, meeting_id: &str, days: i64) {
+        sqlx::query("UPDATE meetings SET started_at = ? WHERE id = ?")
+            .bind(timestamp(Utc::now() - chrono::Duration::days(days)))
+            .bind(meeting_id)
+            .execute(database.pool())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn default_retention_keeps_everything() {
+        let database = Database::in_memory().await.unwrap();
+        let meeting = database.create_meeting("Planning").await.unwrap();
+        database.append_transcript(&meeting.id, &final_transcript(1, 0.0, "where are we")).await.unwrap();
+        database.add_suggestion(&meeting.id, "response", "Ask for the date", 0.8).await.unwrap();
+        started_days_ago(&database, &meeting.id, 3650).await;
+
+        let report = database.apply_retention(&RetentionSettings::default(), None).await.unwrap();
+        assert!(report.is_empty());
+        assert_eq!(database.transcript(&meeting.id).await.unwrap().len(), 1);
+        assert_eq!(database.suggestions(&meeting.id).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retention_deletes_expired_transcripts_but_spares_the_active_meeting() {
+        let database = Database::in_memory().await.unwrap();
+        let old = database.create_meeting("Old").await.unwrap();
+        let active = database.create_meeting("Active").await.unwrap();
+        for meeting in [&old, &active] {
+            database.append_transcript(&meeting.id, &final_transcript(1, 0.0, "where are we")).await.unwrap();
+            started_days_ago(&database, &meeting.id, 10).await;
+        }
+
+        let mut retention = RetentionSettings::default();
+        retention.default.transcript_days = Some(7);
+        let report = database.apply_retention(&retention, Some(&active.id)).await.unwrap();
+        assert_eq!(report.transcript_chunks, 1);
+        assert!(database.transcript(&old.id).await.unwrap().is_empty());
+        assert_eq!(database.transcript(&active.id).await.unwrap().len(), 1);
+        // Only the transcript was due; the meeting itself stays
+        assert!(database.meeting(&old.id).await.unwrap().is_some());
+    }
+
+    async fn meeting_with_transcript(database: &Database, title: &str, lines: &[&str]) -> Meeting {
+        let meeting = database.create_meeting(title).await.unwrap();
+        for (i, line) in lines.iter().enumerate() {
+            database.append_transcript(&meeting.id, &final_transcript(i as u64 + 1, i as f32 * 5.0, line)).await.unwrap();
+        }
+        meeting
+    }
+
+    fn hit_titles(hits: &[SearchHit]) -> Vec<&str> {
+        hits.iter().map(|hit| hit.meeting_title.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn search_matches_the_last_word_as_a_prefix_and_marks_snippets() {
+        let database = Database::in_memory().await.unwrap();
+        let meeting = meeting_with_transcript(&database, "Planning", &[
+            "hello everyone",
+            "the quarterly forecast looks strong",
+        ]).await;
+        let filter = SearchFilter::default();
+
+        let hits = database.search_transcripts("quarterly fore", &filter, 10).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        let hit = &hits[0];
+        assert_eq!(hit.meeting_id, meeting.id);
+        assert_eq!(hit.text, "the quarterly forecast looks strong");
+        assert_eq!(hit.snippet, "the <mark>quarterly</mark> <mark>forecast</mark> looks strong");
+        assert_eq!(hit.start_time, 5.0);
+        assert_eq!(hit.spoken_at, hit.meeting_started_at + chrono::Duration::seconds(5));
+        assert!(hit.score > 0.0);
+
+        // Only the last word may be incomplete; punctuation is ignored
+        assert!(database.search_transcripts("quarter forecast", &filter, 10).await.unwrap().is_empty());
+        assert_eq!(database.search_transcripts("\"Forecast\"!", &filter, 10).await.unwrap().len(), 1);
+        assert!(database.search_transcripts(" -*- ", &filter, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn search_ranks_better_matches_first_and_limits_results() {
+        let database = Database::in_memory().await.unwrap();
+        meeting_with_transcript(&database, "Passing", &[
+            "we touched on the budget briefly before moving on to hiring plans and travel",
+        ]).await;
+        meeting_with_transcript(&database, "Focused", &["budget budget budget"]).await;
+
+        let hits = database.search_transcripts("budget", &SearchFilter::default(), 10).await.unwrap();
+        assert_eq!(hit_titles(&hits), ["Focused", "Passing"]);
+        assert!(hits[0].score > hits[1].score);
+
+        let hits = database.search_transcripts("budget", &SearchFilter::default(), 1).await.unwrap();
+        assert_eq!(hit_titles(&hits), ["Focused"]);
+    }
+
+    #[tokio::test]
+    async fn search_filters_by_start_date() {
+        let database = Database::in_memory().await.unwrap();
+        for (title, days) in [("Recent", 1), ("Last week", 10), ("Last month", 30)] {
+            let meeting = meeting_with_transcript(&database, title, &["budget review"]).await;
+            started_days_ago(&database, &meeting.id, days).await;
+        }
+
+        let now = Utc::now();
+        let filter = SearchFilter {
+            from: Some(now - chrono::Duration::days(15)),
+            to: Some(now - chrono::Duration::days(5)),
+            ..SearchFilter::default()
+        };
+        let hits = database.search_transcripts("budget", &filter, 10).await.unwrap();
+        assert_eq!(hit_titles(&hits), ["Last week"]);
+
+        let filter = SearchFilter { from: Some(now - chrono::Duration::days(15)), ..SearchFilter::default() };
+        let hits = database.search_transcripts("budget", &filter, 10).await.unwrap();
+        let mut titles = hit_titles(&hits);
+        titles.sort();
+        assert_eq!(titles, ["Last week", "Recent"]);
+    }
+
+    #[tokio::test]
+    async fn search_filters_by_participant_and_meeting_type_ignoring_case() {
+        let database = Database::in_memory().await.unwrap();
+        let standup = meeting_with_transcript(&database, "Standup", &["budget review"]).await;
+        database.set_meeting_details(&standup.id, "Standup", &["Dana Scully".to_string()]).await.unwrap();
+        let review = meeting_with_transcript(&database, "Review", &["budget review"]).await;
+        database.set_meeting_details(&review.id, "Review", &["Fox Mulder".to_string()]).await.unwrap();
+
+        let by_participant = |names: &[&str]| SearchFilter {
+            participants: names.iter().map(|name| name.to_string()).collect(),
+            ..SearchFilter::default()
+        };
+        let hits = database.search_transcripts("budget", &by_participant(&["dana scully"]), 10).await.unwrap();
+        assert_eq!(hit_titles(&hits), ["Standup"]);
+        let hits = database.search_transcripts("budget", &by_participant(&[" FOX MULDER ", "Walter Skinner"]), 10).await.unwrap();
+        assert_eq!(hit_titles(&hits), ["Review"]);
+        assert!(database.search_transcripts("budget", &by_participant(&["Dana"]), 10).await.unwrap().is_empty());
+
+        let by_type = |meeting_type: &str| SearchFilter {
+            meeting_type: Some(meeting_type.to_string()),
+            ..SearchFilter::default()
+        };
+        let hits = database.search_transcripts("budget", &by_type("STANDUP"), 10).await.unwrap();
+        assert_eq!(hit_titles(&hits), ["Standup"]);
+        // An empty type doesn't filter
+        assert_eq!(database.search_transcripts("budget", &by_type(""), 10).await.unwrap().len(), 2);
+    }
+
+    async fn count(database: &Database, sql: &str) -> i64 {
+        sqlx::query_scalar(sql).fetch_one(database.pool()).await.unwrap()
+    }
+
+    // A transcript, a suggestion, an action item, a summary and an indexed
+    // chunk of the transcript, all mentioning `word`
+    async fn record_meeting(database: &Database, title: &str, word: &str) -> Meeting {
+        let meeting = database.create_meeting(title).await.unwrap();
+        let text = format!("the {} is due friday", word);
+        database.append_transcript(&meeting.id, &final_transcript(1, 0.0, &text)).await.unwrap();
+        database.add_suggestion(&meeting.id, "response", &format!("Ask who owns the {}", word), 0.8).await.unwrap();
+        database.add_action_item(&meeting.id, &format!("Send the {}", word), Some("Dana")).await.unwrap();
+        database.save_summary(&meeting.id, &format!("Agreed on the {}", word)).await.unwrap();
+
+        let store = VectorStore::new(database, EmbedderKind::default().build().unwrap());
+        let document = DocumentInfo {
+            id: format!("meeting-{}", meeting.id),
+            source: format!("meeting:{}", meeting.id),
+            meeting_id: Some(meeting.id.clone()),
+        };
+        store.insert(&document, &[Chunk { content: text, start_offset: 0 }]).await.unwrap();
+        meeting
+    }
+
+    async fn transcript_search_hits(database: &Database, word: &str) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM transcript_search WHERE transcript_search MATCH ?")
+            .bind(word)
+            .fetch_one(database.pool())
+            .await
+            .unwrap()
+    }
+
+    async fn rag_search_hits(database: &Database, word: &str) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM rag_search WHERE rag_search MATCH ?")
+            .bind(word)
+            .fetch_one(database.pool())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn forgetting_a_meeting_removes_it_from_every_index() {
+        let database = Database::in_memory().await.unwrap();
+        let forgotten = record_meeting(&database, "Forgotten", "budget").await;
+        let kept = record_meeting(&database, "Kept", "roadmap").await;
+
+        database.forget_meeting(&forgotten.id).await.unwrap();
+
+        assert!(database.meeting(&forgotten.id).await.unwrap().is_none());
+        assert_eq!(transcript_search_hits(&database, "budget").await, 0);
+        assert_eq!(rag_search_hits(&database, "budget").await, 0);
+        assert_eq!(count(&database, "SELECT COUNT(*) FROM transcript_chunks").await, 1);
+        assert_eq!(count(&database, "SELECT COUNT(*) FROM rag_chunks").await, 1);
+        assert_eq!(count(&database, "SELECT COUNT(*) FROM suggestions").await, 1);
+        assert_eq!(count(&database, "SELECT COUNT(*) FROM action_items").await, 1);
+        assert!(database.search_transcripts("budget", &SearchFilter::default(), 10).await.unwrap().is_empty());
+
+        // The other meeting is untouched
+        assert_eq!(transcript_search_hits(&database, "roadmap").await, 1);
+        assert_eq!(rag_search_hits(&database, "roadmap").await, 1);
+        assert!(database.forget_meeting(&forgotten.id).await.is_err());
+        assert!(database.meeting(&kept.id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn expired_transcripts_leave_summary_and_action_items() {
+        let database = Database::in_memory().await.unwrap();
+        let meeting = record_meeting(&database, "Planning", "budget").await;
+        started_days_ago(&database, &meeting.id, 10).await;
+
+        let mut retention = RetentionSettings::default();
+        retention.default.transcript_days = Some(7);
+        let report = database.apply_retention(&retention, None).await.unwrap();
+        assert_eq!(report.transcript_chunks, 1);
+        assert_eq!(report.rag_chunks, 1);
+        assert_eq!(report.suggestions + report.action_items + report.meetings, 0);
+
+        // What was said is gone from every index
+        assert!(database.transcript(&meeting.id).await.unwrap().is_empty());
+        assert_eq!(transcript_search_hits(&database, "budget").await, 0);
+        assert_eq!(count(&database, "SELECT COUNT(*) FROM rag_chunks").await, 0);
+        assert_eq!(rag_search_hits(&database, "budget").await, 0);
+
+        // What came out of it stays
+        let stored = database.meeting(&meeting.id).await.unwrap().unwrap();
+        assert_eq!(stored.summary.as_deref(), Some("Agreed on the budget"));
+        assert_eq!(database.action_items(&meeting.id).await.unwrap().len(), 1);
+        assert_eq!(database.suggestions(&meeting.id).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn expired_meeting_is_forgotten_whole() {
+        let database = Database::in_memory().await.unwrap();
+        let standup = record_meeting(&database, "Standup", "budget").await;
+        database.set_meeting_details(&standup.id, "Standup", &[]).await.unwrap();
+        let review = record_meeting(&database, "Review", "roadmap").await;
+        for meeting in [&standup, &review] {
+            started_days_ago(&database, &meeting.id, 40).await;
+        }
+
+        // Only standups expire; the type is matched ignoring case
+        let mut retention = RetentionSettings::default();
+        retention.meeting_types.insert("standup".to_string(), RetentionPolicy {
+            meeting_days: Some(30),
+            ..RetentionPolicy::default()
+        });
+        let report = database.apply_retention(&retention, None).await.unwrap();
+        assert_eq!(report.meetings, 1);
+
+        assert!(database.meeting(&standup.id).await.unwrap().is_none());
+        assert!(database.action_items(&standup.id).await.unwrap().is_empty());
+        assert_eq!(transcript_search_hits(&database, "budget").await, 0);
+        assert_eq!(rag_search_hits(&database, "budget").await, 0);
+        assert!(database.meeting(&review.id).await.unwrap().is_some());
+        assert_eq!(transcript_search_hits(&database, "roadmap").await, 1);
+    }
+
+    fn database_path(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("storage-{}", uuid::Uuid::new_v4()));
+        dir.join(name).to_string_lossy().into_owned()
+    }
+
+    // Raw keys in files skip the passphrase key derivation, which is slow
+    fn key_file(path: &str, name: &str) -> KeySource {
+        KeySource::KeyFile(Path::new(path).with_file_name(name))
+    }
+
+    async fn meeting_titles(database: &Database) -> Vec<String> {
+        sqlx::query_scalar("SELECT title FROM meetings ORDER BY title")
+            .fetch_all(database.pool())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn database_is_encrypted_and_reopens_with_its_key() {
+        let path = database_path("meetings.db");
+        let keys = key_file(&path, "database.key");
+        let database = Database::new(&path, &keys).await.unwrap();
+        database.create_meeting("Planning").await.unwrap();
+        database.pool().close().await;
+
+        assert!(!is_plaintext(Path::new(&path)).unwrap());
+        let database = Database::new(&path, &keys).await.unwrap();
+        assert_eq!(meeting_titles(&database).await, ["Planning"]);
+    }
+
+    #[tokio::test]
+    async fn wrong_or_missing_key_is_an_error() {
+        let path = database_path("meetings.db");
+        let database = Database::new(&path, &key_file(&path, "database.key")).await.unwrap();
+        database.create_meeting("Planning").await.unwrap();
+        database.pool().close().await;
+
+        let other = key_file(&path, "other.key");
+        other.load(true).unwrap();
+        let error = Database::new(&path, &other).await.err().unwrap().to_string();
+        assert!(error.contains("Cannot decrypt"), "{}", error);
+
+        // An existing database never gets a new key file
+        let missing = Path::new(&path).with_file_name("missing.key");
+        assert!(Database::new(&path, &KeySource::KeyFile(missing.clone())).await.is_err());
+        assert!(!missing.exists());
+    }
+
+    #[tokio::test]
+    async fn plaintext_database_is_encrypted_in_place() {
+        let path = database_path("meetings.db");
+        std::fs::create_dir_all(Path::new(&path).parent().unwrap()).unwrap();
+        let options = SqliteConnectOptions::new().filename(&path).create_if_missing(true);
+        let pool = SqlitePoolOptions::new().connect_with(options).await.unwrap();
+        let plain = Database::migrate(pool).await.unwrap();
+        plain.create_meeting("Before encryption").await.unwrap();
+        plain.pool().close().await;
+        assert!(is_plaintext(Path::new(&path)).unwrap());
+
+        let keys = key_file(&path, "database.key");
+        let database = Database::new(&path, &keys).await.unwrap();
+        assert_eq!(meeting_titles(&database).await, ["Before encryption"]);
+        database.pool().close().await;
+
+        assert!(!is_plaintext(Path::new(&path)).unwrap());
+        assert!(!Path::new(&format!("{}.encrypting", path)).exists());
+        let database = Database::new(&path, &keys).await.unwrap();
+        assert_eq!(meeting_titles(&database).await, ["Before encryption"]);
+    }
+
+    #[tokio::test]
+    async fn rotated_key_replaces_the_old_one() {
+        let path = database_path("meetings.db");
+        let keys = key_file(&path, "database.key");
+        let database = Database::new(&path, &keys).await.unwrap();
+        database.create_meeting("Planning").await.unwrap();
+        let old = key_file(&path, "old.key");
+        old.stage(&keys.load(false).unwrap()).unwrap();
+        old.commit().unwrap();
+
+        let new = keys.next_key(None).unwrap();
+        database.rotate_key(&keys, new.clone()).await.unwrap();
+        assert_eq!(keys.load(false).unwrap(), new);
+        // Every pooled connection is on the new key
+        let mut connections = Vec::new();
+        for _ in 0..database.pool().options().get_max_connections() {
+            connections.push(database.pool().acquire().await.unwrap());
+        }
+        for connection in &mut connections {
+            let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM meetings")
+                .fetch_one(&mut **connection)
+                .await
+                .unwrap();
+            assert_eq!(count, 1);
+        }
+        drop(connections);
+        database.pool().close().await;
+
+        assert!(Database::new(&path, &old).await.is_err());
+        let database = Database::new(&path, &keys).await.unwrap();
+        assert_eq!(meeting_titles(&database).await, ["Planning"]);
+    }
+
+    #[tokio::test]
+    async fn interrupted_rotation_is_finished_on_open() {
+        let path = database_path("meetings.db");
+        let key_path = Path::new(&path).with_file_name("database.key");
+        let keys = KeySource::KeyFile(key_path.clone());
+        let database = Database::new(&path, &keys).await.unwrap();
+        database.create_meeting("Planning").await.unwrap();
+        let old = keys.load(false).unwrap();
+
+        // The database switched to the new key, but the app stopped before
+        // the staged key file replaced the old one
+        let backup = key_path.with_file_name("database.key.old");
+        std::fs::copy(&key_path, &backup).unwrap();
+        let new = keys.next_key(None).unwrap();
+        database.rotate_key(&keys, new.clone()).await.unwrap();
+        database.pool().close().await;
+        std::fs::rename(&key_path, key_path.with_file_name("database.key.new")).unwrap();
+        std::fs::rename(&backup, &key_path).unwrap();
+        assert_eq!(keys.load(false).unwrap(), old);
+        assert_eq!(keys.pending().unwrap(), Some(new.clone()));
+
+        let database = Database::new(&path, &keys).await.unwrap();
+        assert_eq!(meeting_titles(&database).await, ["Planning"]);
+        assert_eq!(keys.load(false).unwrap(), new);
+        assert_eq!(keys.pending().unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn staged_key_the_database_never_used_is_discarded() {
+        let path = database_path("meetings.db");
+        let keys = key_file(&path, "database.key");
+        let database = Database::new(&path, &keys).await.unwrap();
+        database.pool().close().await;
+        let current = keys.load(false).unwrap();
+
+        keys.stage(&keys.next_key(None).unwrap()).unwrap();
+        let database = Database::new(&path, &keys).await.unwrap();
+        assert!(meeting_titles(&database).await.is_empty());
+        assert_eq!(keys.load(false).unwrap(), current);
+        assert_eq!(keys.pending().unwrap(), None);
     }
 }
@@ -1,160 +1,345 @@
 use anyhow::Result;
-use std::sync::mpsc;
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, SampleFormat, SampleRate, StreamConfig};
-use tracing::{info, warn, error};
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::watch;
+use tracing::{info, error};
+use crate::detector::{rms, EnergyDetector, VoiceDetector};
+use crate::noise::{NoiseFloorConfig, NoiseFloorEstimator, VadLevels};
+use crate::queue::QueueSender;
+use crate::resample::{AudioConverter, Mixer, TARGET_SAMPLE_RATE};
+use crate::segment::{SegmenterConfig, UtteranceSegmenter};
+use crate::source::{AudioSource, MicrophoneSource, SourceRole};
 
 pub struct VadEngine {
-    threshold: f32,
+    detector: Box<dyn VoiceDetector>,
     frame_size: usize,
     sample_rate: u32,
-    device: Option<Device>,
+    sources: Vec<Box<dyn AudioSource>>,
+    source_name: String,
+    source_role: SourceRole,
+    utterance_ids: Option<Arc<AtomicU64>>,
+    control: Option<watch::Receiver<CaptureState>>,
+    segmenter_config: SegmenterConfig,
+    noise_config: NoiseFloorConfig,
+    levels: Arc<Mutex<VadLevels>>,
 }
 
+/// Requested state of capture, set by whoever controls the pipeline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureState {
+    Running,
+    /// Sources are closed, releasing the devices, until capture resumes.
+    Paused,
+    #[default]
+    Stopped,
+}
+
+// Frames from a source, tagged with the capture run that started it and its
+// index; `None` marks a source that has ended.
+type SourceFrame = (u64, usize, Option<AudioChunk>);
+
 #[derive(Debug, Clone)]
 pub struct AudioChunk {
     pub data: Vec<f32>,
     pub sample_rate: u32,
+    /// Interleaved channel count of `data`; 1 once converted by the VAD.
+    pub channels: u16,
     pub timestamp: std::time::Instant,
     pub has_voice: bool,
+    /// Seconds since the session started; set on utterances emitted by the VAD.
+    pub start_time: f32,
+    pub end_time: f32,
+    /// Utterance this audio belongs to; partial snapshots share the id of the
+    /// final chunk that follows them.
+    pub utterance_id: u64,
+    pub is_final: bool,
+    /// Set by the diarization stage that follows the VAD.
+    pub speaker_id: Option<u32>,
+    /// Name of the input the audio came from; set on utterances.
+    pub source: String,
+    pub source_role: SourceRole,
 }
 
 impl VadEngine {
     pub fn new() -> Result<Self> {
-        let host = cpal::default_host();
-        let device = host.default_input_device()
-            .ok_or_else(|| anyhow::anyhow!("No input device available"))?;
-        
-        info!("Audio device: {}", device.name().unwrap_or("Unknown".to_string()));
-        
-        Ok(Self {
-            threshold: 0.005, // Lowered threshold for better sensitivity
-            frame_size: 1024,
-            sample_rate: 16000, // Standard for Whisper
-            device: Some(device),
-        })
+        Ok(Self::with_source(Box::new(MicrophoneSource::new(1024))))
     }
     
-    pub fn detect_voice(&self, audio_data: &[f32]) -> bool {
-        if audio_data.is_empty() {
-            return false;
+    /// Run detection over frames from an arbitrary source, e.g. a file replay.
+    pub fn with_source(source: Box<dyn AudioSource>) -> Self {
+        Self::with_sources(vec![source])
+    }
+    
+    /// Mix several sources into one stream before detection.
+    pub fn with_sources(sources: Vec<Box<dyn AudioSource>>) -> Self {
+        Self {
+            detector: Box::new(EnergyDetector::new(0.005)), // Lowered threshold for better sensitivity
+            frame_size: 1024,
+            sample_rate: TARGET_SAMPLE_RATE, // Standard for Whisper
+            sources,
+            source_name: String::new(),
+            source_role: SourceRole::default(),
+            utterance_ids: None,
+            control: None,
+            segmenter_config: SegmenterConfig::default(),
+            noise_config: NoiseFloorConfig::default(),
+            levels: Arc::new(Mutex::new(VadLevels::default())),
         }
-        
-        // Calculate RMS energy
-        let rms = (audio_data.iter()
-            .map(|&sample| sample * sample)
-            .sum::<f32>() / audio_data.len() as f32)
-            .sqrt();
-        
-        // Simple spectral centroid for better voice detection
-        let mut spectral_energy = 0.0f32;
-        for i in 1..audio_data.len().min(512) {
-            spectral_energy += (audio_data[i] - audio_data[i-1]).abs();
+    }
+    
+    pub fn with_noise_config(mut self, config: NoiseFloorConfig) -> Self {
+        self.noise_config = config;
+        self
+    }
+    
+    /// Shared view of the current noise floor and speech threshold.
+    pub fn levels(&self) -> Arc<Mutex<VadLevels>> {
+        self.levels.clone()
+    }
+    
+    /// Swap the speech detector, e.g. for `DetectorKind::Silero`.
+    pub fn with_detector(mut self, detector: Box<dyn VoiceDetector>) -> Self {
+        self.detector = detector;
+        self
+    }
+    
+    pub fn with_segmenter_config(mut self, config: SegmenterConfig) -> Self {
+        self.segmenter_config = config;
+        self
+    }
+    
+    /// Tag every utterance with the input it was heard on.
+    pub fn with_label(mut self, name: &str, role: SourceRole) -> Self {
+        self.source_name = name.to_string();
+        self.source_role = role;
+        self
+    }
+    
+    /// Share utterance IDs with other engines feeding the same ASR stage.
+    pub fn with_utterance_ids(mut self, ids: Arc<AtomicU64>) -> Self {
+        self.utterance_ids = Some(ids);
+        self
+    }
+    
+    /// Follow pause, resume and stop requests; without a controller the
+    /// engine runs until its sources end. Dropping the controller stops it.
+    pub fn with_control(mut self, control: watch::Receiver<CaptureState>) -> Self {
+        self.control = Some(control);
+        self
+    }
+    
+    /// Run detection until the sources end, the consumer goes away or a stop
+    /// is requested. Sources are closed on return but stay with the engine,
+    /// so it can be started again.
+    pub async fn start_detection(&mut self, tx: QueueSender) -> Result<()> {
+        let mut sources = std::mem::take(&mut self.sources);
+        if sources.is_empty() {
+            return Err(anyhow::anyhow!("No audio source configured"));
         }
-        spectral_energy /= audio_data.len().min(512) as f32;
-        
-        let voice_detected = rms > self.threshold && spectral_energy > 0.001;
         
-        if voice_detected {
-            info!("Voice detected - RMS: {:.4}, Spectral: {:.4}", rms, spectral_energy);
-        }
+        let result = self.detect(&mut sources, tx).await;
         
-        voice_detected
+        sources.iter_mut().for_each(|source| source.stop());
+        self.sources = sources;
+        info!("Audio stream ended");
+        result
     }
     
-    pub async fn start_detection(&self, tx: mpsc::Sender<AudioChunk>) -> Result<()> {
-        let device = self.device.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No audio device configured"))?;
+    async fn detect(&mut self, sources: &mut [Box<dyn AudioSource>], tx: QueueSender) -> Result<()> {
+        let descriptions: Vec<String> = sources.iter().map(|source| source.describe()).collect();
+        info!("Audio source: {}, detector: {}", descriptions.join(" + "), self.detector.name());
         
-        let supported_configs = device.supported_input_configs()?;
-        info!("Supported audio configs: {:?}", supported_configs.collect::<Vec<_>>());
-        
-        // Try to get 16kHz mono config for Whisper compatibility
-        let config = device.default_input_config()?;
-        let sample_format = config.sample_format();
-        let channels = config.channels();
-        
-        info!("Audio config - Sample rate: {}, Channels: {}, Format: {:?}", 
-              config.sample_rate().0, channels, sample_format);
-        
-        let config: StreamConfig = StreamConfig {
-            channels,
-            sample_rate: SampleRate(16000), // Force 16kHz for Whisper
-            buffer_size: cpal::BufferSize::Fixed(self.frame_size as u32),
-        };
+        // Frames from every source are funnelled into one channel. The sender
+        // is kept so sources can be restarted after a pause, which is why
+        // the end of the stream is tracked per source
+        let (frame_tx, mut frame_rx) = unbounded_channel::<SourceFrame>();
+        let mut run = 0;
+        start_sources(sources, &frame_tx, run)?;
+        let mut ended = vec![false; sources.len()];
+        let mut paused = false;
+        let mut control = self.control.clone();
+        info!("Audio stream started successfully");
         
-        let tx_clone = tx.clone();
-        let threshold = self.threshold;
+        let mut converters: Vec<Option<AudioConverter>> = sources.iter().map(|_| None).collect();
+        let mut mixer = Mixer::new(sources.len());
+        let mut segmenter = UtteranceSegmenter::new(self.segmenter_config.clone(), self.sample_rate);
+        if let Some(ids) = &self.utterance_ids {
+            segmenter = segmenter.with_shared_ids(ids.clone());
+        }
+        let mut noise_floor = NoiseFloorEstimator::new(self.noise_config.clone());
+        let mut has_voice = false;
+        self.detector.reset();
+        info!("Calibrating noise floor for {} ms, keep quiet...", self.noise_config.calibration_ms);
         
-        let stream = match sample_format {
-            SampleFormat::F32 => {
-                device.build_input_stream(
-                    &config,
-                    move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                        let chunk = AudioChunk {
-                            data: data.to_vec(),
-                            sample_rate: 16000,
-                            timestamp: std::time::Instant::now(),
-                            has_voice: Self::detect_voice_static(data, threshold),
-                        };
-                        
-                        if chunk.has_voice {
-                            if let Err(e) = tx_clone.send(chunk) {
-                                error!("Failed to send audio chunk: {}", e);
+        // Runs until the source is exhausted, the consumer goes away or a
+        // stop is requested
+        while !ended.iter().all(|&ended| ended) {
+            tokio::select! {
+                state = next_state(&mut control) => match state {
+                    CaptureState::Stopped => break,
+                    CaptureState::Paused if !paused => {
+                        sources.iter_mut().for_each(|source| source.stop());
+                        paused = true;
+                        // Close the utterance in progress rather than
+                        // joining it to speech after the pause
+                        if let Some(utterance) = segmenter.flush() {
+                            if !self.emit(&tx, utterance) {
+                                return Ok(());
                             }
                         }
+                        info!("Audio capture paused");
                     },
-                    |err| error!("Audio input error: {}", err),
-                    None,
-                )?
-            },
-            SampleFormat::I16 => {
-                device.build_input_stream(
-                    &config,
-                    move |data: &[i16], _: &cpal::InputCallbackInfo| {
-                        let f32_data: Vec<f32> = data.iter()
-                            .map(|&sample| sample as f32 / i16::MAX as f32)
-                            .collect();
+                    CaptureState::Running if paused => {
+                        run += 1;
+                        start_sources(sources, &frame_tx, run)?;
+                        ended.fill(false);
+                        mixer = Mixer::new(sources.len());
+                        self.detector.reset();
+                        paused = false;
+                        info!("Audio capture resumed");
+                    },
+                    _ => {},
+                },
+                Some((frame_run, index, chunk)) = frame_rx.recv() => {
+                    // Frames still in flight from before a pause are stale
+                    if paused || frame_run != run {
+                        continue;
+                    }
+                    
+                    let data = match chunk {
+                        // An ended source must not hold back the others
+                        None => {
+                            ended[index] = true;
+                            mixer.finish(index)
+                        },
+                        Some(chunk) => {
+                            let mut data = chunk.data;
+                            if chunk.sample_rate != self.sample_rate || chunk.channels != 1 {
+                                let converter = &mut converters[index];
+                                if !converter.as_ref().is_some_and(|c| c.matches(chunk.sample_rate, chunk.channels)) {
+                                    info!("Converting {} Hz / {} ch input to {} Hz mono",
+                                          chunk.sample_rate, chunk.channels, self.sample_rate);
+                                    *converter = Some(AudioConverter::new(chunk.sample_rate, chunk.channels));
+                                }
+                                if let Some(converter) = converter.as_mut() {
+                                    data = converter.process(&data);
+                                }
+                            }
+                            
+                            if sources.len() > 1 {
+                                mixer.push(index, &data)
+                            } else {
+                                data
+                            }
+                        },
+                    };
+                    
+                    if data.is_empty() {
+                        continue;
+                    }
+                    
+                    let frame_rms = rms(&data);
+                    let frame_secs = data.len() as f32 / self.sample_rate as f32;
+                    
+                    if noise_floor.is_calibrating() {
+                        noise_floor.observe(frame_rms, frame_secs, false);
+                        if !noise_floor.is_calibrating() {
+                            info!("Noise floor calibrated - floor: {:.4}, threshold: {:.4}",
+                                  noise_floor.floor(), noise_floor.threshold());
+                        }
+                        // Calibration audio still goes to the segmenter as
+                        // silence, so utterance times match the recording
+                        has_voice = false;
+                    } else {
+                        self.detector.set_noise_threshold(noise_floor.threshold());
                         
-                        let chunk = AudioChunk {
-                            data: f32_data.clone(),
-                            sample_rate: 16000,
-                            timestamp: std::time::Instant::now(),
-                            has_voice: Self::detect_voice_static(&f32_data, threshold),
-                        };
+                        // Frames shorter than a detector window keep the previous decision
+                        let probabilities = self.detector.speech_probabilities(&data)?;
+                        if let Some(peak) = probabilities.iter().copied().reduce(f32::max) {
+                            has_voice = peak >= self.detector.threshold();
+                        }
                         
-                        if chunk.has_voice {
-                            if let Err(e) = tx_clone.send(chunk) {
-                                error!("Failed to send audio chunk: {}", e);
-                            }
+                        noise_floor.observe(frame_rms, frame_secs, has_voice);
+                    }
+                    self.publish_levels(&noise_floor, frame_rms, has_voice);
+                    
+                    for utterance in segmenter.push(&data, has_voice) {
+                        if utterance.is_final {
+                            info!("Utterance {:.2}s - {:.2}s", utterance.start_time, utterance.end_time);
                         }
-                    },
-                    |err| error!("Audio input error: {}", err),
-                    None,
-                )?
-            },
-            _ => return Err(anyhow::anyhow!("Unsupported sample format: {:?}", sample_format)),
-        };
-        
-        stream.play()?;
-        info!("Audio stream started successfully");
+                        if !self.emit(&tx, utterance) {
+                            return Ok(());
+                        }
+                    }
+                },
+            }
+        }
         
-        // Keep stream alive - in production this would be managed differently
-        tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
+        if let Some(utterance) = segmenter.flush() {
+            self.emit(&tx, utterance);
+        }
         
         Ok(())
     }
     
-    fn detect_voice_static(audio_data: &[f32], threshold: f32) -> bool {
-        if audio_data.is_empty() {
+    // Label an utterance with its input and queue it for ASR; false once the
+    // ASR stage has gone away.
+    fn emit(&self, tx: &QueueSender, mut utterance: AudioChunk) -> bool {
+        utterance.source = self.source_name.clone();
+        utterance.source_role = self.source_role;
+        if tx.send(utterance).is_err() {
+            error!("Failed to send audio chunk: ASR stage has stopped");
             return false;
         }
+        true
+    }
+    
+    fn publish_levels(&self, noise_floor: &NoiseFloorEstimator, last_rms: f32, has_voice: bool) {
+        if let Ok(mut levels) = self.levels.lock() {
+            *levels = VadLevels {
+                noise_floor: noise_floor.floor(),
+                threshold: noise_floor.threshold(),
+                last_rms,
+                calibrating: noise_floor.is_calibrating(),
+                has_voice,
+            };
+        }
+    }
+}
+
+// Start every source, forwarding its frames tagged with `run` and its index.
+// Sources already started are stopped again if a later one fails.
+fn start_sources(sources: &mut [Box<dyn AudioSource>], frame_tx: &UnboundedSender<SourceFrame>, run: u64) -> Result<()> {
+    for index in 0..sources.len() {
+        let (source_tx, mut source_rx) = unbounded_channel::<AudioChunk>();
+        if let Err(e) = sources[index].start(source_tx) {
+            sources[..index].iter_mut().for_each(|source| source.stop());
+            return Err(e);
+        }
         
-        let rms = (audio_data.iter()
-            .map(|&sample| sample * sample)
-            .sum::<f32>() / audio_data.len() as f32)
-            .sqrt();
-        
-        rms > threshold
+        let frame_tx = frame_tx.clone();
+        tokio::spawn(async move {
+            while let Some(chunk) = source_rx.recv().await {
+                if frame_tx.send((run, index, Some(chunk))).is_err() {
+                    return;
+                }
+            }
+            let _ = frame_tx.send((run, index, None));
+        });
+    }
+    Ok(())
+}
+
+// Resolves with the next requested state; never for engines without a
+// controller, and with `Stopped` once the controller is dropped.
+async fn next_state(control: &mut Option<watch::Receiver<CaptureState>>) -> CaptureState {
+    match control {
+        Some(control) => match control.changed().await {
+            Ok(()) => *control.borrow_and_update(),
+            Err(_) => CaptureState::Stopped,
+        },
+        None => std::future::pending().await,
     }
 }
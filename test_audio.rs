@@ -6,10 +6,32 @@ use tracing::{info, error};
 mod audio;
 #[path = "src/vad.rs"]
 mod vad;
+#[path = "src/detector.rs"]
+mod detector;
+#[path = "src/noise.rs"]
+mod noise;
 #[path = "src/asr.rs"]
 mod asr;
+#[path = "src/http_asr.rs"]
+mod http_asr;
+#[path = "src/source.rs"]
+mod source;
+#[path = "src/resample.rs"]
+mod resample;
+#[path = "src/segment.rs"]
+mod segment;
+#[path = "src/queue.rs"]
+mod queue;
+#[path = "src/diarize.rs"]
+mod diarize;
+#[path = "src/embedding.rs"]
+mod embedding;
 
 use audio::AudioPipeline;
+use asr::{AsrBackendKind, LanguageConfig};
+use detector::DetectorKind;
+use diarize::DiarizerConfig;
+use source::FileSource;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -18,8 +40,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     info!("Starting Meeting Copilot audio pipeline test...");
     
-    // Create and start audio pipeline
-    let mut pipeline = AudioPipeline::new()?;
+    // Replay a recording when one is given, e.g. `test_audio meeting.wav 4.0`,
+    // otherwise listen on the default microphone
+    let args: Vec<String> = std::env::args().collect();
+    let mut pipeline = match args.get(1) {
+        Some(path) => {
+            let speed = args.get(2).and_then(|s| s.parse().ok()).unwrap_or(1.0);
+            info!("Replaying audio file: {} ({}x)", path, speed);
+            AudioPipeline::with_source(Box::new(FileSource::new(path).with_speed(speed)))
+                .with_asr_backend(AsrBackendKind::from_env()?)
+                .with_language(LanguageConfig::from_env())
+                .with_diarizer(DiarizerConfig::from_env()?)
+                .with_detector(DetectorKind::from_env()?)
+        },
+        None => AudioPipeline::new()?,
+    };
     info!("Audio pipeline created successfully");
     
     let mut transcript_rx = pipeline.start_streaming().await?;
@@ -33,10 +68,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         tokio::select! {
             result = transcript_rx.recv() => {
                 match result {
-                    Ok(transcript) => {
-                        println!("📝 TRANSCRIPT: '{}' (confidence: {:.2})", 
+                    Ok(transcript) if transcript.is_final => {
+                        println!("📝 TRANSCRIPT [{}] {}: '{}' (confidence: {:.2})", 
+                                transcript.language,
+                                transcript.speaker.as_deref().unwrap_or("Unknown speaker"),
                                 transcript.text, transcript.confidence);
                     },
+                    Ok(transcript) => {
+                        println!("   … '{}' [{}]", transcript.stable_text, transcript.unstable_text);
+                    },
                     Err(e) => {
                         error!("Error receiving transcript: {}", e);
                         break;
@@ -50,7 +90,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
     
-    pipeline.stop();
+    pipeline.stop().await;
     info!("Audio pipeline test completed");
     
     Ok(())
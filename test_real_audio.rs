@@ -1,4 +1,5 @@
 use std::sync::{Arc, mpsc};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 use tokio::time::sleep;
 use tracing::{info, error};
@@ -7,11 +8,30 @@ use cpal::{SampleFormat, SampleRate, StreamConfig};
 
 #[path = "src/vad.rs"]
 mod vad;
+#[path = "src/detector.rs"]
+mod detector;
+#[path = "src/noise.rs"]
+mod noise;
 #[path = "src/asr.rs"]
 mod asr;
+#[path = "src/http_asr.rs"]
+mod http_asr;
+#[path = "src/source.rs"]
+mod source;
+#[path = "src/resample.rs"]
+mod resample;
+#[path = "src/segment.rs"]
+mod segment;
+#[path = "src/queue.rs"]
+mod queue;
+#[path = "src/diarize.rs"]
+mod diarize;
+#[path = "src/embedding.rs"]
+mod embedding;
 
 use vad::AudioChunk;
-use asr::WhisperEngine;
+use asr::{AsrBackendKind, LanguageConfig};
+use resample::{AudioConverter, TARGET_SAMPLE_RATE};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -28,10 +48,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     info!("✅ Audio device: {}", device.name().unwrap_or("Unknown".to_string()));
     
-    // Create ASR engine (currently mock, but responds to real audio)
-    let mut whisper = WhisperEngine::new("models/ggml-base.en.bin")?;
-    whisper.load_model().await?;
-    info!("✅ Transcription engine loaded (mock mode)");
+    // Create ASR backend (selected with ASR_BACKEND, mock by default)
+    let mut asr_backend = AsrBackendKind::from_env()?.build(&LanguageConfig::from_env())?;
+    asr_backend.load().await?;
+    info!("✅ Transcription engine loaded: {}", asr_backend.model_info());
     
     // Create channel for audio chunks
     let (audio_tx, audio_rx) = mpsc::channel::<AudioChunk>();
@@ -53,6 +73,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     let threshold = 0.005f32;
     
+    // Down-mix and resample device audio to the 16kHz mono Whisper expects
+    let mut f32_converter = AudioConverter::new(sample_rate, channels);
+    let mut i16_converter = AudioConverter::new(sample_rate, channels);
+    
+    // Every voiced buffer is transcribed on its own, so each gets a fresh id
+    let chunk_counter = Arc::new(AtomicU64::new(1));
+    
     // Build and start audio stream
     let stream = match sample_format {
         SampleFormat::F32 => {
@@ -64,12 +91,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         .sum::<f32>() / data.len() as f32)
                         .sqrt();
                     
+                    let converted = f32_converter.process(data);
+                    
                     if rms > threshold {
                         let chunk = AudioChunk {
-                            data: data.to_vec(),
-                            sample_rate,
+                            data: converted,
+                            sample_rate: TARGET_SAMPLE_RATE,
+                            channels: 1,
                             timestamp: std::time::Instant::now(),
                             has_voice: true,
+                            start_time: 0.0,
+                            end_time: 0.0,
+                            utterance_id: chunk_counter.fetch_add(1, Ordering::Relaxed),
+                            is_final: true,
+                            speaker_id: None,
+                            source: "microphone".to_string(),
+                            source_role: source::SourceRole::Mixed,
                         };
                         
                         if let Err(_) = audio_tx.send(chunk) {
@@ -94,12 +131,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         .sum::<f32>() / f32_data.len() as f32)
                         .sqrt();
                     
+                    let converted = i16_converter.process(&f32_data);
+                    
                     if rms > threshold {
                         let chunk = AudioChunk {
-                            data: f32_data,
-                            sample_rate,
+                            data: converted,
+                            sample_rate: TARGET_SAMPLE_RATE,
+                            channels: 1,
                             timestamp: std::time::Instant::now(),
                             has_voice: true,
+                            start_time: 0.0,
+                            end_time: 0.0,
+                            utterance_id: chunk_counter.fetch_add(1, Ordering::Relaxed),
+                            is_final: true,
+                            speaker_id: None,
+                            source: "microphone".to_string(),
+                            source_role: source::SourceRole::Mixed,
                         };
                         
                         if let Err(_) = audio_tx.send(chunk) {
@@ -118,16 +165,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("🔊 Audio stream started! Listening for voice...");
     
     // Process audio chunks and transcribe
-    let whisper_handle = tokio::spawn(async move {
+    let asr_handle = tokio::spawn(async move {
         while let Ok(chunk) = audio_rx.recv() {
             info!("🎵 Voice detected! Processing {} samples (RMS: {:.4})", 
                   chunk.data.len(), 
                   (chunk.data.iter().map(|&x| x*x).sum::<f32>() / chunk.data.len() as f32).sqrt());
             
-            match whisper.transcribe_chunk(&chunk).await {
+            match asr_backend.transcribe_chunk(&chunk).await {
                 Ok(Some(transcript)) => {
-                    println!("📝 TRANSCRIPT: '{}' (confidence: {:.2})", 
-                            transcript.text, transcript.confidence);
+                    println!("📝 TRANSCRIPT [{}]: '{}' (confidence: {:.2})", 
+                            transcript.language, transcript.text, transcript.confidence);
                 },
                 Ok(None) => {
                     info!("Audio chunk too short, skipping...");
@@ -145,7 +192,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // Clean up
     drop(stream);
-    whisper_handle.abort();
+    asr_handle.abort();
     info!("🛑 Test completed");
     
     Ok(())
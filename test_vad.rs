@@ -0,0 +1,93 @@
+use std::path::Path;
+use tracing::info;
+
+#[path = "src/vad.rs"]
+mod vad;
+#[path = "src/detector.rs"]
+mod detector;
+#[path = "src/noise.rs"]
+mod noise;
+#[path = "src/source.rs"]
+mod source;
+#[path = "src/resample.rs"]
+mod resample;
+#[path = "src/segment.rs"]
+mod segment;
+#[path = "src/queue.rs"]
+mod queue;
+
+use detector::{DetectorKind, DETECTOR_WINDOW};
+use resample::{AudioConverter, TARGET_SAMPLE_RATE};
+
+// Scores a recording frame by frame so VAD thresholds can be tuned offline.
+//
+//   test_vad meeting.wav [energy|silero:models/silero_vad.onnx] [labels.txt]
+//
+// Prints `time,probability` for every 32 ms window. When a labels file with
+// one `start end` pair (in seconds) per speech region is given, precision and
+// recall are reported across a sweep of thresholds instead.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt::init();
+
+    let args: Vec<String> = std::env::args().collect();
+    let wav_path = args.get(1).ok_or("usage: test_vad <audio> [detector] [labels]")?;
+
+    let kind = match args.get(2) {
+        Some(spec) => DetectorKind::parse(spec)?,
+        None => DetectorKind::Energy,
+    };
+
+    let (samples, sample_rate, channels) = source::read_audio_file(Path::new(wav_path))?;
+    let samples = AudioConverter::new(sample_rate, channels).process(&samples);
+
+    let mut detector = kind.build()?;
+    let probabilities = detector.speech_probabilities(&samples)?;
+    info!("Scored {} windows with the {} detector", probabilities.len(), detector.name());
+
+    let window_secs = DETECTOR_WINDOW as f32 / TARGET_SAMPLE_RATE as f32;
+
+    let Some(labels_path) = args.get(3) else {
+        println!("time,probability");
+        for (index, probability) in probabilities.iter().enumerate() {
+            println!("{:.3},{:.4}", index as f32 * window_secs, probability);
+        }
+        return Ok(());
+    };
+
+    let regions: Vec<(f32, f32)> = std::fs::read_to_string(labels_path)?
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace().map(|p| p.parse::<f32>());
+            match (parts.next(), parts.next()) {
+                (Some(Ok(start)), Some(Ok(end))) => Some((start, end)),
+                _ => None,
+            }
+        })
+        .collect();
+
+    let labels: Vec<bool> = (0..probabilities.len())
+        .map(|index| {
+            let center = (index as f32 + 0.5) * window_secs;
+            regions.iter().any(|&(start, end)| center >= start && center < end)
+        })
+        .collect();
+
+    println!("threshold,precision,recall");
+    for step in 1..20 {
+        let threshold = step as f32 * 0.05;
+        let (mut tp, mut fp, mut fn_) = (0u32, 0u32, 0u32);
+        for (&probability, &is_speech) in probabilities.iter().zip(&labels) {
+            match (probability >= threshold, is_speech) {
+                (true, true) => tp += 1,
+                (true, false) => fp += 1,
+                (false, true) => fn_ += 1,
+                (false, false) => {},
+            }
+        }
+        let precision = tp as f32 / (tp + fp).max(1) as f32;
+        let recall = tp as f32 / (tp + fn_).max(1) as f32;
+        println!("{:.2},{:.3},{:.3}", threshold, precision, recall);
+    }
+
+    Ok(())
+}